@@ -12,33 +12,44 @@
 use futures::TryFutureExt;
 extern crate bson;
 use lru::LruCache;
-use std::{num::NonZeroUsize, sync::Mutex};
+use rustc_hash::FxHashSet;
+use std::{num::NonZeroUsize, sync::{Condvar, Mutex}};
 use async_channel::{Receiver, Sender};
 use image::ImageReader;
 use gio::prelude::*;
 use glib::clone;
 use gtk::{gdk::{self, Texture}, gio, glib};
 use once_cell::sync::Lazy;
+use time::OffsetDateTime;
 use uuid::Uuid;
 use std::{
-    cell::OnceCell, fmt, fs::create_dir_all, path::PathBuf, rc::Rc, sync::{Arc, RwLock}
+    cell::{Cell, OnceCell}, fmt, fs::create_dir_all, path::PathBuf, rc::Rc, sync::{Arc, RwLock}, thread
 };
 
-use crate::{common::{DynamicPlaylist, SongInfo}, meta_providers::{get_provider, models::{ArtistMeta, Lyrics}}, utils::strip_filename_linux};
+use crate::{common::{DynamicPlaylist, SongInfo}, meta_providers::{get_provider, models::{ArtistMeta, Lyrics}, provider_order}, utils::strip_filename_linux};
 use crate::{
-    client::{BackgroundTask, MpdWrapper},
+    client::{BackgroundTask, ConnectionState, MpdWrapper},
     common::{AlbumInfo, ArtistInfo},
     meta_providers::{
-        models, prelude::*, utils::get_best_image, MetadataChain, ProviderMessage,
+        models, musicbrainz::{self, ReleaseCandidate}, prelude::*,
+        utils::{download_image, get_best_image}, MetadataChain, ProviderMessage,
     },
-    utils::{resize_convert_image, settings_manager},
+    utils::{meta_provider_settings, resize_convert_image, settings_manager},
 };
 
 use super::{
     CacheState,
+    PrefetchState,
+    prefetch,
     sqlite
 };
 
+/// How often to look for expired metadata to refresh in the background.
+const REVALIDATE_PERIOD_S: u32 = 300;
+/// How many expired albums (and artists) to refresh each time. Kept small so that
+/// revalidation trickles along without hogging the providers or their rate limits.
+const REVALIDATE_BATCH_SIZE: u32 = 10;
+
 static APP_CACHE_PATH: Lazy<PathBuf> =
     Lazy::new(|| {
         let mut res = glib::user_cache_dir();
@@ -70,6 +81,50 @@ pub fn get_new_image_paths() -> (PathBuf, PathBuf) {
     (path, thumbnail_path)
 }
 
+/// Name of an image file, without the path & query. Providers like Last.fm list the same
+/// image in several sizes, each in its own folder but under the same name.
+fn image_file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Drop repeated images, keeping their first occurrence. Sizes of the same image from the
+/// same source are counted as repeats too, of which the largest is kept.
+fn dedup_images(images: Vec<(String, models::ImageMeta)>) -> Vec<(String, models::ImageMeta)> {
+    let mut res: Vec<(String, models::ImageMeta)> = Vec::with_capacity(images.len());
+    for (source, image) in images {
+        if res.iter().any(|(_, kept)| kept.url == image.url) {
+            continue;
+        }
+        let resized = res.iter_mut().find(|(kept_source, kept)| {
+            *kept_source == source
+                && kept.size != image.size
+                && image_file_name(&kept.url) == image_file_name(&image.url)
+        });
+        match resized {
+            Some((_, kept)) => {
+                if image.size > kept.size {
+                    *kept = image;
+                }
+            }
+            None => res.push((source, image)),
+        }
+    }
+    res
+}
+
+/// A downloaded album art candidate, for the user to choose from. It's kept in a temporary
+/// file until then, so that it can be stored through Cache::set_cover().
+#[derive(Debug, Clone)]
+pub struct CoverCandidate {
+    /// Name of the provider that found it
+    pub source: String,
+    pub width: u32,
+    pub height: u32,
+    pub path: PathBuf,
+    pub texture: Texture,
+}
+
 #[derive(Default, Debug, Clone)]
 pub enum ImageAction {
     #[default]
@@ -141,6 +196,35 @@ fn clear_image_internal(key: &String, key_prefix: Option<&'static str>) -> Resul
     Ok(removed)
 }
 
+/// Load a user-provided image (playlist cover, station logo...) in the background.
+/// There is nothing to fetch automatically for these, so a missing one is simply None.
+fn load_user_image(key: &str, prefix: &'static str, thumbnail: bool) -> gio::JoinHandle<Option<gdk::Texture>> {
+    let key = key.to_owned();
+    gio::spawn_blocking(move || {
+        // First try to get from cache
+        if let Some(filename) = sqlite::find_image_by_key(&key, Some(prefix), thumbnail).expect("Sqlite DB error") {
+            if let Some(tex) = IMAGE_CACHE.lock().unwrap().get(&filename) {
+                // Cloning GObjects is cheap since they're just references
+                return Some(tex.clone());
+            }
+            // If missed, try loading from disk
+            let mut path = get_image_cache_path();
+            path.push(&filename);
+            if let Ok(tex) = Texture::from_filename(&path) {
+                IMAGE_CACHE.lock().unwrap().put(filename, tex.clone());
+                return Some(tex);
+            } else {
+                // File no longer exists (maybe user had removed it). Unregister it from DB.
+                // NO NEED TO REPEAT PROCESS. There is nothing to fetch automatically.
+                println!("User-provided image no longer present on disk. Unregistering...");
+                sqlite::unregister_image_key(key, Some(prefix), thumbnail)
+                    .join().unwrap().expect("Sqlite DB error");
+            }
+        }
+        None
+    })
+}
+
 // In-memory image cache.
 // gdk::Textures are GObjects, which by themselves are boxed reference-counted.
 // This means that even if a texture is evicted from this cache, as long as there
@@ -163,6 +247,8 @@ pub struct Cache {
     bg_sender: Sender<ProviderMessage>,
     meta_providers: Arc<RwLock<MetadataChain>>,
     state: CacheState,
+    // Whether a prefetch job thread is currently alive (regardless of its saved state)
+    prefetching: Rc<Cell<bool>>,
 }
 
 impl fmt::Debug for Cache {
@@ -172,13 +258,303 @@ impl fmt::Debug for Cache {
     }
 }
 
+/// Oldest fetch times for positive and negative (nothing found) metadata that are still
+/// considered fresh. None means that kind never expires.
+fn expiry_cutoffs() -> (Option<OffsetDateTime>, Option<OffsetDateTime>) {
+    let settings = settings_manager().child("metaprovider");
+    let now = OffsetDateTime::now_utc();
+    let cutoff = |key: &str| {
+        let days = settings.uint(key);
+        if days > 0 {
+            Some(now - time::Duration::days(days as i64))
+        } else {
+            None
+        }
+    };
+    (cutoff("meta-ttl-days"), cutoff("negative-ttl-days"))
+}
+
+/// Whether metadata with the given status (see sqlite::get_album_meta_status) should be
+/// fetched again. Missing metadata always should.
+fn is_meta_expired(status: Option<(OffsetDateTime, bool)>) -> bool {
+    let Some((last_modified, empty)) = status else {
+        return true;
+    };
+    let (positive, negative) = expiry_cutoffs();
+    let cutoff = if empty { negative } else { positive };
+    cutoff.is_some_and(|cutoff| last_modified < cutoff)
+}
+
+// Keys of the albums & artists whose metadata is currently being fetched. Several workers
+// (and the prefetcher) may be asked for the same one at once, for example when a cover
+// and the metadata of an album are requested together.
+static IN_FLIGHT: Lazy<(Mutex<FxHashSet<String>>, Condvar)> =
+    Lazy::new(|| (Mutex::new(FxHashSet::default()), Condvar::new()));
+
+/// Claim on a key in IN_FLIGHT, released when dropped.
+struct InFlight(String);
+
+impl InFlight {
+    /// Claim the given key, first waiting for any fetch already running for it to finish.
+    /// Also returns whether there was one.
+    fn claim(key: String) -> (Self, bool) {
+        let (lock, cvar) = &*IN_FLIGHT;
+        let mut keys = lock.lock().unwrap();
+        let mut waited = false;
+        while keys.contains(&key) {
+            waited = true;
+            keys = cvar.wait(keys).unwrap();
+        }
+        keys.insert(key.clone());
+        (Self(key), waited)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let (lock, cvar) = &*IN_FLIGHT;
+        lock.lock().unwrap().remove(&self.0);
+        cvar.notify_all();
+    }
+}
+
+/// Fetch an album's metadata through the provider chain, unless there's a fresh copy
+/// already (or overwrite is true). Concurrent fetches of the same album are coalesced
+/// into the first one, except for overwriting ones which run again afterwards. Blocking.
+pub(super) fn fetch_album_meta(
+    providers: &RwLock<MetadataChain>,
+    mut key: AlbumInfo,
+    overwrite: bool,
+    fg_sender: &Sender<ProviderMessage>,
+) {
+    // There must be enough information to store the result under
+    if key.mbid.is_none() && key.albumartist.is_none() {
+        return;
+    }
+    if !overwrite && !is_meta_expired(sqlite::get_album_meta_status(&key).ok().flatten()) {
+        return;
+    }
+    let folder_uri = key.folder_uri.to_owned();
+    let (_claim, waited) = InFlight::claim(format!("album:{folder_uri}"));
+    if waited && !overwrite {
+        // Whoever we waited for has just fetched it, and told the UI so. Explicit refreshes
+        // still go ahead, as that fetch might have started before whatever prompted them
+        // (such as a newly pinned release) and so be stale already.
+        return;
+    }
+    // Start from the release the user picked for this album, if any.
+    let pinned = sqlite::find_album_meta(&key)
+        .ok()
+        .flatten()
+        .and_then(|meta| meta.pinned_mbid);
+    let mut chain_key = key.clone();
+    if pinned.is_some() {
+        chain_key.mbid = pinned.clone();
+    }
+    let res = providers.read().unwrap().get_album_meta(&mut chain_key, None);
+    // Tagged albums stay stored under their own MBID, others under the one found for them.
+    if key.mbid.is_none() {
+        key.mbid = chain_key.mbid;
+    }
+    if let Some(mut album) = res {
+        album.pinned_mbid = pinned;
+        let _ = sqlite::write_album_meta(&key, &album);
+    }
+    else if let Some(old) = sqlite::find_album_meta(&key).ok().flatten().filter(|old| !old.is_empty()) {
+        // Keep what we had, as the providers might just be unreachable
+        // right now. Rewriting it resets its expiry.
+        println!("No album meta could be found for {}. Keeping existing document...", &folder_uri);
+        let _ = sqlite::write_album_meta(&key, &old);
+    }
+    else {
+        // Push an empty AlbumMeta to block further calls for this album until it expires.
+        println!("No album meta could be found for {}. Pushing empty document...", &folder_uri);
+        let mut empty = models::AlbumMeta::from_key(&key);
+        empty.pinned_mbid = pinned;
+        let _ = sqlite::write_album_meta(&key, &empty);
+    }
+    let _ = fg_sender.send_blocking(ProviderMessage::AlbumMetaAvailable(folder_uri));
+}
+
+/// Fetch an artist's metadata (and avatar, if there isn't one yet) through the provider
+/// chain, unless there's a fresh copy already (or overwrite is true). Concurrent fetches
+/// of the same artist are coalesced into the first one, except for overwriting ones which
+/// run again afterwards. Blocking.
+pub(super) fn fetch_artist_meta(
+    providers: &RwLock<MetadataChain>,
+    mut key: ArtistInfo,
+    overwrite: bool,
+    fg_sender: &Sender<ProviderMessage>,
+) {
+    if !overwrite && !is_meta_expired(sqlite::get_artist_meta_status(&key).ok().flatten()) {
+        return;
+    }
+    // Guaranteed to have this field so just unwrap it
+    let name = key.name.to_owned();
+    let (_claim, waited) = InFlight::claim(format!("artist:{name}"));
+    if waited && !overwrite {
+        // Whoever we waited for has just fetched it, and told the UI so. Explicit refreshes
+        // still go ahead, as that fetch might have started before whatever prompted them
+        // (such as a newly pinned release) and so be stale already.
+        return;
+    }
+    let res = providers.read().unwrap().get_artist_meta(&mut key, None);
+    if let Some(artist) = res {
+        sqlite::write_artist_meta(&key, &artist)
+            .expect("Unable to write downloaded artist meta");
+        if sqlite::find_image_by_key(&key.name, Some("avatar"), false).expect("Sqlite DB error").is_none() {
+            // Try to download artist avatar too
+            download_artist_avatar(&key, &artist, fg_sender);
+        }
+    }
+    else if let Some(old) = sqlite::find_artist_meta(&key).ok().flatten().filter(|old| !old.is_empty()) {
+        // Keep what we had, as the providers might just be unreachable
+        // right now. Rewriting it resets its expiry.
+        println!("No artist meta could be found for {:?}. Keeping existing document...", &key);
+        sqlite::write_artist_meta(&key, &old)
+            .expect("Unable to write downloaded artist meta");
+    }
+    else {
+        // Push an empty ArtistMeta to block further calls for this artist until it expires.
+        println!("No artist meta could be found for {:?}. Pushing empty document...", &key);
+        sqlite::write_artist_meta(&key, &models::ArtistMeta::from_key(&key))
+            .expect("Unable to write downloaded artist meta");
+    }
+    let _ = fg_sender.send_blocking(ProviderMessage::ArtistMetaAvailable(name));
+}
+
+/// Download the best of the images listed in an artist's metadata as their avatar.
+/// Returns whether one was saved. Blocking.
+pub(super) fn download_artist_avatar(
+    key: &ArtistInfo,
+    meta: &ArtistMeta,
+    fg_sender: &Sender<ProviderMessage>,
+) -> bool {
+    let res = get_best_image(&meta.image);
+    let (path, thumbnail_path) = get_new_image_paths();
+    if res.is_ok() {
+        let (hires, thumbnail) = resize_convert_image(res.unwrap());
+        if let (Ok(_), Ok(_)) = (
+            hires.save(&path),
+            thumbnail.save(&thumbnail_path)
+        ) {
+            // Wait for these to be written, so that callers checking for an avatar
+            // right after won't download it again.
+            let _ = sqlite::register_image_key(
+                key.name.clone(), Some("avatar"),
+                Some(path.file_name().unwrap().to_str().unwrap().to_string()), false
+            ).join();
+            let _ = sqlite::register_image_key(
+                key.name.clone(), Some("avatar"),
+                Some(thumbnail_path.file_name().unwrap().to_str().unwrap().to_string()), true
+            ).join();
+            let hires_tex = gdk::Texture::from_filename(&path).unwrap();
+            let thumbnail_tex = gdk::Texture::from_filename(&thumbnail_path).unwrap();
+            let _ = fg_sender.send_blocking(ProviderMessage::ArtistAvatarAvailable(key.name.clone(), false, hires_tex));
+            let _ = fg_sender.send_blocking(ProviderMessage::ArtistAvatarAvailable(key.name.clone(), true, thumbnail_tex));
+            return true;
+        }
+    }
+    else {
+        println!("[Cache] Failed to download artist avatar for {:?} (perhaps all providers were disabled)", res.err());
+    }
+    false
+}
+
+/// Fetch a folder cover from external providers, for folders that MPD has none for.
+/// Concurrent fetches of the same cover are coalesced into the first one.
+/// Returns whether one was found. Blocking.
+pub(super) fn fetch_folder_cover_externally(
+    providers: &RwLock<MetadataChain>,
+    album: AlbumInfo,
+    fg_sender: &Sender<ProviderMessage>,
+) -> bool {
+    let (_claim, waited) = InFlight::claim(format!("cover:{}", &album.folder_uri));
+    if waited {
+        return sqlite::find_image_by_key(&album.folder_uri, None, false)
+            .ok()
+            .flatten()
+            .is_some_and(|name| !name.is_empty());
+    }
+    // Fill out metadata before attempting to fetch album art from
+    // external sources. Done here rather than as a separate request
+    // since requests may be handled concurrently.
+    fetch_album_meta(providers, album.clone(), false, fg_sender);
+    if let Ok(Some(meta)) = sqlite::find_album_meta(&album) {
+        let res = get_best_image(&meta.image);
+        if res.is_ok() {
+            let (hires, thumbnail) = resize_convert_image(res.unwrap());
+            let (path, thumbnail_path) = get_new_image_paths();
+            if let (Ok(_), Ok(_)) = (
+                hires.save(&path),
+                thumbnail.save(&thumbnail_path)
+            ) {
+                let _ = sqlite::register_image_key(
+                    album.folder_uri.clone(), None,
+                    Some(path.file_name().unwrap().to_str().unwrap().to_string()), false
+                );
+                let _ = sqlite::register_image_key(
+                    album.folder_uri.clone(), None,
+                    Some(thumbnail_path.file_name().unwrap().to_str().unwrap().to_string()), true
+                );
+                let hires_tex = gdk::Texture::from_filename(&path).unwrap();
+                let thumbnail_tex = gdk::Texture::from_filename(&thumbnail_path).unwrap();
+                let _ = fg_sender.send_blocking(ProviderMessage::CoverAvailable(album.folder_uri.to_owned(), false, hires_tex));
+                let _ = fg_sender.send_blocking(ProviderMessage::CoverAvailable(album.folder_uri.to_owned(), true, thumbnail_tex));
+                return true;
+            }
+        }
+    }
+    // End of the road, still unable to find anything for this folder (or its songs).
+    println!("Cannot download folder cover for {} externally (perhaps all providers were disabled)", album.folder_uri);
+    // Write empty entries to prevent further (fruitless) lookups
+    let _ = sqlite::register_image_key(album.folder_uri.clone(), None, None, false);
+    let _ = sqlite::register_image_key(album.folder_uri.clone(), None, None, true);
+    let _ = fg_sender.send_blocking(ProviderMessage::CoverNotAvailable(album.folder_uri));
+    false
+}
+
+/// Fetch a song's lyrics through the provider chain. Returns whether any were found.
+/// Blocking.
+pub(super) fn fetch_lyrics(
+    providers: &RwLock<MetadataChain>,
+    key: SongInfo,
+    fg_sender: &Sender<ProviderMessage>,
+) -> bool {
+    let res = providers.read().unwrap().get_lyrics(&key);
+    if let Some(lyrics) = res {
+        sqlite::write_lyrics(&key, Some(&lyrics))
+                 .expect("Unable to write downloaded lyrics");
+        let _ = fg_sender.send_blocking(ProviderMessage::LyricsAvailable(key.uri));
+        true
+    }
+    else {
+        false
+    }
+}
+
+/// Spawn a thread for the prefetch job, unless one is still alive. Such a thread checks
+/// the saved state between items, so resuming a job that hasn't quite paused yet simply
+/// lets it carry on.
+fn spawn_prefetch(
+    providers: Arc<RwLock<MetadataChain>>,
+    fg_sender: Sender<ProviderMessage>,
+    prefetching: Rc<Cell<bool>>,
+) {
+    if prefetching.replace(true) {
+        return;
+    }
+    glib::spawn_future_local(async move {
+        let _ = gio::spawn_blocking(move || prefetch::run(providers, fg_sender)).await;
+        prefetching.set(false);
+    });
+}
+
 fn init_meta_provider_chain() -> MetadataChain {
     let mut providers = MetadataChain::new();
-    providers.providers = settings_manager()
-        .child("metaprovider")
-        .value("order")
-        .array_iter_str()
-        .unwrap()
+    providers.keys = provider_order();
+    providers.providers = providers.keys
+        .iter()
         .map(|key| get_provider(key))
         .collect();
     providers
@@ -201,11 +577,13 @@ impl Cache {
             fg_sender: fg_sender.clone(),
             bg_sender,
             state: CacheState::default(),
+            prefetching: Rc::new(Cell::new(false)),
         };
         let res = Rc::new(cache);
 
         res.clone()
             .setup_channel(bg_receiver, fg_sender, fg_receiver);
+        res.clone().setup_revalidation();
         res
     }
     /// Re-initialise list of providers when priority order is changed
@@ -215,182 +593,176 @@ impl Cache {
     }
 
     pub fn set_mpd_client(&self, client: Rc<MpdWrapper>) {
+        // Resume unfinished prefetch jobs (including those interrupted by a restart or a
+        // lost connection) once connected.
+        let providers = self.meta_providers.clone();
+        let fg_sender = self.fg_sender.clone();
+        let prefetching = self.prefetching.clone();
+        client.get_client_state().connect_notify_local(
+            Some("connection-state"),
+            move |state, _| {
+                if state.get_connection_state() == ConnectionState::Connected
+                    && PrefetchState::get() == PrefetchState::Running
+                {
+                    spawn_prefetch(providers.clone(), fg_sender.clone(), prefetching.clone());
+                }
+            },
+        );
         let _ = self.mpd_client.set(client);
     }
 
+    /// Prefetch metadata & covers for the whole library, starting over. Progress is
+    /// reported through the prefetch-* keys of the metaprovider settings.
+    pub fn start_prefetch(&self) {
+        let _ = sqlite::clear_prefetch_report();
+        let settings = settings_manager().child("metaprovider");
+        let _ = settings.set_string("prefetch-last-key", "");
+        let _ = settings.set_uint("prefetch-position", 0);
+        let _ = settings.set_uint("prefetch-total", 0);
+        PrefetchState::Running.set();
+        spawn_prefetch(self.meta_providers.clone(), self.fg_sender.clone(), self.prefetching.clone());
+    }
+
+    /// Stop the prefetch job after the items currently being fetched, keeping its position.
+    pub fn pause_prefetch(&self) {
+        if PrefetchState::get() == PrefetchState::Running {
+            PrefetchState::Paused.set();
+        }
+    }
+
+    pub fn resume_prefetch(&self) {
+        PrefetchState::Running.set();
+        spawn_prefetch(self.meta_providers.clone(), self.fg_sender.clone(), self.prefetching.clone());
+    }
+
+    /// Stop the prefetch job for good. Its report is kept.
+    pub fn cancel_prefetch(&self) {
+        PrefetchState::Idle.set();
+    }
+
     pub fn get_sender(&self) -> Sender<ProviderMessage> {
         self.fg_sender.clone()
     }
 
+    /// Periodically queue up a few albums & artists whose metadata have expired.
+    /// They go through the same queue as normal requests, so rate limits still apply.
+    fn setup_revalidation(self: Rc<Self>) {
+        glib::timeout_add_seconds_local(
+            REVALIDATE_PERIOD_S,
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || {
+                    this.revalidate_expired_meta();
+                    glib::ControlFlow::Continue
+                }
+            ),
+        );
+    }
+
+    fn revalidate_expired_meta(&self) {
+        let (positive, negative) = expiry_cutoffs();
+        if positive.is_none() && negative.is_none() {
+            return;
+        }
+        let bg_sender = self.bg_sender.clone();
+        gio::spawn_blocking(move || {
+            // Not overwriting, so entries that got refreshed in the meantime (or queued
+            // twice) are skipped by the expiry check.
+            for album in sqlite::get_stale_albums(positive, negative, REVALIDATE_BATCH_SIZE)
+                .unwrap_or_default()
+            {
+                let _ = bg_sender.send_blocking(ProviderMessage::AlbumMeta(album, false));
+            }
+            for artist in sqlite::get_stale_artists(positive, negative, REVALIDATE_BATCH_SIZE)
+                .unwrap_or_default()
+            {
+                let _ = bg_sender.send_blocking(ProviderMessage::ArtistMeta(artist, false));
+            }
+        });
+    }
+
     fn setup_channel(
         self: Rc<Self>,
         bg_receiver: Receiver<ProviderMessage>,
         fg_sender: Sender<ProviderMessage>,
         fg_receiver: Receiver<ProviderMessage>,
     ) {
-        // Handle remote metadata fetching tasks in another thread
+        // Handle remote metadata fetching tasks in other threads. Several workers pull
+        // from the same queue, so different albums, artists & songs are fetched concurrently.
+        // Each provider enforces its own rate limit, so this doesn't break any API policy.
         let providers = self.clone().meta_providers.clone();
-        glib::MainContext::default().spawn_local(
-            async move {
-                use futures::prelude::*;
-                // Allow receiver to be mutated, but keep it at the same memory address.
-                // See Receiver::next doc for why this is needed.
-                let mut receiver = std::pin::pin!(bg_receiver);
-
-                while let Some(request) = receiver.next().await {
-                    match request {
-                        ProviderMessage::AlbumMeta(mut key, overwrite) => {
-                            let _ = gio::spawn_blocking(clone!(
-                                #[strong]
-                                fg_sender,
-                                #[strong]
-                                providers,
-                                move || {
-                                    // Check whether there is one already
-                                    if key.mbid.is_some() || key.albumartist.is_some() {
-                                        let folder_uri = key.folder_uri.to_owned();
-                                        if overwrite || sqlite::find_album_meta(&key).ok().flatten().is_none() {
-                                            let res = providers.read().unwrap().get_album_meta(&mut key, None);
-                                            if let Some(album) = res {
-                                                let _ = sqlite::write_album_meta(&key, &album);
-                                            }
-                                            else {
-                                                // Push an empty AlbumMeta to block further calls for this album.
-                                                println!("No album meta could be found for {}. Pushing empty document...", &folder_uri);
-                                                let _ = sqlite::write_album_meta(&key, &models::AlbumMeta::from_key(&key));
-                                            }
-                                            let _ = fg_sender.send_blocking(ProviderMessage::AlbumMetaAvailable(folder_uri));
-                                            sleep_after_request();
-                                        }
-                                    }
-                                }
-                            )).await;
-                        },
-                        ProviderMessage::ArtistMeta(mut key, overwrite) => {
-                            let _ = gio::spawn_blocking(clone!(
-                                #[strong]
-                                fg_sender,
-                                #[strong]
-                                providers,
-                                move || {
-                                    // Check whether there is one already
-                                    if overwrite || sqlite::find_artist_meta(&key).ok().flatten().is_none() {
-                                        // Guaranteed to have this field so just unwrap it
-                                        let name = key.name.to_owned();
-                                        let res = providers.read().unwrap().get_artist_meta(&mut key, None);
-                                        if let Some(artist) = res {
-                                            sqlite::write_artist_meta(&key, &artist)
-                                                .expect("Unable to write downloaded artist meta");
-                                            if sqlite::find_image_by_key(&key.name, Some("avatar"), false).expect("Sqlite DB error").is_none() {
-                                                // Try to download artist avatar too
-                                                let res = get_best_image(&artist.image);
-                                                let (path, thumbnail_path) = get_new_image_paths();
-                                                if res.is_ok() {
-                                                    let (hires, thumbnail) = resize_convert_image(res.unwrap());
-                                                    if let (Ok(_), Ok(_)) = (
-                                                        hires.save(&path),
-                                                        thumbnail.save(&thumbnail_path)
-                                                    ) {
-                                                        let _ = sqlite::register_image_key(
-                                                            key.name.clone(), Some("avatar"),
-                                                            Some(path.file_name().unwrap().to_str().unwrap().to_string()), false
-                                                        );
-                                                        let _ = sqlite::register_image_key(
-                                                            key.name.clone(), Some("avatar"),
-                                                            Some(thumbnail_path.file_name().unwrap().to_str().unwrap().to_string()), true
-                                                        );
-                                                        let hires_tex = gdk::Texture::from_filename(&path).unwrap();
-                                                        let thumbnail_tex = gdk::Texture::from_filename(&thumbnail_path).unwrap();
-                                                        let _ = fg_sender.send_blocking(ProviderMessage::ArtistAvatarAvailable(name.clone(), false, hires_tex));
-                                                        let _ = fg_sender.send_blocking(ProviderMessage::ArtistAvatarAvailable(name.clone(), true, thumbnail_tex));
-                                                    }
-                                                }
-                                                else {
-                                                    println!("[Cache] Failed to download artist avatar for {:?} (perhaps all providers were disabled)", res.err());
-                                                }
-                                            }
-                                        }
-                                        else {
-                                            // Push an empty ArtistMeta to block further calls for this album.
-                                            println!("No artist meta could be found for {:?}. Pushing empty document...", &key);
-                                            sqlite::write_artist_meta(&key, &models::ArtistMeta::from_key(&key))
-                                                .expect("Unable to write downloaded artist meta");
-                                        }
-                                        let _ = fg_sender.send_blocking(ProviderMessage::ArtistMetaAvailable(name));
-                                        sleep_after_request();
+        let n_workers = settings_manager()
+            .child("metaprovider")
+            .uint("max-concurrent-requests")
+            .max(1);
+        for _ in 0..n_workers {
+            let bg_receiver = bg_receiver.clone();
+            let fg_sender = fg_sender.clone();
+            let providers = providers.clone();
+            glib::MainContext::default().spawn_local(
+                async move {
+                    use futures::prelude::*;
+                    // Allow receiver to be mutated, but keep it at the same memory address.
+                    // See Receiver::next doc for why this is needed.
+                    let mut receiver = std::pin::pin!(bg_receiver);
+
+                    while let Some(request) = receiver.next().await {
+                        match request {
+                            ProviderMessage::AlbumMeta(key, overwrite) => {
+                                let _ = gio::spawn_blocking(clone!(
+                                    #[strong]
+                                    fg_sender,
+                                    #[strong]
+                                    providers,
+                                    move || {
+                                        fetch_album_meta(&providers, key, overwrite, &fg_sender);
                                     }
-                                }
-                            )).await;
-                        },
-                        ProviderMessage::FolderCover(album) => {
-                            let _ = gio::spawn_blocking(clone!(
-                                #[strong]
-                                fg_sender,
-                                move || {
-                                    let mut success: bool = false;
-                                    if sqlite::find_image_by_key(&album.folder_uri, None, false).expect("Sqlite DB error").is_some() {
-                                        success = true;
+                                )).await;
+                            },
+                            ProviderMessage::ArtistMeta(key, overwrite) => {
+                                let _ = gio::spawn_blocking(clone!(
+                                    #[strong]
+                                    fg_sender,
+                                    #[strong]
+                                    providers,
+                                    move || {
+                                        fetch_artist_meta(&providers, key, overwrite, &fg_sender);
                                     }
-                                    else if let Ok(Some(meta)) = sqlite::find_album_meta(&album) {
-                                        let res = get_best_image(&meta.image);
-                                        if res.is_ok() {
-                                            let (hires, thumbnail) = resize_convert_image(res.unwrap());
-                                            let (path, thumbnail_path) = get_new_image_paths();
-                                            if let (Ok(_), Ok(_)) = (
-                                                hires.save(&path),
-                                                thumbnail.save(&thumbnail_path)
-                                            ) {
-                                                let _ = sqlite::register_image_key(
-                                                    album.folder_uri.clone(), None,
-                                                    Some(path.file_name().unwrap().to_str().unwrap().to_string()), false
-                                                );
-                                                let _ = sqlite::register_image_key(
-                                                    album.folder_uri.clone(), None,
-                                                    Some(thumbnail_path.file_name().unwrap().to_str().unwrap().to_string()), true
-                                                );
-                                                let hires_tex = gdk::Texture::from_filename(&path).unwrap();
-                                                let thumbnail_tex = gdk::Texture::from_filename(&thumbnail_path).unwrap();
-                                                let _ = fg_sender.send_blocking(ProviderMessage::CoverAvailable(album.folder_uri.to_owned(), false, hires_tex));
-                                                let _ = fg_sender.send_blocking(ProviderMessage::CoverAvailable(album.folder_uri.to_owned(), true, thumbnail_tex));
-                                                success = true;
-                                            }
+                                )).await;
+                            },
+                            ProviderMessage::FolderCover(album) => {
+                                let _ = gio::spawn_blocking(clone!(
+                                    #[strong]
+                                    fg_sender,
+                                    #[strong]
+                                    providers,
+                                    move || {
+                                        if sqlite::find_image_by_key(&album.folder_uri, None, false).expect("Sqlite DB error").is_none() {
+                                            fetch_folder_cover_externally(&providers, album, &fg_sender);
                                         }
-                                        sleep_after_request();
                                     }
-                                    if !success {
-                                        // End of the road, still unable to find anything for this folder (or its songs).
-                                        println!("Cannot download folder cover for {} externally (perhaps all providers were disabled)", album.folder_uri);
-                                        // Write empty entries to prevent further (fruitless) lookups
-                                        let _ = sqlite::register_image_key(album.folder_uri.clone(), None, None, false);
-                                        let _ = sqlite::register_image_key(album.folder_uri.clone(), None, None, true);
-                                        let _ = fg_sender.send_blocking(ProviderMessage::CoverNotAvailable(album.folder_uri));
+                                )).await;
+                            },
+                            ProviderMessage::Lyrics(key) => {
+                                let _ = gio::spawn_blocking(clone!(
+                                    #[strong]
+                                    fg_sender,
+                                    #[strong]
+                                    providers,
+                                    move || {
+                                        fetch_lyrics(&providers, key, &fg_sender);
                                     }
-                                }
-                            )).await;
-                        },
-                        ProviderMessage::Lyrics(key) => {
-                            let _ = gio::spawn_blocking(clone!(
-                                #[strong]
-                                fg_sender,
-                                #[strong]
-                                providers,
-                                move || {
-                                    // Guaranteed to have this field so just unwrap it
-                                    let res = providers.read().unwrap().get_lyrics(&key);
-                                    if let Some(lyrics) = res {
-                                        sqlite::write_lyrics(&key, Some(&lyrics))
-                                                 .expect("Unable to write downloaded lyrics");
-                                        let _ = fg_sender.send_blocking(ProviderMessage::LyricsAvailable(key.uri));
-                                    }
-                                    sleep_after_request();
-                                }
-                            )).await;
-                        }
-                        _ => {}
-                    };
+                                )).await;
+                            }
+                            _ => {}
+                        };
+                    }
                 }
-            }
-        );
+            );
+        }
         let this = self.clone();
         // Listen to the background thread.
         glib::MainContext::default().spawn_local(async move {
@@ -417,8 +789,6 @@ impl Cache {
                             "MPD does not have cover for folder {}, will try fetching from external providers.",
                             &album.folder_uri
                         );
-                        // Fill out metadata before attempting to fetch album art from external sources.
-                        let _ = this.bg_sender.send_blocking(ProviderMessage::AlbumMeta(album.clone(), false));
                         let _ = this.bg_sender.send_blocking(ProviderMessage::FolderCover(album));
                     }
                     ProviderMessage::ArtistAvatarAvailable(name, thumb, tex) => {
@@ -789,8 +1159,86 @@ impl Cache {
     }
 
 
-    pub fn set_cover(&self, folder_uri: &str, path: &str) {
-        self.set_image(folder_uri, None, path, Some("album-art-downloaded"));
+    pub fn set_cover(&self, folder_uri: &str, path: &str) -> glib::JoinHandle<()> {
+        self.set_image(folder_uri, None, path, Some("album-art-downloaded"))
+    }
+
+    /// Remember the MusicBrainz release the user picked for an album, then refetch its
+    /// metadata from that release.
+    pub fn pin_album_release(&self, album: &AlbumInfo, mbid: &str) {
+        let album = album.clone();
+        let mbid = mbid.to_owned();
+        let bg_sender = self.bg_sender.clone();
+        gio::spawn_blocking(move || {
+            if let Err(e) = sqlite::pin_album_release(&album, &mbid) {
+                println!("[Cache] Unable to save picked release: {e:?}");
+                return;
+            }
+            let _ = bg_sender.send_blocking(ProviderMessage::AlbumMeta(album, true));
+        });
+    }
+
+    /// List the MusicBrainz releases matching the album's title & artist in the background.
+    pub fn search_releases(&self, album: &AlbumInfo) -> gio::JoinHandle<Vec<ReleaseCandidate>> {
+        let album = album.clone();
+        gio::spawn_blocking(move || musicbrainz::search_releases(&album))
+    }
+
+    /// Ask every enabled provider for covers of this album, optionally pinned to the given
+    /// MusicBrainz release. Candidates are sent as soon as each is downloaded, and the channel
+    /// closes once all are done. Their temporary files are the caller's to delete.
+    pub fn find_cover_candidates(&self, album: &AlbumInfo, mbid: Option<String>) -> Receiver<CoverCandidate> {
+        let (sender, receiver) = async_channel::unbounded();
+        let providers = self.meta_providers.clone();
+        let mut key = album.clone();
+        gio::spawn_blocking(move || {
+            // Default to the release picked for this album before, if any.
+            let mbid = mbid.or_else(|| {
+                sqlite::find_album_meta(&key)
+                    .ok()
+                    .flatten()
+                    .and_then(|meta| meta.pinned_mbid)
+            });
+            if mbid.is_some() {
+                key.mbid = mbid;
+            }
+            let mut images = providers.read().unwrap().get_album_image_candidates(&mut key);
+            let mb_settings = meta_provider_settings("musicbrainz");
+            if let (Some(mbid), true) = (&key.mbid, mb_settings.boolean("enabled")) {
+                images.push((
+                    mb_settings.string("name").to_string(),
+                    musicbrainz::cover_art_archive_image(mbid),
+                ));
+            }
+            let images = dedup_images(images);
+            // Download concurrently; each provider's rate limit still applies.
+            thread::scope(|scope| {
+                for (source, image) in images {
+                    let sender = &sender;
+                    scope.spawn(move || {
+                        let Some(dyn_img) = download_image(&image.url) else {
+                            return;
+                        };
+                        let mut path = glib::tmp_dir();
+                        path.push(format!("euphonica-cover-{}.png", Uuid::new_v4().simple()));
+                        if let Err(e) = dyn_img.save(&path) {
+                            println!("[Cache] Unable to save cover candidate: {e:?}");
+                            return;
+                        }
+                        if let Ok(texture) = Texture::from_filename(&path) {
+                            let _ = sender.send_blocking(CoverCandidate {
+                                source,
+                                width: dyn_img.width(),
+                                height: dyn_img.height(),
+                                path,
+                                texture,
+                            });
+                        }
+                    });
+                }
+            });
+        });
+        receiver
     }
 
 
@@ -919,36 +1367,24 @@ impl Cache {
         is_dynamic_playlist: bool,
         thumbnail: bool,
     ) -> gio::JoinHandle<Option<gdk::Texture>> {
-        let playlist_name = playlist_name.to_owned();
-        gio::spawn_blocking(move || {
-            let prefix = Some(if is_dynamic_playlist {"dynamic_playlist"} else {"playlist"});
-            // First try to get from cache
-            if let Some(filename) = sqlite::find_image_by_key(
-                &playlist_name,
-                prefix,
-                thumbnail
-            ).expect("Sqlite DB error") {
-                if let Some(tex) = IMAGE_CACHE.lock().unwrap().get(&filename) {
-                    // Cloning GObjects is cheap since they're just references
-                    return Some(tex.clone());
-                }
-                // If missed, try loading from disk
-                let mut path = get_image_cache_path();
-                path.push(&filename);
-                // Try to load from disk. Do this using the threadpool to avoid blocking UI.
-                if let Ok(tex) = Texture::from_filename(&path) {
-                    IMAGE_CACHE.lock().unwrap().put(filename, tex.clone());
-                    return Some(tex);
-                } else {
-                    // File no longer exists (maybe user had removed it). Unregister it from DB.
-                    // NO NEED TO REPEAT PROCESS. There is nothing to fetch automatically.
-                    println!("Playlist cover no longer present on disk. Unregistering...");
-                    sqlite::unregister_image_key(playlist_name, prefix, thumbnail)
-                        .join().unwrap().expect("Sqlite DB error");
-                }
-            }
-            None
-        })
+        let prefix = if is_dynamic_playlist {"dynamic_playlist"} else {"playlist"};
+        load_user_image(playlist_name, prefix, thumbnail)
+    }
+
+    pub fn set_station_logo(&self, station_name: &str, path: &str) -> glib::JoinHandle<()> {
+        self.set_image(station_name, Some("radio"), path, None)
+    }
+
+    pub fn clear_station_logo(&self, station_name: &str) -> glib::JoinHandle<()> {
+        self.clear_image(station_name, Some("radio"), None)
+    }
+
+    pub fn load_cached_station_logo(
+        &self,
+        station_name: &str,
+        thumbnail: bool,
+    ) -> gio::JoinHandle<Option<gdk::Texture>> {
+        load_user_image(station_name, "radio", thumbnail)
     }
 
 
@@ -1,12 +1,15 @@
 mod controller;
 mod state;
+mod prefetch;
 pub mod sqlite;
 
 pub use state::CacheState;
+pub use prefetch::PrefetchState;
 pub mod placeholders;
 
 pub use controller::Cache;
 pub use controller::{
+    CoverCandidate,
     ImageAction,
     get_app_cache_path,
     get_image_cache_path,
@@ -0,0 +1,295 @@
+// Whole-library prefetching.
+// Instead of waiting for cells to become visible, walk every album & artist in the library
+// and fetch whatever the cache is still missing: covers (from MPD first, then external
+// providers), album wikis, artist bios & avatars and optionally lyrics.
+// The job opens an MPD connection of its own so as not to hog the background client.
+// Its state & the key of the last item it went through are kept in GSettings, so a paused
+// (or interrupted) job picks up where it left off, even after a restart or a library update.
+// Since every step skips what's already cached, redoing a few items after an interruption
+// costs little.
+// Whatever couldn't be found is recorded in the prefetch_report table for the user to review.
+use std::{
+    sync::{Arc, RwLock},
+    thread,
+};
+
+use rustc_hash::FxHashSet;
+
+use async_channel::Sender;
+use gio::prelude::*;
+use mpd::Client;
+
+use crate::{
+    client::{
+        connect_main_server, download_embedded_cover_inner, download_folder_cover_inner,
+        list_album_songs, list_all_albums, list_all_artists, password::get_mpd_password_blocking,
+        StreamWrapper,
+    },
+    common::{AlbumInfo, ArtistInfo, SongInfo},
+    meta_providers::{MetadataChain, ProviderMessage},
+    utils::settings_manager,
+};
+
+use super::{
+    controller::{
+        download_artist_avatar, fetch_album_meta, fetch_artist_meta,
+        fetch_folder_cover_externally, fetch_lyrics,
+    },
+    sqlite,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefetchState {
+    /// Never run, finished or cancelled
+    Idle,
+    Running,
+    Paused,
+}
+
+impl PrefetchState {
+    pub fn get() -> Self {
+        match settings_manager()
+            .child("metaprovider")
+            .string("prefetch-state")
+            .as_str()
+        {
+            "running" => Self::Running,
+            "paused" => Self::Paused,
+            _ => Self::Idle,
+        }
+    }
+
+    pub fn set(self) {
+        let _ = settings_manager().child("metaprovider").set_string(
+            "prefetch-state",
+            match self {
+                Self::Idle => "idle",
+                Self::Running => "running",
+                Self::Paused => "paused",
+            },
+        );
+    }
+}
+
+/// One album or artist to go through.
+enum Item {
+    Album(AlbumInfo),
+    Artist(ArtistInfo),
+}
+
+impl Item {
+    /// Items are walked in the order of these keys: albums by folder then title, then
+    /// artists by name. Saving the last one lets the job resume at the right place even
+    /// if items were added or removed in the meantime.
+    fn key(&self) -> String {
+        match self {
+            Self::Album(album) => format!("album\t{}\t{}", &album.folder_uri, &album.title),
+            Self::Artist(artist) => format!("artist\t{}", &artist.name),
+        }
+    }
+}
+
+/// One unit of work, after its MPD-side part has been done.
+enum Job {
+    /// Album, whether it already has a cover, and its songs (empty if not fetching lyrics)
+    Album(AlbumInfo, bool, Vec<SongInfo>),
+    Artist(ArtistInfo),
+}
+
+fn report(kind: &str, name: &str, missing: &str) {
+    println!("[Prefetch] Could not find {missing} for {kind} {name}");
+    let _ = sqlite::add_prefetch_report_entry(kind, name, missing);
+}
+
+fn describe(title: &str, artist: Option<&str>) -> String {
+    if let Some(artist) = artist {
+        format!("{title} – {artist}")
+    } else {
+        title.to_owned()
+    }
+}
+
+fn has_image(key: &str, prefix: Option<&str>) -> bool {
+    sqlite::find_image_by_key(key, prefix, false)
+        .ok()
+        .flatten()
+        .is_some_and(|filename| !filename.is_empty())
+}
+
+/// Whether the album has a cover, either cached already or freshly downloaded from MPD.
+/// Folder-level art is preferred over art embedded in its songs.
+fn ensure_cover_from_mpd(
+    client: &mut Client<StreamWrapper>,
+    album: &AlbumInfo,
+    fg_sender: &Sender<ProviderMessage>,
+) -> bool {
+    if has_image(&album.folder_uri, None) || has_image(&album.example_uri, None) {
+        return true;
+    }
+    if !settings_manager().child("client").boolean("mpd-download-album-art") {
+        return false;
+    }
+    let (uri, res) = if let Some(textures) = download_folder_cover_inner(client, album.folder_uri.clone()) {
+        (album.folder_uri.clone(), textures)
+    } else if let Some(textures) = download_embedded_cover_inner(client, album.example_uri.clone()) {
+        (album.example_uri.clone(), textures)
+    } else {
+        return false;
+    };
+    let (hires_tex, thumb_tex) = res;
+    let _ = fg_sender.send_blocking(ProviderMessage::CoverAvailable(uri.clone(), false, hires_tex));
+    let _ = fg_sender.send_blocking(ProviderMessage::CoverAvailable(uri, true, thumb_tex));
+    true
+}
+
+impl Job {
+    fn run(self, providers: &RwLock<MetadataChain>, fg_sender: &Sender<ProviderMessage>) {
+        match self {
+            Self::Album(album, has_cover, songs) => {
+                let name = describe(&album.title, album.albumartist.as_deref());
+                if !has_cover && !fetch_folder_cover_externally(providers, album.clone(), fg_sender) {
+                    report("album", &name, "cover");
+                }
+                // No-op if fetched while looking for a cover above
+                fetch_album_meta(providers, album.clone(), false, fg_sender);
+                if !sqlite::find_album_meta(&album)
+                    .ok()
+                    .flatten()
+                    .is_some_and(|meta| meta.wiki.is_some())
+                {
+                    report("album", &name, "wiki");
+                }
+                for song in songs {
+                    if sqlite::find_lyrics(&song).ok().flatten().is_some() {
+                        continue;
+                    }
+                    let name = describe(&song.title, song.artist_tag.as_deref());
+                    if !fetch_lyrics(providers, song, fg_sender) {
+                        report("song", &name, "lyrics");
+                    }
+                }
+            }
+            Self::Artist(artist) => {
+                fetch_artist_meta(providers, artist.clone(), false, fg_sender);
+                let meta = sqlite::find_artist_meta(&artist).ok().flatten();
+                if !meta.as_ref().is_some_and(|meta| meta.bio.is_some()) {
+                    report("artist", &artist.name, "bio");
+                }
+                // Avatars are only downloaded along with new metadata, so retry here
+                // for artists whose metadata was already cached.
+                if !has_image(&artist.name, Some("avatar"))
+                    && !meta.is_some_and(|meta| download_artist_avatar(&artist, &meta, fg_sender))
+                {
+                    report("artist", &artist.name, "avatar");
+                }
+            }
+        }
+    }
+}
+
+/// Blocking. Run the prefetch job from its saved position until it's done, paused or
+/// cancelled. If the connection to MPD is lost, the job stops but stays in the running
+/// state, so that it's resumed upon reconnection.
+pub fn run(providers: Arc<RwLock<MetadataChain>>, fg_sender: Sender<ProviderMessage>) {
+    let settings = settings_manager().child("metaprovider");
+    let password = get_mpd_password_blocking().ok().flatten();
+    let mut client = match connect_main_server(password.as_deref()) {
+        Ok(client) => client,
+        Err(state) => {
+            println!("[Prefetch] Unable to connect to MPD: {state:?}");
+            return;
+        }
+    };
+    let listed = list_all_albums(&mut client)
+        .and_then(|albums| Ok((albums, list_all_artists(&mut client)?)));
+    let (albums, artists) = match listed {
+        Ok(res) => res,
+        Err(e) => {
+            println!("[Prefetch] Unable to list library contents: {e:?}");
+            let _ = client.close();
+            return;
+        }
+    };
+    // Artists only credited as album artists aren't listed by their Artist tag.
+    let mut seen: FxHashSet<String> = artists.iter().map(|artist| artist.name.clone()).collect();
+    let album_artists: Vec<ArtistInfo> = albums
+        .iter()
+        .flat_map(|album| album.artists.iter())
+        .filter(|artist| seen.insert(artist.name.clone()))
+        .cloned()
+        .collect();
+    let mut items: Vec<(String, Item)> = albums
+        .into_iter()
+        .map(Item::Album)
+        .chain(artists.into_iter().chain(album_artists).map(Item::Artist))
+        .map(|item| (item.key(), item))
+        .collect();
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+    items.dedup_by(|a, b| a.0 == b.0);
+    let total = items.len();
+    let with_lyrics = settings.boolean("prefetch-lyrics");
+    // Items are handled in chunks, one per worker. Waiting for the whole chunk to finish
+    // before moving on means that everything before the saved position is done.
+    let n_workers = settings.uint("max-concurrent-requests").max(1) as usize;
+    println!("[Prefetch] Walking {total} items");
+
+    loop {
+        // Re-read every time, as the job might have been restarted from scratch while
+        // this thread was still finishing up its last chunk.
+        let last_key = settings.string("prefetch-last-key");
+        let pos = if last_key.is_empty() {
+            0
+        } else {
+            items.partition_point(|(key, _)| key.as_str() <= last_key.as_str())
+        };
+        if settings.uint("prefetch-total") as usize != total {
+            let _ = settings.set_uint("prefetch-total", total as u32);
+        }
+        if settings.uint("prefetch-position") as usize != pos {
+            let _ = settings.set_uint("prefetch-position", pos as u32);
+        }
+        if pos >= total {
+            break;
+        }
+        if PrefetchState::get() != PrefetchState::Running {
+            println!("[Prefetch] Stopped at item {pos} of {total}");
+            let _ = client.close();
+            return;
+        }
+        if client.ping().is_err() {
+            println!("[Prefetch] Lost connection to MPD at item {pos} of {total}");
+            return;
+        }
+        let end = (pos + n_workers).min(total);
+        // MPD-side parts are done here, one by one, on our own connection.
+        let jobs: Vec<Job> = items[pos..end]
+            .iter()
+            .map(|(_, item)| match item {
+                Item::Album(album) => {
+                    let has_cover = ensure_cover_from_mpd(&mut client, album, &fg_sender);
+                    let songs = if with_lyrics {
+                        list_album_songs(&mut client, album)
+                    } else {
+                        Vec::new()
+                    };
+                    Job::Album(album.clone(), has_cover, songs)
+                }
+                Item::Artist(artist) => Job::Artist(artist.clone()),
+            })
+            .collect();
+        // Provider-side parts are done concurrently, as each provider enforces its own
+        // rate limit.
+        thread::scope(|scope| {
+            for job in jobs {
+                let providers = &providers;
+                let fg_sender = &fg_sender;
+                scope.spawn(move || job.run(providers, fg_sender));
+            }
+        });
+        let _ = settings.set_string("prefetch-last-key", &items[end - 1].0);
+        let _ = settings.set_uint("prefetch-position", end as u32);
+    }
+    let _ = client.close();
+    println!("[Prefetch] Done");
+    PrefetchState::Idle.set();
+}
@@ -1,29 +1,38 @@
 extern crate bson;
 extern crate rusqlite;
 
-use std::{io::Cursor, str::FromStr};
+use std::{collections::{HashMap, HashSet}, io::Cursor, str::FromStr};
 
 use once_cell::sync::Lazy;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{params, Error as SqliteError, OptionalExtension, Result, Row};
+use rusqlite::{params, params_from_iter, Error as SqliteError, OptionalExtension, Result, Row};
 use time::OffsetDateTime;
 use glib::{ThreadPool, ThreadHandle};
 
 use crate::{
-    common::{dynamic_playlist::{AutoRefresh, Ordering, Rule}, inode::INodeInfo, AlbumInfo, ArtistInfo, DynamicPlaylist, INodeType, SongInfo},
+    common::{dynamic_playlist::{AutoRefresh, Ordering, Rule}, inode::INodeInfo, AlbumInfo, ArtistInfo, DynamicPlaylist, INodeType, PodcastEpisode, PodcastFeed, QueueSnapshot, RadioStation, SongInfo},
     meta_providers::models::{AlbumMeta, ArtistMeta, Lyrics, LyricsParseError},
+    player::PlaybackFlow,
     utils::{format_datetime_local_tz, strip_filename_linux},
 };
 
 use super::controller::get_doc_cache_path;
 
+/// How long to wait for a lock on the DB before giving up.
+const BUSY_TIMEOUT_S: u64 = 10;
+/// How many URIs to look up per query when batching song range lookups.
+const SONG_RANGES_CHUNK_SIZE: usize = 500;
+
 // Limit writes to a single thread to avoid DatabaseBusy races.
 // Thread will be parked when idle.
 static SQLITE_WRITE_THREADPOOL: Lazy<glib::ThreadPool> = Lazy::new(|| {
     ThreadPool::shared(Some(1)).expect("Failed to spawn Sqlite write threadpool")
 });
 static SQLITE_POOL: Lazy<r2d2::Pool<SqliteConnectionManager>> = Lazy::new(|| {
-    let manager = SqliteConnectionManager::file(get_doc_cache_path());
+    // Metadata are fetched by several threads at once, so wait for other writers
+    // instead of failing with DatabaseBusy.
+    let manager = SqliteConnectionManager::file(get_doc_cache_path())
+        .with_init(|conn| conn.busy_timeout(std::time::Duration::from_secs(BUSY_TIMEOUT_S)));
     let pool = r2d2::Pool::new(manager).unwrap();
     let conn = pool.get().unwrap();
     // Init schema & indices
@@ -37,7 +46,103 @@ static SQLITE_POOL: Lazy<r2d2::Pool<SqliteConnectionManager>> = Lazy::new(|| {
 
         println!("Local metadata DB version: {user_version}");
         match user_version {
-            4 => {break;},
+            10 => {break;},
+            9 => {
+                conn.execute_batch("create table if not exists `prefetch_report` (
+    `kind` VARCHAR not null,
+    `name` VARCHAR not null,
+    `missing` VARCHAR not null,
+    primary key(`kind`, `name`, `missing`)
+);
+pragma user_version = 10;").expect("Unable to migrate DB version 9 to 10");
+            },
+            8 => {
+                // Existing negative results can't be told apart, so they'll only be
+                // refreshed once the positive TTL runs out.
+                conn.execute_batch("alter table `albums` add column `empty` BOOL not null default 0;
+alter table `artists` add column `empty` BOOL not null default 0;
+pragma user_version = 9;").expect("Unable to migrate DB version 8 to 9");
+            },
+            7 => {
+                conn.execute_batch("create table if not exists `podcast_feeds` (
+    `url` VARCHAR not null,
+    `title` VARCHAR not null,
+    `description` VARCHAR null,
+    `link` VARCHAR null,
+    `image_url` VARCHAR null,
+    `added_at` DATETIME not null,
+    `last_checked` DATETIME null,
+    `last_error` VARCHAR null,
+    primary key(`url`)
+);
+
+create table if not exists `podcast_episodes` (
+    `feed_url` VARCHAR not null,
+    `guid` VARCHAR not null,
+    `title` VARCHAR not null,
+    `url` VARCHAR not null,
+    `description` VARCHAR null,
+    `published` DATETIME null,
+    `duration` REAL null,
+    `listened` BOOL not null default 0,
+    `position` REAL not null default 0,
+    primary key(`feed_url`, `guid`)
+);
+create index if not exists `podcast_episodes_url` on `podcast_episodes` (
+    `url`
+);
+pragma user_version = 8;").expect("Unable to migrate DB version 7 to 8");
+            },
+            6 => {
+                conn.execute_batch("create table if not exists `radio_stations` (
+    `name` VARCHAR not null,
+    `url` VARCHAR not null,
+    `genre` VARCHAR null,
+    `added_at` DATETIME not null,
+    primary key(`name`)
+);
+
+create table if not exists `radio_titles` (
+    `station_name` VARCHAR not null,
+    `title` VARCHAR not null,
+    `timestamp` DATETIME not null
+);
+create index if not exists `radio_titles_key` on `radio_titles` (
+    `station_name`
+);
+pragma user_version = 7;").expect("Unable to migrate DB version 6 to 7");
+            },
+            5 => {
+                conn.execute_batch("create table if not exists `queue_snapshots` (
+    `name` VARCHAR not null,
+    `saved_at` DATETIME not null,
+    `pos` INTEGER null,
+    `elapsed` REAL not null,
+    `flow` VARCHAR not null,
+    `random` BOOL not null,
+    `consume` BOOL not null,
+    primary key(`name`)
+);
+
+create table if not exists `queue_snapshot_songs` (
+    `snapshot_name` VARCHAR not null,
+    `idx` INTEGER not null,
+    `uri` VARCHAR not null
+);
+create index if not exists `queue_snapshot_songs_key` on `queue_snapshot_songs` (
+    `snapshot_name`
+);
+pragma user_version = 6;").expect("Unable to migrate DB version 5 to 6");
+            },
+            4 => {
+                conn.execute_batch("create table if not exists `song_ranges` (
+    `uri` VARCHAR not null,
+    `start` REAL not null,
+    `end` REAL null,
+    primary key(`uri`)
+);
+pragma user_version = 5;").expect("Unable to migrate DB version 4 to 5");
+            },
             3 => {
                 conn.execute_batch("create table if not exists `queries` (
     `name` VARCHAR not null,
@@ -166,7 +271,8 @@ create table if not exists `albums` (
     `title` VARCHAR not null,
     `artist` VARCHAR null,
     `last_modified` DATETIME not null,
-    `data` BLOB not null
+    `data` BLOB not null,
+    `empty` BOOL not null default 0
 );
 create unique index if not exists `album_mbid` on `albums` (
     `mbid`
@@ -180,6 +286,7 @@ create table if not exists `artists` (
     `mbid` VARCHAR null unique,
     `last_modified` DATETIME not null,
     `data` BLOB not null,
+    `empty` BOOL not null default 0,
     primary key (`name`)
 );
 create unique index if not exists `artist_mbid` on `artists` (
@@ -258,8 +365,87 @@ create index if not exists `query_results_key` on `query_results` (
     `query_name`
 );
 
+create table if not exists `song_ranges` (
+    `uri` VARCHAR not null,
+    `start` REAL not null,
+    `end` REAL null,
+    primary key(`uri`)
+);
+
+create table if not exists `queue_snapshots` (
+    `name` VARCHAR not null,
+    `saved_at` DATETIME not null,
+    `pos` INTEGER null,
+    `elapsed` REAL not null,
+    `flow` VARCHAR not null,
+    `random` BOOL not null,
+    `consume` BOOL not null,
+    primary key(`name`)
+);
+
+create table if not exists `queue_snapshot_songs` (
+    `snapshot_name` VARCHAR not null,
+    `idx` INTEGER not null,
+    `uri` VARCHAR not null
+);
+create index if not exists `queue_snapshot_songs_key` on `queue_snapshot_songs` (
+    `snapshot_name`
+);
+
+create table if not exists `radio_stations` (
+    `name` VARCHAR not null,
+    `url` VARCHAR not null,
+    `genre` VARCHAR null,
+    `added_at` DATETIME not null,
+    primary key(`name`)
+);
+
+create table if not exists `radio_titles` (
+    `station_name` VARCHAR not null,
+    `title` VARCHAR not null,
+    `timestamp` DATETIME not null
+);
+create index if not exists `radio_titles_key` on `radio_titles` (
+    `station_name`
+);
+
+create table if not exists `podcast_feeds` (
+    `url` VARCHAR not null,
+    `title` VARCHAR not null,
+    `description` VARCHAR null,
+    `link` VARCHAR null,
+    `image_url` VARCHAR null,
+    `added_at` DATETIME not null,
+    `last_checked` DATETIME null,
+    `last_error` VARCHAR null,
+    primary key(`url`)
+);
+
+create table if not exists `podcast_episodes` (
+    `feed_url` VARCHAR not null,
+    `guid` VARCHAR not null,
+    `title` VARCHAR not null,
+    `url` VARCHAR not null,
+    `description` VARCHAR null,
+    `published` DATETIME null,
+    `duration` REAL null,
+    `listened` BOOL not null default 0,
+    `position` REAL not null default 0,
+    primary key(`feed_url`, `guid`)
+);
+create index if not exists `podcast_episodes_url` on `podcast_episodes` (
+    `url`
+);
+
+create table if not exists `prefetch_report` (
+    `kind` VARCHAR not null,
+    `name` VARCHAR not null,
+    `missing` VARCHAR not null,
+    primary key(`kind`, `name`, `missing`)
+);
+
 pragma journal_mode=WAL;
-pragma user_version = 4;
+pragma user_version = 10;
 end;
 ").expect("Unable to init metadata SQLite DB");
                     }
@@ -462,7 +648,7 @@ pub fn write_album_meta(album: &AlbumInfo, meta: &AlbumMeta) -> Result<(), Error
         return Err(Error::InsufficientKey);
     }
     tx.execute(
-        "insert into albums (folder_uri, mbid, title, artist, last_modified, data) values (?1,?2,?3,?4,?5,?6)",
+        "insert into albums (folder_uri, mbid, title, artist, last_modified, data, empty) values (?1,?2,?3,?4,?5,?6,?7)",
         params![
             &album.folder_uri,
             &album.mbid,
@@ -473,13 +659,37 @@ pub fn write_album_meta(album: &AlbumInfo, meta: &AlbumMeta) -> Result<(), Error
                 &bson
                     ::serialize_to_document(meta)
                     .map_err(Error::ObjectToDocError)?
-            ).map_err(Error::DocToBytesError)?
+            ).map_err(Error::DocToBytesError)?,
+            meta.is_empty()
         ]
     ).map_err(Error::DbError)?;
     tx.commit().map_err(Error::DbError)?;
     Ok(())
 }
 
+/// Remember the MusicBrainz release the user picked for an album. Its cached metadata, if
+/// any, is kept until refetched from that release.
+pub fn pin_album_release(album: &AlbumInfo, mbid: &str) -> Result<(), Error> {
+    let mut meta = find_album_meta(album)?.unwrap_or_else(|| AlbumMeta::from_key(album));
+    meta.mbid = Some(mbid.to_owned());
+    meta.pinned_mbid = Some(mbid.to_owned());
+    let mut key = album.clone();
+    if key.mbid.is_none() {
+        // Untagged albums are looked up by title & artist and stored under the MBID found
+        // for them, so the row of the previous best match would shadow this one.
+        if let Some(artist) = album.get_artist_tag() {
+            let conn = SQLITE_POOL.get().unwrap();
+            conn.execute(
+                "delete from albums where title = ?1 and artist = ?2",
+                params![&album.title, artist],
+            )
+            .map_err(Error::DbError)?;
+        }
+        key.mbid = Some(mbid.to_owned());
+    }
+    write_album_meta(&key, &meta)
+}
+
 pub fn write_artist_meta(artist: &ArtistInfo, meta: &ArtistMeta) -> Result<(), Error> {
     let mut conn = SQLITE_POOL.get().unwrap();
     let tx = conn.transaction().map_err(Error::DbError)?;
@@ -491,14 +701,15 @@ pub fn write_artist_meta(artist: &ArtistInfo, meta: &ArtistMeta) -> Result<(), E
             .map_err(Error::DbError)?;
     }
     tx.execute(
-        "insert into artists (name, mbid, last_modified, data) values (?1,?2,?3,?4)",
+        "insert into artists (name, mbid, last_modified, data, empty) values (?1,?2,?3,?4,?5)",
         params![
             &artist.name,
             &artist.mbid,
             OffsetDateTime::now_utc(),
             bson::serialize_to_vec(
                 &bson::serialize_to_document(meta).map_err(Error::ObjectToDocError)?
-            ).map_err(Error::DocToBytesError)?
+            ).map_err(Error::DocToBytesError)?,
+            meta.is_empty()
         ],
     )
     .map_err(Error::DbError)?;
@@ -506,6 +717,138 @@ pub fn write_artist_meta(artist: &ArtistInfo, meta: &ArtistMeta) -> Result<(), E
     Ok(())
 }
 
+/// When the album's metadata was last fetched, and whether nothing was found then.
+pub fn get_album_meta_status(album: &AlbumInfo) -> Result<Option<(OffsetDateTime, bool)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let query = if let Some(mbid) = album.mbid.as_deref() {
+        conn.prepare("select last_modified, empty from albums where mbid = ?1")
+            .unwrap()
+            .query_row(params![mbid], |r| Ok((r.get(0)?, r.get(1)?)))
+    } else if let (title, Some(artist)) = (&album.title, album.get_artist_tag()) {
+        conn.prepare("select last_modified, empty from albums where title = ?1 and artist = ?2")
+            .unwrap()
+            .query_row(params![title, artist], |r| Ok((r.get(0)?, r.get(1)?)))
+    } else {
+        return Ok(None);
+    };
+    match query {
+        Ok(status) => Ok(Some(status)),
+        Err(SqliteError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(Error::DbError(e)),
+    }
+}
+
+/// When the artist's metadata was last fetched, and whether nothing was found then.
+pub fn get_artist_meta_status(artist: &ArtistInfo) -> Result<Option<(OffsetDateTime, bool)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let query = if let Some(mbid) = artist.mbid.as_deref() {
+        conn.prepare("select last_modified, empty from artists where mbid = ?1")
+            .unwrap()
+            .query_row(params![mbid], |r| Ok((r.get(0)?, r.get(1)?)))
+    } else {
+        conn.prepare("select last_modified, empty from artists where name = ?1")
+            .unwrap()
+            .query_row(params![&artist.name], |r| Ok((r.get(0)?, r.get(1)?)))
+    };
+    match query {
+        Ok(status) => Ok(Some(status)),
+        Err(SqliteError::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(Error::DbError(e)),
+    }
+}
+
+/// Albums whose metadata was fetched before the given cutoffs, oldest first. Positive and
+/// negative (empty) results have separate cutoffs. A None cutoff means never expiring.
+pub fn get_stale_albums(
+    positive_before: Option<OffsetDateTime>,
+    negative_before: Option<OffsetDateTime>,
+    limit: u32,
+) -> Result<Vec<AlbumInfo>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare(
+            "select folder_uri, mbid, title, artist from albums
+where ((empty = 0 and last_modified < ?1) or (empty = 1 and last_modified < ?2))
+and (mbid is not null or artist is not null)
+order by last_modified asc limit ?3",
+        )
+        .unwrap();
+    let res = query
+        .query_map(params![positive_before, negative_before, limit], |r| {
+            Ok(AlbumInfo {
+                folder_uri: r.get(0)?,
+                mbid: r.get(1)?,
+                title: r.get(2)?,
+                albumartist: r.get(3)?,
+                ..Default::default()
+            })
+        })
+        .map_err(Error::DbError)?
+        .collect::<Result<Vec<AlbumInfo>, SqliteError>>()
+        .map_err(Error::DbError)?;
+    Ok(res)
+}
+
+/// Artists whose metadata was fetched before the given cutoffs, oldest first.
+/// See get_stale_albums().
+pub fn get_stale_artists(
+    positive_before: Option<OffsetDateTime>,
+    negative_before: Option<OffsetDateTime>,
+    limit: u32,
+) -> Result<Vec<ArtistInfo>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare(
+            "select name, mbid from artists
+where (empty = 0 and last_modified < ?1) or (empty = 1 and last_modified < ?2)
+order by last_modified asc limit ?3",
+        )
+        .unwrap();
+    let res = query
+        .query_map(params![positive_before, negative_before, limit], |r| {
+            let mut artist = ArtistInfo::new(&r.get::<usize, String>(0)?, None, false);
+            artist.mbid = r.get(1)?;
+            Ok(artist)
+        })
+        .map_err(Error::DbError)?
+        .collect::<Result<Vec<ArtistInfo>, SqliteError>>()
+        .map_err(Error::DbError)?;
+    Ok(res)
+}
+
+/// Record something the library prefetch job could not find. Kind is either "album",
+/// "artist" or "song", with name being a human-readable description of the item.
+pub fn add_prefetch_report_entry(kind: &str, name: &str, missing: &str) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute(
+        "insert or ignore into prefetch_report (kind, name, missing) values (?1,?2,?3)",
+        params![kind, name, missing],
+    )
+    .map_err(Error::DbError)?;
+    Ok(())
+}
+
+/// Everything the last prefetch job could not find, as (kind, name, missing) tuples.
+pub fn get_prefetch_report() -> Result<Vec<(String, String, String)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select kind, name, missing from prefetch_report order by kind, name, missing")
+        .unwrap();
+    let res = query
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+        .map_err(Error::DbError)?
+        .collect::<Result<Vec<(String, String, String)>, SqliteError>>()
+        .map_err(Error::DbError)?;
+    Ok(res)
+}
+
+pub fn clear_prefetch_report() -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute("delete from prefetch_report", [])
+        .map_err(Error::DbError)?;
+    Ok(())
+}
+
 pub fn find_lyrics(song: &SongInfo) -> Result<Option<Lyrics>, Error> {
     let query: Result<LyricsRow, SqliteError>;
     let conn = SQLITE_POOL.get().unwrap();
@@ -759,6 +1102,179 @@ group by name order by last_played desc limit ?1",
     Ok(res.collect())
 }
 
+/// Things often played in the same listening sessions as an album or artist.
+pub struct CoListened {
+    /// Song URIs.
+    pub songs: Vec<String>,
+    /// (title, artist, mbid)s of albums.
+    pub albums: Vec<(String, Option<String>, Option<String>)>,
+    /// Artist names.
+    pub artists: Vec<String>,
+}
+
+/// Rank the keys of a history table (as (key, timestamp) rows in chronological order) by
+/// how often they are played together with the seed plays. Sessions are split wherever
+/// two consecutive plays are more than `session_gap_mins` apart. Within a session
+/// containing seed plays, every other key scores 1 / (1 + distance to the nearest seed
+/// play), summed over all such sessions. Keys that are themselves part of the seed are
+/// never returned.
+fn rank_co_listened(
+    seed_timestamps: &HashSet<OffsetDateTime>,
+    history: &[(String, OffsetDateTime)],
+    session_gap_mins: u32,
+    n: u32
+) -> Vec<String> {
+    let seed_keys: HashSet<&str> = history
+        .iter()
+        .filter(|(_, ts)| seed_timestamps.contains(ts))
+        .map(|(key, _)| key.as_str())
+        .collect();
+
+    let max_gap = time::Duration::minutes(session_gap_mins as i64);
+    let mut scores: HashMap<&str, f64> = HashMap::new();
+    let mut session_start: usize = 0;
+    for idx in 1..=history.len() {
+        if idx < history.len() && history[idx].1 - history[idx - 1].1 <= max_gap {
+            continue;
+        }
+        // history[session_start..idx] is one complete session
+        let session = &history[session_start..idx];
+        session_start = idx;
+        let seed_positions: Vec<usize> = session
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, ts))| seed_timestamps.contains(ts))
+            .map(|(pos, _)| pos)
+            .collect();
+        if seed_positions.is_empty() {
+            continue;
+        }
+        // Only count each key once per session, at its closest occurrence.
+        let mut session_scores: HashMap<&str, f64> = HashMap::new();
+        for (pos, (key, _)) in session.iter().enumerate() {
+            if seed_keys.contains(key.as_str()) {
+                continue;
+            }
+            let dist = seed_positions
+                .iter()
+                .map(|seed_pos| seed_pos.abs_diff(pos))
+                .min()
+                .unwrap();
+            let score = 1.0 / (1.0 + dist as f64);
+            let entry = session_scores.entry(key.as_str()).or_insert(0.0);
+            if score > *entry {
+                *entry = score;
+            }
+        }
+        for (key, score) in session_scores.into_iter() {
+            *scores.entry(key).or_insert(0.0) += score;
+        }
+    }
+
+    let mut ranked: Vec<(&str, f64)> = scores.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    ranked
+        .into_iter()
+        .take(n as usize)
+        .map(|(key, _)| key.to_owned())
+        .collect()
+}
+
+/// Mine local listening sessions for up to N songs, albums and artists each that are often
+/// played together with the history entries selected by `seed_query` (which must select
+/// a `timestamp` column and take a single parameter).
+fn get_co_listened(
+    seed_query: &str,
+    seed: &str,
+    session_gap_mins: u32,
+    n: u32
+) -> Result<CoListened, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let seed_timestamps: HashSet<OffsetDateTime> = conn
+        .prepare(seed_query)
+        .unwrap()
+        .query_map(params![seed], |r| r.get::<usize, OffsetDateTime>(0))
+        .map_err(Error::DbError)?
+        .filter_map(|r| r.ok())
+        .collect();
+    if seed_timestamps.is_empty() {
+        return Ok(CoListened {
+            songs: Vec::new(),
+            albums: Vec::new(),
+            artists: Vec::new(),
+        });
+    }
+    let history = |query: &str| -> Result<Vec<(String, OffsetDateTime)>, Error> {
+        Ok(conn
+            .prepare(query)
+            .unwrap()
+            .query_map([], |r| Ok((r.get::<usize, String>(0)?, r.get::<usize, OffsetDateTime>(1)?)))
+            .map_err(Error::DbError)?
+            .filter_map(|r| r.ok())
+            .collect())
+    };
+
+    let songs = rank_co_listened(
+        &seed_timestamps,
+        &history("select uri, timestamp from songs_history order by timestamp asc")?,
+        session_gap_mins,
+        n
+    );
+    // Albums are told apart by title, like in the Recent view. Use the latest play's
+    // album artist & MBID to find them again.
+    let mut album_query = conn
+        .prepare("select artist, mbid from albums_history where title = ?1 order by timestamp desc limit 1")
+        .unwrap();
+    let albums = rank_co_listened(
+        &seed_timestamps,
+        &history("select title, timestamp from albums_history order by timestamp asc")?,
+        session_gap_mins,
+        n
+    )
+        .into_iter()
+        .map(|title| {
+            let (artist, mbid) = album_query
+                .query_row(params![&title], |r| Ok((
+                    r.get::<usize, Option<String>>(0)?,
+                    r.get::<usize, Option<String>>(1)?
+                )))
+                .optional()
+                .map_err(Error::DbError)?
+                .unwrap_or_default();
+            Ok((title, artist, mbid))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let artists = rank_co_listened(
+        &seed_timestamps,
+        &history("select name, timestamp from artists_history order by timestamp asc")?,
+        session_gap_mins,
+        n
+    );
+    Ok(CoListened { songs, albums, artists })
+}
+
+/// Get up to N songs, albums and artists each often played in the same sessions as the
+/// given album.
+pub fn get_album_co_listened(title: &str, session_gap_mins: u32, n: u32) -> Result<CoListened, Error> {
+    get_co_listened(
+        "select timestamp from albums_history where title = ?1",
+        title,
+        session_gap_mins,
+        n
+    )
+}
+
+/// Get up to N songs, albums and artists each often played in the same sessions as the
+/// given artist.
+pub fn get_artist_co_listened(name: &str, session_gap_mins: u32, n: u32) -> Result<CoListened, Error> {
+    get_co_listened(
+        "select timestamp from artists_history where name = ?1",
+        name,
+        session_gap_mins,
+        n
+    )
+}
+
 pub fn clear_history() -> Result<(), Error> {
     let mut conn = SQLITE_POOL.get().unwrap();
     let tx = conn.transaction().map_err(Error::DbError)?;
@@ -977,3 +1493,440 @@ pub fn delete_dynamic_playlist(name: &str) -> Result<(), Error> {
     tx.commit()?;
     Ok(())
 }
+
+/// Get saved play ranges (start and optional end, in seconds) of the given songs.
+/// Songs without a saved range are not included.
+pub fn get_song_ranges(uris: &[String]) -> Result<HashMap<String, (f64, Option<f64>)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut res: HashMap<String, (f64, Option<f64>)> = HashMap::new();
+    // One query per chunk, staying under SQLite's limit on the number of parameters.
+    for chunk in uris.chunks(SONG_RANGES_CHUNK_SIZE) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        let mut query = conn
+            .prepare(&format!("select uri, start, end from song_ranges where uri in ({placeholders})"))
+            .map_err(Error::DbError)?;
+        let rows = query
+            .query_map(params_from_iter(chunk.iter()), |r| Ok((
+                r.get::<_, String>(0)?,
+                (r.get::<_, f64>(1)?, r.get::<_, Option<f64>>(2)?)
+            )))
+            .map_err(Error::DbError)?;
+        for row in rows {
+            let (uri, range) = row.map_err(Error::DbError)?;
+            res.insert(uri, range);
+        }
+    }
+    Ok(res)
+}
+
+/// Save a song's play range (start and optional end, in seconds), or forget it if None.
+pub fn write_song_range(uri: &str, range: Option<(f64, Option<f64>)>) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(Error::DbError)?;
+    tx.execute("delete from song_ranges where uri = ?1", params![uri])
+        .map_err(Error::DbError)?;
+    if let Some((start, end)) = range {
+        tx.execute(
+            "insert into song_ranges (uri, start, end) values (?1, ?2, ?3)",
+            params![uri, start, end],
+        )
+        .map_err(Error::DbError)?;
+    }
+    tx.commit().map_err(Error::DbError)?;
+    Ok(())
+}
+
+/// Save a queue snapshot, replacing any existing one with the same name.
+pub fn write_queue_snapshot(snapshot: &QueueSnapshot) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(Error::DbError)?;
+    tx.execute("delete from queue_snapshots where name = ?1", params![&snapshot.name])
+        .map_err(Error::DbError)?;
+    tx.execute("delete from queue_snapshot_songs where snapshot_name = ?1", params![&snapshot.name])
+        .map_err(Error::DbError)?;
+    tx.execute(
+        "insert into queue_snapshots (name, saved_at, pos, elapsed, flow, random, consume)
+values (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            &snapshot.name,
+            OffsetDateTime::now_utc(),
+            snapshot.pos,
+            snapshot.elapsed,
+            snapshot.flow.to_str(),
+            snapshot.random,
+            snapshot.consume
+        ],
+    )
+    .map_err(Error::DbError)?;
+    for (idx, uri) in snapshot.uris.iter().enumerate() {
+        tx.execute(
+            "insert into queue_snapshot_songs (snapshot_name, idx, uri) values (?1, ?2, ?3)",
+            params![&snapshot.name, idx, uri],
+        )
+        .map_err(Error::DbError)?;
+    }
+    tx.commit().map_err(Error::DbError)?;
+    Ok(())
+}
+
+/// List saved queue snapshots as (name, formatted save time, number of songs), most
+/// recently saved first.
+pub fn get_queue_snapshots() -> Result<Vec<(String, String, u32)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select name, saved_at, (
+    select count(*) from queue_snapshot_songs where snapshot_name = queue_snapshots.name
+) from queue_snapshots order by saved_at desc")
+        .unwrap();
+    Ok(
+        query
+            .query_map([], |r| {
+                Ok((
+                    r.get::<usize, String>(0)?,
+                    format_datetime_local_tz(r.get::<usize, OffsetDateTime>(1)?),
+                    r.get::<usize, u32>(2)?
+                ))
+            })
+            .map_err(Error::DbError)?
+            .map(|r| r.unwrap())
+            .collect()
+    )
+}
+
+pub fn get_queue_snapshot(name: &str) -> Result<Option<QueueSnapshot>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select pos, elapsed, flow, random, consume from queue_snapshots where name = ?1")
+        .unwrap();
+    let Some(mut snapshot) = query
+        .query_one(params![name], |r| {
+            Ok(QueueSnapshot {
+                name: name.to_owned(),
+                uris: Vec::new(),
+                pos: r.get::<usize, Option<u32>>(0)?,
+                elapsed: r.get::<usize, f64>(1)?,
+                flow: PlaybackFlow::from_str(&r.get::<usize, String>(2)?).unwrap_or_default(),
+                random: r.get::<usize, bool>(3)?,
+                consume: r.get::<usize, bool>(4)?
+            })
+        })
+        .optional()
+        .map_err(Error::DbError)? else {
+            return Ok(None);
+        };
+    let mut songs_query = conn
+        .prepare("select uri from queue_snapshot_songs where snapshot_name = ?1 order by idx")
+        .unwrap();
+    snapshot.uris = songs_query
+        .query_map(params![name], |r| r.get::<usize, String>(0))
+        .map_err(Error::DbError)?
+        .map(|r| r.unwrap())
+        .collect();
+    Ok(Some(snapshot))
+}
+
+pub fn delete_queue_snapshot(name: &str) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(Error::DbError)?;
+    tx.execute("delete from queue_snapshots where name = ?1", params![name])
+        .map_err(Error::DbError)?;
+    tx.execute("delete from queue_snapshot_songs where snapshot_name = ?1", params![name])
+        .map_err(Error::DbError)?;
+    tx.commit().map_err(Error::DbError)?;
+    Ok(())
+}
+
+fn radio_station_from_row(r: &Row) -> std::result::Result<RadioStation, SqliteError> {
+    Ok(RadioStation {
+        name: r.get::<usize, String>(0)?,
+        url: r.get::<usize, String>(1)?,
+        genre: r.get::<usize, Option<String>>(2)?
+    })
+}
+
+pub fn get_radio_stations() -> Result<Vec<RadioStation>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select name, url, genre from radio_stations order by name collate nocase")
+        .unwrap();
+    Ok(
+        query
+            .query_map([], radio_station_from_row)
+            .map_err(Error::DbError)?
+            .map(|r| r.unwrap())
+            .collect()
+    )
+}
+
+pub fn find_radio_station_by_url(url: &str) -> Result<Option<RadioStation>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select name, url, genre from radio_stations where url = ?1")
+        .unwrap();
+    query
+        .query_one(params![url], radio_station_from_row)
+        .optional()
+        .map_err(Error::DbError)
+}
+
+/// Add a new station. Stations are keyed by name, so if another station by the same name
+/// but with a different URL already exists it is left alone & false is returned. Adding a
+/// station that already exists with the same URL only updates its genre.
+pub fn add_radio_station(station: &RadioStation) -> Result<bool, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let n_changed = conn
+        .execute(
+            "insert into radio_stations (name, url, genre, added_at) values (?1, ?2, ?3, ?4)
+on conflict(name) do update set genre = excluded.genre where radio_stations.url = excluded.url",
+            params![&station.name, &station.url, station.genre.as_ref(), OffsetDateTime::now_utc()],
+        )
+        .map_err(Error::DbError)?;
+    Ok(n_changed > 0)
+}
+
+/// Update the URL & genre of an existing station (or add it if it's gone by now).
+pub fn write_radio_station(station: &RadioStation) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute(
+        "insert into radio_stations (name, url, genre, added_at) values (?1, ?2, ?3, ?4)
+on conflict(name) do update set url = excluded.url, genre = excluded.genre",
+        params![&station.name, &station.url, station.genre.as_ref(), OffsetDateTime::now_utc()],
+    )
+    .map_err(Error::DbError)?;
+    Ok(())
+}
+
+pub fn delete_radio_station(name: &str) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(Error::DbError)?;
+    tx.execute("delete from radio_stations where name = ?1", params![name])
+        .map_err(Error::DbError)?;
+    tx.execute("delete from radio_titles where station_name = ?1", params![name])
+        .map_err(Error::DbError)?;
+    tx.commit().map_err(Error::DbError)?;
+    Ok(())
+}
+
+/// Log a stream title (as reported by the station via ICY metadata).
+pub fn add_radio_title(station_name: &str, title: &str) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute(
+        "insert into radio_titles (station_name, title, timestamp) values (?1, ?2, ?3)",
+        params![station_name, title, OffsetDateTime::now_utc()],
+    )
+    .map_err(Error::DbError)?;
+    Ok(())
+}
+
+/// Get up to N last titles played by a station as (title, formatted time), most recent
+/// first.
+pub fn get_radio_titles(station_name: &str, n: u32) -> Result<Vec<(String, String)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select title, timestamp from radio_titles where station_name = ?1
+order by timestamp desc limit ?2")
+        .unwrap();
+    Ok(
+        query
+            .query_map(params![station_name, n], |r| {
+                Ok((
+                    r.get::<usize, String>(0)?,
+                    format_datetime_local_tz(r.get::<usize, OffsetDateTime>(1)?)
+                ))
+            })
+            .map_err(Error::DbError)?
+            .map(|r| r.unwrap())
+            .collect()
+    )
+}
+
+pub fn clear_radio_titles(station_name: &str) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute("delete from radio_titles where station_name = ?1", params![station_name])
+        .map_err(Error::DbError)?;
+    Ok(())
+}
+
+fn podcast_feed_from_row(r: &Row) -> std::result::Result<PodcastFeed, SqliteError> {
+    Ok(PodcastFeed {
+        url: r.get::<usize, String>(0)?,
+        title: r.get::<usize, String>(1)?,
+        description: r.get::<usize, Option<String>>(2)?,
+        link: r.get::<usize, Option<String>>(3)?,
+        image_url: r.get::<usize, Option<String>>(4)?,
+        last_checked: r.get::<usize, Option<OffsetDateTime>>(5)?,
+        last_error: r.get::<usize, Option<String>>(6)?
+    })
+}
+
+pub fn get_podcast_feeds() -> Result<Vec<PodcastFeed>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select url, title, description, link, image_url, last_checked, last_error
+from podcast_feeds order by title collate nocase")
+        .unwrap();
+    Ok(
+        query
+            .query_map([], podcast_feed_from_row)
+            .map_err(Error::DbError)?
+            .map(|r| r.unwrap())
+            .collect()
+    )
+}
+
+/// Subscribe to a feed. It will be downloaded on the next refresh.
+pub fn add_podcast_feed(url: &str) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let n = conn.execute(
+        "insert into podcast_feeds (url, title, added_at) values (?1, ?1, ?2)
+on conflict(url) do nothing",
+        params![url, OffsetDateTime::now_utc()],
+    )
+    .map_err(Error::DbError)?;
+    if n == 0 {
+        return Err(Error::KeyAlreadyExists);
+    }
+    Ok(())
+}
+
+/// Update a feed's details after a successful download.
+pub fn update_podcast_feed(feed: &PodcastFeed) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute(
+        "update podcast_feeds set title = ?2, description = ?3, link = ?4, image_url = ?5,
+last_checked = ?6, last_error = null where url = ?1",
+        params![
+            &feed.url,
+            &feed.title,
+            feed.description.as_ref(),
+            feed.link.as_ref(),
+            feed.image_url.as_ref(),
+            OffsetDateTime::now_utc()
+        ],
+    )
+    .map_err(Error::DbError)?;
+    Ok(())
+}
+
+/// Record a failed download. The feed won't be retried until its next scheduled refresh.
+pub fn set_podcast_feed_error(url: &str, error: &str) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute(
+        "update podcast_feeds set last_checked = ?2, last_error = ?3 where url = ?1",
+        params![url, OffsetDateTime::now_utc(), error],
+    )
+    .map_err(Error::DbError)?;
+    Ok(())
+}
+
+pub fn delete_podcast_feed(url: &str) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(Error::DbError)?;
+    tx.execute("delete from podcast_feeds where url = ?1", params![url])
+        .map_err(Error::DbError)?;
+    tx.execute("delete from podcast_episodes where feed_url = ?1", params![url])
+        .map_err(Error::DbError)?;
+    tx.commit().map_err(Error::DbError)?;
+    Ok(())
+}
+
+/// Add newly-published episodes and update the details of known ones, keeping their
+/// listened state and resume position. Returns the number of new episodes.
+pub fn write_podcast_episodes(episodes: &[PodcastEpisode]) -> Result<usize, Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(Error::DbError)?;
+    let mut n_new: usize = 0;
+    for ep in episodes.iter() {
+        let inserted = tx.execute(
+            "insert into podcast_episodes (feed_url, guid, title, url, description, published, duration)
+values (?1, ?2, ?3, ?4, ?5, ?6, ?7) on conflict(feed_url, guid) do nothing",
+            params![&ep.feed_url, &ep.guid, &ep.title, &ep.url, ep.description.as_ref(), ep.published, ep.duration],
+        )
+        .map_err(Error::DbError)?;
+        if inserted > 0 {
+            n_new += 1;
+        } else {
+            tx.execute(
+                "update podcast_episodes set title = ?3, url = ?4, description = ?5, published = ?6,
+duration = ?7 where feed_url = ?1 and guid = ?2",
+                params![&ep.feed_url, &ep.guid, &ep.title, &ep.url, ep.description.as_ref(), ep.published, ep.duration],
+            )
+            .map_err(Error::DbError)?;
+        }
+    }
+    tx.commit().map_err(Error::DbError)?;
+    Ok(n_new)
+}
+
+fn podcast_episode_from_row(r: &Row) -> std::result::Result<PodcastEpisode, SqliteError> {
+    Ok(PodcastEpisode {
+        feed_url: r.get::<usize, String>(0)?,
+        guid: r.get::<usize, String>(1)?,
+        title: r.get::<usize, String>(2)?,
+        url: r.get::<usize, String>(3)?,
+        description: r.get::<usize, Option<String>>(4)?,
+        published: r.get::<usize, Option<OffsetDateTime>>(5)?,
+        duration: r.get::<usize, Option<f64>>(6)?,
+        listened: r.get::<usize, bool>(7)?,
+        position: r.get::<usize, f64>(8)?
+    })
+}
+
+/// Get up to N latest episodes of a feed, newest first.
+pub fn get_podcast_episodes(feed_url: &str, n: u32) -> Result<Vec<PodcastEpisode>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select feed_url, guid, title, url, description, published, duration, listened, position
+from podcast_episodes where feed_url = ?1 order by published desc nulls last limit ?2")
+        .unwrap();
+    Ok(
+        query
+            .query_map(params![feed_url, n], podcast_episode_from_row)
+            .map_err(Error::DbError)?
+            .map(|r| r.unwrap())
+            .collect()
+    )
+}
+
+pub fn find_podcast_episode_by_url(url: &str) -> Result<Option<PodcastEpisode>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select feed_url, guid, title, url, description, published, duration, listened, position
+from podcast_episodes where url = ?1 limit 1")
+        .unwrap();
+    query
+        .query_one(params![url], podcast_episode_from_row)
+        .optional()
+        .map_err(Error::DbError)
+}
+
+pub fn count_unlistened_podcast_episodes(feed_url: &str) -> Result<u32, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.query_row(
+        "select count(*) from podcast_episodes where feed_url = ?1 and not listened",
+        params![feed_url],
+        |r| r.get::<usize, u32>(0),
+    )
+    .map_err(Error::DbError)
+}
+
+pub fn set_podcast_episode_progress(feed_url: &str, guid: &str, position: f64, listened: bool) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute(
+        "update podcast_episodes set position = ?3, listened = ?4 where feed_url = ?1 and guid = ?2",
+        params![feed_url, guid, position, listened],
+    )
+    .map_err(Error::DbError)?;
+    Ok(())
+}
+
+/// Mark every episode of a feed as listened (or not), forgetting resume positions.
+pub fn set_podcast_feed_listened(feed_url: &str, listened: bool) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute(
+        "update podcast_episodes set position = 0, listened = ?2 where feed_url = ?1",
+        params![feed_url, listened],
+    )
+    .map_err(Error::DbError)?;
+    Ok(())
+}
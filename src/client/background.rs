@@ -1,5 +1,5 @@
 use std::{
-    borrow::Cow, cmp::Ordering as StdOrdering, hash::BuildHasherDefault, i64, num::NonZero, ops::Range, sync::Mutex
+    borrow::Cow, collections::HashMap, cmp::Ordering as StdOrdering, hash::BuildHasherDefault, i64, num::NonZero, ops::Range, sync::Mutex
 };
 use chrono::{DateTime, Duration, Local};
 
@@ -14,11 +14,11 @@ use time::OffsetDateTime;
 
 use mpd::{
     error::{Error as MpdError, ErrorCode},
-    search::{Operation as QueryOperation, Query, Term, Window}, Id,
+    search::{Operation as QueryOperation, Query, Term, Window}, song::Range as SongRange, Id,
 };
 use rustc_hash::FxHashSet;
 
-use crate::{cache::{get_new_image_paths, sqlite}, common::{dynamic_playlist::{Ordering, QueryLhs, Rule, StickerObjectType, StickerOperation}, SongInfo}, meta_providers::ProviderMessage, utils::{self, strip_filename_linux}};
+use crate::{cache::{get_new_image_paths, sqlite}, common::{dynamic_playlist::{Ordering, QueryLhs, Rule, StickerObjectType, StickerOperation}, parse_mb_artist_tag, ArtistRadio, SongInfo}, meta_providers::ProviderMessage, utils::{self, strip_filename_linux}};
 
 use super::*;
 
@@ -99,6 +99,77 @@ pub fn get_current_queue(
     }
 }
 
+/// Drop cached queued songs so that their next change will be fetched afresh. Needed for
+/// changes that only affect queue metadata, such as priorities, as changesposid only gives
+/// us IDs and positions.
+pub fn forget_queued_songs(ids: &[u32]) {
+    let mut cache = QUEUED_SONG_CACHE.lock().unwrap();
+    for id in ids.iter() {
+        cache.pop(id);
+    }
+}
+
+pub fn search_queue(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    term: String
+) {
+    // We only need queue IDs, so turn off tags to reduce server & connection burden.
+    let res = client.tagtypes_clear().and_then(|_| client.playlistsearch(
+        Query::new().and_with_op(Term::Any, QueryOperation::Contains, term.clone())
+    ));
+    let _ = client.tagtypes_all();
+    match res {
+        Ok(songs) => {
+            let ids: Vec<u32> = songs
+                .into_iter()
+                .filter_map(|song| song.place.map(|place| place.id.0))
+                .collect();
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::QueueSearchResults(term, ids));
+        }
+        Err(mpd_error) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+        }
+    }
+}
+
+/// Maximum number of songs returned by a library search.
+const LIBRARY_SEARCH_LIMIT: u32 = 50;
+
+pub fn search_library(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    term: String
+) {
+    let mut res = Ok(Vec::new());
+    // Stream titles are usually "Artist - Title". Try that first.
+    if let Some((artist, title)) = term.split_once(" - ") {
+        res = client.search(
+            Query::new()
+                .and_with_op(Term::Tag(Cow::Borrowed("artist")), QueryOperation::Contains, artist.trim())
+                .and_with_op(Term::Tag(Cow::Borrowed("title")), QueryOperation::Contains, title.trim()),
+            Window::from((0, LIBRARY_SEARCH_LIMIT))
+        );
+    }
+    if res.as_ref().is_ok_and(|songs| songs.is_empty()) {
+        res = client.search(
+            Query::new().and_with_op(Term::Any, QueryOperation::Contains, term.clone()),
+            Window::from((0, LIBRARY_SEARCH_LIMIT))
+        );
+    }
+    match res {
+        Ok(songs) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::LibrarySearchResults(
+                term,
+                songs.into_iter().map(SongInfo::from).collect()
+            ));
+        }
+        Err(mpd_error) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+        }
+    }
+}
+
 pub fn get_queue_changes(
     client: &mut mpd::Client<stream::StreamWrapper>,
     sender_to_fg: &Sender<AsyncClientMessage>,
@@ -156,7 +227,7 @@ pub fn get_queue_changes(
     }
 }
 
-fn download_embedded_cover_inner(
+pub fn download_embedded_cover_inner(
     client: &mut mpd::Client<stream::StreamWrapper>,
     uri: String,
 ) -> Option<(gdk::Texture, gdk::Texture)> {
@@ -197,7 +268,7 @@ fn download_embedded_cover_inner(
     }
 }
 
-fn download_folder_cover_inner(
+pub fn download_folder_cover_inner(
     client: &mut mpd::Client<stream::StreamWrapper>,
     folder_uri: String,
 ) -> Option<(gdk::Texture, gdk::Texture)> {
@@ -373,10 +444,10 @@ pub fn download_folder_cover(
 fn fetch_albums_by_query<F>(
     client: &mut mpd::Client<stream::StreamWrapper>,
     query: &Query,
-    respond: F,
+    mut respond: F,
 ) -> Result<(), MpdError>
 where
-    F: Fn(AlbumInfo) -> Result<(), SendError<AsyncClientMessage>>,
+    F: FnMut(AlbumInfo) -> Result<(), SendError<AsyncClientMessage>>,
 {
     // TODO: batched windowed retrieval
     // Get list of unique album tags, grouped by albumartist
@@ -532,6 +603,19 @@ pub fn fetch_all_albums(
     }
 }
 
+/// Query for an album as recorded in the listening history.
+fn history_album_query(title: String, artist: Option<String>, mbid: Option<String>) -> Query<'static> {
+    let mut query = Query::new();
+    query.and(Term::Tag(Cow::Borrowed("album")), title);
+    if let Some(artist) = artist {
+        query.and(Term::Tag(Cow::Borrowed("albumartist")), artist);
+    }
+    if let Some(mbid) = mbid {
+        query.and(Term::Tag(Cow::Borrowed("musicbrainz_albumid")), mbid);
+    }
+    query
+}
+
 pub fn fetch_recent_albums(
     client: &mut mpd::Client<stream::StreamWrapper>,
     sender_to_fg: &Sender<AsyncClientMessage>,
@@ -539,15 +623,8 @@ pub fn fetch_recent_albums(
     let settings = utils::settings_manager().child("library");
     let recent_albums =
         sqlite::get_last_n_albums(settings.uint("n-recent-albums")).expect("Sqlite DB error");
-    for tup in recent_albums.into_iter() {
-        let mut query = Query::new();
-        query.and(Term::Tag(Cow::Borrowed("album")), tup.0);
-        if let Some(artist) = tup.1 {
-            query.and(Term::Tag(Cow::Borrowed("albumartist")), artist);
-        }
-        if let Some(mbid) = tup.2 {
-            query.and(Term::Tag(Cow::Borrowed("musicbrainz_albumid")), mbid);
-        }
+    for (title, artist, mbid) in recent_albums.into_iter() {
+        let query = history_album_query(title, artist, mbid);
         if let Err(mpd_error) = fetch_albums_by_query(client, &query, |info| {
             sender_to_fg.send_blocking(AsyncClientMessage::RecentAlbumDownloaded(info))
         }) {
@@ -596,65 +673,109 @@ pub fn fetch_album_songs(
     );
 }
 
+/// Fetch all artists found in the given tag. Artist tags usually contain multiple
+/// artists, and one artist can appear in multiple tags, so we reuse the artist parsing
+/// code in our SongInfo struct and deduplicate the parsed ArtistInfos by name.
+fn fetch_artists_by_tag<F>(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    tag_type: &'static str,
+    mut respond: F,
+) -> Result<(), MpdError>
+where
+    F: FnMut(ArtistInfo),
+{
+    let mut already_parsed: FxHashSet<String> = FxHashSet::default();
+    let grouped_vals = client.list(&Term::Tag(Cow::Borrowed(tag_type)), &Query::new(), None)?;
+    // TODO: Limit tags to only what we need locally
+    for tag in &grouped_vals.groups[0].1 {
+        if let Ok(mut songs) = client.find(
+            Query::new().and(Term::Tag(Cow::Borrowed(tag_type)), tag),
+            Window::from((0, 1)),
+        ) {
+            if !songs.is_empty() {
+                let first_song = SongInfo::from(std::mem::take(&mut songs[0]));
+                let artists = first_song.into_artist_infos();
+                for artist in artists.into_iter() {
+                    if already_parsed.insert(artist.name.clone()) {
+                        respond(artist);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 pub fn fetch_artists(
     client: &mut mpd::Client<stream::StreamWrapper>,
     sender_to_fg: &Sender<AsyncClientMessage>,
     use_album_artist: bool,
 ) {
-    // Fetching artists is a bit more involved: artist tags usually contain multiple artists.
-    // For the same reason, one artist can appear in multiple tags.
-    // Here we'll reuse the artist parsing code in our SongInfo struct and put parsed
-    // ArtistInfos in a Set to deduplicate them.
     let tag_type: &'static str = if use_album_artist {
         "albumartist"
     } else {
         "artist"
     };
-    let mut already_parsed: FxHashSet<String> = FxHashSet::default();
-    match client.list(&Term::Tag(Cow::Borrowed(tag_type)), &Query::new(), None) {
-        Ok(grouped_vals) => {
-            // TODO: Limit tags to only what we need locally
-            for tag in &grouped_vals.groups[0].1 {
-                if let Ok(mut songs) = client.find(
-                    Query::new().and(Term::Tag(Cow::Borrowed(tag_type)), tag),
-                    Window::from((0, 1)),
-                ) {
-                    if !songs.is_empty() {
-                        let first_song = SongInfo::from(std::mem::take(&mut songs[0]));
-                        let artists = first_song.into_artist_infos();
-                        // println!("Got these artists: {artists:?}");
-                        for artist in artists.into_iter() {
-                            if already_parsed.insert(artist.name.clone()) {
-                                // println!("Never seen {artist:?} before, inserting...");
-                                let _ = sender_to_fg.send_blocking(
-                                    AsyncClientMessage::ArtistBasicInfoDownloaded(artist),
-                                );
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        Err(mpd_error) => {
-            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
-        }
+    if let Err(mpd_error) = fetch_artists_by_tag(client, tag_type, |artist| {
+        let _ = sender_to_fg.send_blocking(AsyncClientMessage::ArtistBasicInfoDownloaded(artist));
+    }) {
+        let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
     }
 }
 
-pub fn fetch_recent_artists(
+/// Blocking. List all albums in the library at once, for jobs that walk the whole library
+/// on a connection of their own rather than through the background client.
+pub fn list_all_albums(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+) -> Result<Vec<AlbumInfo>, MpdError> {
+    let mut albums = Vec::new();
+    fetch_albums_by_query(client, &Query::new(), |info| {
+        albums.push(info);
+        Ok(())
+    })?;
+    Ok(albums)
+}
+
+/// Blocking. List all artists in the library at once. See list_all_albums().
+pub fn list_all_artists(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+) -> Result<Vec<ArtistInfo>, MpdError> {
+    let mut artists = Vec::new();
+    fetch_artists_by_tag(client, "artist", |artist| artists.push(artist))?;
+    Ok(artists)
+}
+
+/// Blocking. List the songs of an album, telling apart same-named albums by their
+/// AlbumArtist tag. See list_all_albums().
+pub fn list_album_songs(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    album: &AlbumInfo,
+) -> Vec<SongInfo> {
+    let mut query = Query::new();
+    query.and(Term::Tag(Cow::Borrowed("album")), album.title.clone());
+    if let Some(artist) = album.albumartist.as_ref() {
+        query.and(Term::Tag(Cow::Borrowed("albumartist")), artist.clone());
+    }
+    let mut res = Vec::new();
+    fetch_songs_by_query(client, &query, |songs| {
+        res.extend(songs);
+        Ok(())
+    });
+    res
+}
+
+/// Find the ArtistInfos of the given artist names, in the same order. Names no longer
+/// found in the library are skipped. Returns None on connection errors, after asking the
+/// main thread to reconnect.
+fn find_artist_infos(
     client: &mut mpd::Client<stream::StreamWrapper>,
     sender_to_fg: &Sender<AsyncClientMessage>,
-) {
+    names: &[String],
+) -> Option<Vec<ArtistInfo>> {
     let mut already_parsed: FxHashSet<String> = FxHashSet::default();
-    let settings = utils::settings_manager().child("library");
-    let n = settings.uint("n-recent-artists");
-    let mut res: Vec<ArtistInfo> = Vec::with_capacity(n as usize);
-    let recent_names = sqlite::get_last_n_artists(n).expect("Sqlite DB error");
-    let mut recent_names_set: FxHashSet<String> = FxHashSet::default();
-    for name in recent_names.iter() {
-        recent_names_set.insert(name.clone());
-    }
-    for name in recent_names.iter() {
+    let mut res: Vec<ArtistInfo> = Vec::with_capacity(names.len());
+    let names_set: FxHashSet<&str> = names.iter().map(String::as_str).collect();
+    for name in names.iter() {
         match client.find(
             Query::new().and_with_op(
                 Term::Tag(Cow::Borrowed("artist")),
@@ -668,7 +789,7 @@ pub fn fetch_recent_artists(
                     let first_song = SongInfo::from(std::mem::take(&mut songs[0]));
                     let artists = first_song.into_artist_infos();
                     for artist in artists.into_iter() {
-                        if recent_names_set.contains(&artist.name)
+                        if names_set.contains(artist.name.as_str())
                             && already_parsed.insert(artist.name.clone()) {
                                 res.push(artist);
                             }
@@ -678,11 +799,23 @@ pub fn fetch_recent_artists(
             Err(MpdError::Io(_)) => {
                 // Connection error => attempt to reconnect
                 let _ = sender_to_fg.send_blocking(AsyncClientMessage::Connect);
-                return;
+                return None;
             }
             _ => {}
         }
     }
+    Some(res)
+}
+
+pub fn fetch_recent_artists(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+) {
+    let settings = utils::settings_manager().child("library");
+    let recent_names = sqlite::get_last_n_artists(settings.uint("n-recent-artists")).expect("Sqlite DB error");
+    let Some(res) = find_artist_infos(client, sender_to_fg, &recent_names) else {
+        return;
+    };
 
     for artist in res.into_iter() {
         let _ = sender_to_fg.send_blocking(AsyncClientMessage::RecentArtistDownloaded(artist));
@@ -865,6 +998,340 @@ pub fn fetch_last_n_songs(
     }
 }
 
+fn fetch_co_listened<F>(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    co_listened: Result<sqlite::CoListened, sqlite::Error>,
+    respond: F
+) where
+    F: Fn(Vec<SongInfo>, Vec<AlbumInfo>, Vec<ArtistInfo>) -> AsyncClientMessage
+{
+    let co_listened = match co_listened {
+        Ok(co_listened) => co_listened,
+        Err(e) => {
+            dbg!(e);
+            return;
+        }
+    };
+    // Songs, albums & artists that have since been removed from the library are silently skipped.
+    let songs = match fetch_songs_by_uri(
+        client,
+        &co_listened.songs.iter().map(String::as_str).collect::<Vec<&str>>(),
+        false
+    ) {
+        Ok(raw_songs) => raw_songs.into_iter().map(|pair| pair.0).collect(),
+        Err(error) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(error, None));
+            return;
+        }
+    };
+    let mut albums: Vec<AlbumInfo> = Vec::with_capacity(co_listened.albums.len());
+    for (title, artist, mbid) in co_listened.albums.into_iter() {
+        let query = history_album_query(title, artist, mbid);
+        if let Err(error) = fetch_albums_by_query(client, &query, |info| {
+            albums.push(info);
+            Ok(())
+        }) {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(error, None));
+            return;
+        }
+    }
+    let Some(artists) = find_artist_infos(client, sender_to_fg, &co_listened.artists) else {
+        return;
+    };
+    let _ = sender_to_fg.send_blocking(respond(songs, albums, artists));
+}
+
+pub fn fetch_album_co_listened(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    tag: String,
+    session_gap_mins: u32,
+    n: u32
+) {
+    let co_listened = sqlite::get_album_co_listened(&tag, session_gap_mins, n);
+    fetch_co_listened(client, sender_to_fg, co_listened, |songs, albums, artists| {
+        AsyncClientMessage::AlbumCoListenedDownloaded(tag.clone(), songs, albums, artists)
+    });
+}
+
+pub fn fetch_artist_co_listened(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    name: String,
+    session_gap_mins: u32,
+    n: u32
+) {
+    let co_listened = sqlite::get_artist_co_listened(&name, session_gap_mins, n);
+    fetch_co_listened(client, sender_to_fg, co_listened, |songs, albums, artists| {
+        AsyncClientMessage::ArtistCoListenedDownloaded(name.clone(), songs, albums, artists)
+    });
+}
+
+/// Weighted random sampling without replacement (Efraimidis-Spirakis). Items with
+/// non-positive weights are never picked.
+fn weighted_sample<T>(items: Vec<(T, f64)>, n: usize) -> Vec<(T, f64)> {
+    let mut keyed: Vec<(f64, (T, f64))> = items
+        .into_iter()
+        .filter(|(_, weight)| *weight > 0.0)
+        .map(|item| (rand::random::<f64>().powf(1.0 / item.1), item))
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keyed.truncate(n);
+    keyed.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Multiplier applied to a song's sampling weight based on its rating (0-10).
+/// Unrated songs sit in the middle so they still get a fair chance.
+fn rating_weight(rating: Option<i8>) -> f64 {
+    match rating {
+        Some(r) if r > 0 => 0.2 + 0.08 * (r.min(10) as f64),
+        _ => 0.5,
+    }
+}
+
+fn artist_radio_pool(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    radio: &ArtistRadio
+) -> Result<Vec<(String, f64)>, MpdError> {
+    // Find out which of the similar artists actually exist in the library. Artist tags
+    // may contain several artists, so split them the same way the Artist View does.
+    let mut library_artists: FxHashSet<String> = FxHashSet::default();
+    let grouped_vals = client.list(&Term::Tag(Cow::Borrowed("artist")), &Query::new(), None)?;
+    for tag in grouped_vals.groups[0].1.iter() {
+        for name in parse_mb_artist_tag(tag) {
+            library_artists.insert(name.to_lowercase());
+        }
+    }
+    let mut candidates: Vec<(&str, f64)> = vec![(radio.seed.as_str(), 1.0)];
+    for (rank, name) in radio.similar.iter().enumerate() {
+        if library_artists.contains(&name.to_lowercase()) {
+            candidates.push((name.as_str(), radio.similarity_weight(rank)));
+        }
+    }
+    println!(
+        "[Artist radio] {} of {} similar artists found in library",
+        candidates.len() - 1,
+        radio.similar.len()
+    );
+
+    let mut seen: FxHashSet<String> = FxHashSet::default();
+    let mut pool: Vec<(String, f64)> = Vec::new();
+    for (name, weight) in candidates.into_iter() {
+        let mut query = Query::new();
+        query.and_with_op(
+            Term::Tag(Cow::Borrowed("artist")),
+            QueryOperation::Contains,
+            name.to_owned()
+        );
+        fetch_songs_by_query(client, &query, |batch| {
+            for song in batch.into_iter() {
+                // Substring matches might pick up unrelated artists with similar names.
+                if song.artists.iter().any(|artist| artist.name.eq_ignore_ascii_case(name))
+                    && seen.insert(song.uri.clone())
+                {
+                    pool.push((song.uri, weight));
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok(pool)
+}
+
+/// Get all candidate URIs of an Auto-DJ source along with their base weights.
+fn auto_dj_pool(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    source: &AutoDjSource
+) -> Result<Vec<(String, f64)>, MpdError> {
+    if let AutoDjSource::ArtistRadio(radio) = source {
+        return artist_radio_pool(client, radio);
+    }
+    // We only need URIs from here on, so turn off tags to reduce server & connection burden.
+    client.tagtypes_clear()?;
+    let mut uris: Vec<String> = Vec::new();
+    match source {
+        AutoDjSource::DynamicPlaylist(name) => match sqlite::get_dynamic_playlist_info(name) {
+            Ok(Some(dp)) => {
+                uris = resolve_dynamic_playlist_rules(client, dp.rules);
+            }
+            Ok(None) => {
+                println!("[Auto-DJ] Dynamic playlist {name} no longer exists");
+            }
+            Err(db_err) => {
+                dbg!(db_err);
+            }
+        },
+        AutoDjSource::Playlist(name) => {
+            fetch_playlist_songs_internal(
+                client,
+                name,
+                |songs| uris.extend(songs.into_iter().map(|song| song.uri)),
+                |mpd_error| {
+                    dbg!(mpd_error);
+                }
+            );
+        }
+        AutoDjSource::Genre(genre) => {
+            let mut query = Query::new();
+            query.and(Term::Tag(Cow::Borrowed("genre")), genre.to_owned());
+            fetch_songs_by_query(client, &query, |batch| {
+                uris.extend(batch.into_iter().map(|song| song.uri));
+                Ok(())
+            });
+        }
+        AutoDjSource::Library => {
+            // Dummy term that basically matches everything.
+            let mut query = Query::new();
+            query.and(Term::AddedSince, i64::MIN.to_string());
+            fetch_songs_by_query(client, &query, |batch| {
+                uris.extend(batch.into_iter().map(|song| song.uri));
+                Ok(())
+            });
+        }
+        AutoDjSource::ArtistRadio(_) => unreachable!(),
+    }
+    client.tagtypes_all()?;
+    // Stored playlists may contain duplicates.
+    let mut seen: FxHashSet<String> = FxHashSet::default();
+    Ok(uris
+        .into_iter()
+        .filter(|uri| seen.insert(uri.clone()))
+        .map(|uri| (uri, 1.0))
+        .collect())
+}
+
+fn sample_auto_dj(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    source: &AutoDjSource,
+    n: u32,
+    exclude: &[String]
+) -> Result<Vec<String>, MpdError> {
+    let excluded: FxHashSet<&str> = exclude.iter().map(String::as_str).collect();
+    let mut pool: Vec<(String, f64)> = auto_dj_pool(client, source)?
+        .into_iter()
+        .filter(|(uri, _)| !excluded.contains(uri.as_str()))
+        .collect();
+
+    // Avoid recently played songs, unless doing so would leave us with too few songs
+    // (small playlists, narrow genres, etc).
+    let n_recent = utils::settings_manager().child("player").uint("auto-dj-avoid-recent");
+    if n_recent > 0 {
+        match sqlite::get_last_n_songs(n_recent) {
+            Ok(recent) => {
+                let recent: FxHashSet<String> = recent.into_iter().map(|(uri, _)| uri).collect();
+                let fresh: Vec<(String, f64)> = pool
+                    .iter()
+                    .filter(|(uri, _)| !recent.contains(uri))
+                    .cloned()
+                    .collect();
+                if fresh.len() >= n as usize {
+                    pool = fresh;
+                }
+            }
+            Err(db_err) => {
+                dbg!(db_err);
+            }
+        }
+    }
+
+    // Oversample by base weight first, then only fetch ratings for the shortlist
+    // to avoid querying stickers for the whole pool.
+    let shortlist = weighted_sample(pool, n as usize * 3);
+    let mut rated: Vec<(String, f64)> = Vec::with_capacity(shortlist.len());
+    for (uri, weight) in shortlist.into_iter() {
+        let rating = client
+            .stickers("song", &uri)
+            .ok()
+            .and_then(|kvs| Stickers::from_mpd_kv(kvs).rating);
+        rated.push((uri, weight * rating_weight(rating)));
+    }
+    Ok(weighted_sample(rated, n as usize)
+        .into_iter()
+        .map(|(uri, _)| uri)
+        .collect())
+}
+
+/// Replace each sampled song with its whole album in track order, until at least n songs
+/// have been gathered. Used in album shuffle mode so that Auto-DJ queues full albums.
+/// Albumless songs are kept as-is.
+fn expand_to_albums(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    seeds: Vec<String>,
+    n: u32,
+    exclude: &[String]
+) -> Result<Vec<String>, MpdError> {
+    let excluded: FxHashSet<&str> = exclude.iter().map(String::as_str).collect();
+    let mut seen: FxHashSet<String> = FxHashSet::default();
+    let mut res: Vec<String> = Vec::new();
+    for seed in seeds.iter() {
+        if res.len() >= n as usize {
+            break;
+        }
+        if seen.contains(seed) {
+            // Already added as part of an earlier seed's album
+            continue;
+        }
+        let album = fetch_songs_by_uri(client, &[seed.as_str()], false)?
+            .pop()
+            .and_then(|(song, _)| song.album);
+        let mut songs: Vec<SongInfo> = Vec::new();
+        if let Some(album) = album {
+            let mut query = Query::new();
+            query.and(Term::Tag(Cow::Borrowed("album")), album.title);
+            if let Some(albumartist) = album.albumartist {
+                query.and(Term::Tag(Cow::Borrowed("albumartist")), albumartist);
+            }
+            fetch_songs_by_query(client, &query, |batch| {
+                songs.extend(batch);
+                Ok(())
+            });
+            songs.sort_by_key(|song| (song.disc.get(), song.track.get()));
+        }
+        if songs.is_empty() {
+            if seen.insert(seed.clone()) {
+                res.push(seed.clone());
+            }
+            continue;
+        }
+        for song in songs.into_iter() {
+            if !excluded.contains(song.uri.as_str()) && seen.insert(song.uri.clone()) {
+                res.push(song.uri);
+            }
+        }
+    }
+    Ok(res)
+}
+
+pub fn top_up_queue(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    source: &AutoDjSource,
+    n: u32,
+    exclude: &[String],
+    start_playing_pos: Option<u32>
+) {
+    let album_shuffle = utils::settings_manager().child("player").boolean("album-shuffle");
+    match sample_auto_dj(client, source, n, exclude).and_then(|uris| {
+        if album_shuffle {
+            expand_to_albums(client, uris, n, exclude)
+        } else {
+            Ok(uris)
+        }
+    }) {
+        Ok(uris) => {
+            if uris.is_empty() {
+                println!("[Auto-DJ] Source {source:?} ran out of songs");
+            } else {
+                add_multi(client, sender_to_fg, &uris, false, start_playing_pos, None);
+            }
+        }
+        Err(mpd_error) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, Some(ClientError::Queuing)));
+        }
+    }
+}
+
 pub fn play_at(
     client: &mut mpd::Client<stream::StreamWrapper>,
     id_or_pos: u32,
@@ -902,6 +1369,8 @@ pub fn find_add(
     }
 }
 
+/// Queue the given URIs, returning the queue IDs of the songs that made it in, in the same
+/// order as (a prefix of) uris. Recursive adds don't report IDs.
 pub fn add_multi(
     client: &mut mpd::Client<stream::StreamWrapper>,
     sender_to_fg: &Sender<AsyncClientMessage>,
@@ -909,11 +1378,17 @@ pub fn add_multi(
     recursive: bool,
     start_playing_pos: Option<u32>,
     insert_pos: Option<u32>
-) {
+) -> Vec<u32> {
+    let mut ids: Vec<u32> = Vec::with_capacity(if recursive { 0 } else { uris.len() });
     if uris.is_empty() {
-        return;
+        return ids;
     }
     let _ = sender_to_fg.send_blocking(AsyncClientMessage::Queuing(true));
+    let saved_ranges = if recursive {
+        HashMap::new()
+    } else {
+        sqlite::get_song_ranges(uris).unwrap_or_default()
+    };
     let mut res: Result<(), MpdError> = Ok(());
     if uris.len() > 1 {
         // Batch by batch to avoid holding the server up too long (and timing out)
@@ -921,10 +1396,11 @@ pub fn add_multi(
         while inserted < uris.len() {
             let to_insert = (uris.len() - inserted).min(BATCH_SIZE);
             res = if let Some(pos) = insert_pos {
-                client.insert_multiple(&uris[inserted..(inserted + to_insert)], pos as usize + inserted).map(|_| ())
+                client.insert_multiple(&uris[inserted..(inserted + to_insert)], pos as usize + inserted)
             } else {
-                client.push_multiple(&uris[inserted..(inserted + to_insert)]).map(|_| ())
-            };
+                client.push_multiple(&uris[inserted..(inserted + to_insert)])
+            }
+            .map(|batch_ids| ids.extend(batch_ids.into_iter().map(|id| id.0)));
             inserted += to_insert;
             if res.is_err() {
                 break;
@@ -935,12 +1411,17 @@ pub fn add_multi(
             // TODO: support inserting at specific location in queue
             client.findadd(Query::new().and(Term::Base, &uris[0])).map(|_| ())
         } else if let Some(pos) = insert_pos {
-            client.insert(&uris[0], pos as usize).map(|_| ())
+            client.insert(&uris[0], pos as usize).map(|id| ids.push(id as u32))
         } else {
-            client.push(&uris[0]).map(|_| ())
+            client.push(&uris[0]).map(|id| ids.push(id.0))
         };
     }
 
+    // Ranges are applied even if some batch failed, to whichever songs did make it in.
+    if !saved_ranges.is_empty() {
+        res = apply_saved_ranges(client, uris.iter().zip(ids.iter().copied()), &saved_ranges).and(res);
+    }
+
     if let Some(pos) = start_playing_pos {
         res = res.and_then(|_| {
             play_at(client, pos, false)
@@ -955,6 +1436,61 @@ pub fn add_multi(
             let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, Some(ClientError::Queuing)));
         }
     }
+    ids
+}
+
+pub fn restore_queue(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    uris: &[String],
+    resume: Option<(u32, f64)>,
+    paused: bool
+) {
+    add_multi(client, sender_to_fg, uris, false, resume.map(|(pos, _)| pos), None);
+    if let Some((_, elapsed)) = resume {
+        let res = client
+            .rewind(elapsed)
+            .and_then(|_| if paused { client.pause(true) } else { Ok(()) });
+        if let Err(mpd_error) = res {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+        }
+    }
+}
+
+pub fn move_ids(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    moves: &[(u32, u32)]
+) {
+    let moves: Vec<(Id, usize)> = moves
+        .iter()
+        .map(|(id, pos)| (Id(*id), *pos as usize))
+        .collect();
+    if let Err(mpd_error) = client.shift_multiple(&moves) {
+        let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+    }
+}
+
+/// Convert a play range in seconds into its MPD form.
+pub fn to_mpd_range(range: (f64, Option<f64>)) -> SongRange {
+    SongRange(
+        std::time::Duration::from_secs_f64(range.0.max(0.0)),
+        range.1.map(|end| std::time::Duration::from_secs_f64(end.max(0.0)))
+    )
+}
+
+/// Reapply saved play ranges to just-queued songs, given as (URI, queue ID) pairs.
+fn apply_saved_ranges<'a>(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    queued: impl Iterator<Item = (&'a String, u32)>,
+    ranges: &HashMap<String, (f64, Option<f64>)>
+) -> Result<(), MpdError> {
+    for (uri, id) in queued {
+        if let Some(range) = ranges.get(uri) {
+            client.rangeid(Id(id), to_mpd_range(*range))?;
+        }
+    }
+    Ok(())
 }
 
 pub fn load_playlist(
@@ -1339,6 +1875,26 @@ pub fn fetch_dynamic_playlist_cached(
     }
 }
 
+/// Set queue priorities of just-queued songs (given as (URI, queue ID) pairs) according to
+/// their ratings, such that in random mode better-rated songs are played sooner. Unrated songs
+/// are left at the default priority.
+fn prioritise_by_rating<'a>(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    queued: impl Iterator<Item = (&'a String, u32)>
+) -> Result<(), MpdError> {
+    for (uri, id) in queued {
+        let rating = client
+            .stickers("song", uri)
+            .ok()
+            .and_then(|kvs| Stickers::from_mpd_kv(kvs).rating);
+        if let Some(rating) = rating.filter(|r| *r > 0) {
+            // Ratings go from 0 to 10 (half-stars)
+            client.priority(Id(id), (rating.min(10) as u8) * 25)?;
+        }
+    }
+    Ok(())
+}
+
 pub fn queue_cached_dynamic_playlist(
     client: &mut mpd::Client<stream::StreamWrapper>,
     sender_to_fg: &Sender<AsyncClientMessage>,
@@ -1347,7 +1903,7 @@ pub fn queue_cached_dynamic_playlist(
 ) {
     let _ = sender_to_fg.send_blocking(AsyncClientMessage::Queuing(true));
     if let Ok(uris) = sqlite::get_cached_dynamic_playlist_results(name) {
-        add_multi(
+        let ids = add_multi(
             client,
             sender_to_fg,
             &uris,
@@ -1355,6 +1911,11 @@ pub fn queue_cached_dynamic_playlist(
             if play {Some(0)} else {None},
             None
         );
+        if utils::settings_manager().child("library").boolean("dp-rating-priority") {
+            if let Err(mpd_error) = prioritise_by_rating(client, uris.iter().zip(ids)) {
+                let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, Some(ClientError::Queuing)));
+            }
+        }
     }
 
     let _ = sender_to_fg.send_blocking(AsyncClientMessage::Queuing(false));
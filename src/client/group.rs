@@ -0,0 +1,204 @@
+use std::{
+    net::TcpStream,
+    os::unix::net::UnixStream,
+    sync::{Arc, Mutex},
+};
+
+use async_channel::Sender;
+use glib::ThreadPool;
+use mpd::{error::Error as MpdError, status::State, Client};
+use once_cell::sync::Lazy;
+use resolve_path::PathResolveExt;
+
+use super::{state::ConnectionState, stream::StreamWrapper, AsyncClientMessage};
+
+// Secondary servers controlled alongside the main one for multi-room playback. Unlike the
+// main connection, these don't have a background client: they never idle and we never
+// fetch anything heavy from them. We only fan commands out & occasionally copy the main
+// server's queue over.
+
+// Fanned-out commands are run on a single thread so that they reach each member in the
+// order they were issued, without blocking the main thread on the members' round trips.
+// Thread will be parked when idle.
+static GROUP_THREADPOOL: Lazy<ThreadPool> = Lazy::new(|| {
+    ThreadPool::shared(Some(1)).expect("Failed to spawn group threadpool")
+});
+
+/// One member of a playback group, other than the main server (the group leader).
+#[derive(Debug)]
+pub struct GroupMember {
+    /// Either host:port or the path to a local socket.
+    pub address: String,
+    pub state: ConnectionState,
+    pub client: Option<Client<StreamWrapper>>,
+}
+
+impl GroupMember {
+    pub fn new(address: String) -> Self {
+        Self {
+            address,
+            state: ConnectionState::NotConnected,
+            client: None,
+        }
+    }
+    }
+}
+
+/// Split a server as typed by the user into its optional password & its address. Like
+/// MPD_HOST, servers may be typed as password@host:port or password@/path/to/socket.
+pub fn split_credentials(server: &str) -> (Option<&str>, &str) {
+    match server.rsplit_once('@') {
+        Some((password, address)) if !password.is_empty() && !address.is_empty() => {
+            (Some(password), address)
+        }
+        _ => (None, server),
+    }
+}
+
+/// Blocking. Connect & log into a group member.
+pub fn connect_member(address: &str, password: Option<&str>) -> Result<Client<StreamWrapper>, ConnectionState> {
+    let stream = if address.starts_with('/') || address.starts_with('~') {
+        let path = address
+            .try_resolve()
+            .map_or(address.to_owned(), |resolved| resolved.to_string_lossy().into_owned());
+        StreamWrapper::new_unix(UnixStream::connect(path).map_err(|_| ConnectionState::SocketNotFound)?)
+    } else {
+        let addr = if address.contains(':') {
+            address.to_owned()
+        } else {
+            format!("{address}:6600")
+        };
+        StreamWrapper::new_tcp(TcpStream::connect(addr).map_err(|_| ConnectionState::ConnectionRefused)?)
+    };
+    let mut client = Client::new(stream).map_err(|_| ConnectionState::ConnectionRefused)?;
+    if let Some(password) = password {
+        if client.login(password).is_err() {
+            let _ = client.close();
+            return Err(ConnectionState::WrongPassword);
+        }
+    }
+    Ok(client)
+}
+
+/// Blocking. Make a member's queue identical to the given one, then play from the same
+/// position if requested, pausing right away if `paused` is set.
+pub fn copy_queue(
+    client: &mut Client<StreamWrapper>,
+    uris: &[String],
+    resume: Option<(u32, f64)>,
+    paused: bool,
+) -> Result<(), MpdError> {
+    client.clear()?;
+    for batch in uris.chunks(128) {
+        client.push_multiple(batch)?;
+    }
+    if let Some((pos, elapsed)) = resume {
+        client.switch(pos)?;
+        client.rewind(elapsed)?;
+        if paused {
+            client.pause(true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Blocking. Make every member's queue identical to the leader's (read through the given
+/// client) & play from the same position. Returns whether any member was lost along the way.
+pub fn resync_group(leader: &mut Client<StreamWrapper>, group: &Mutex<Vec<GroupMember>>) -> Result<bool, MpdError> {
+    let uris: Vec<String> = leader
+        .queue()?
+        .into_iter()
+        .map(|song| song.file)
+        .collect();
+    let status = leader.status()?;
+    let resume = if status.state == State::Play {
+        status.song.map(|place| (place.pos, status.elapsed.map_or(0.0, |e| e.as_secs_f64())))
+    } else {
+        None
+    };
+    Ok(run_on_members(group, |client| copy_queue(client, &uris, resume, false)))
+}
+
+/// Blocking. Set every member's volume, either to the given one or, if a delta is given,
+/// shifted by that much from its current one. Members without a mixer are left alone in the
+/// latter case. Returns whether any member was lost along the way.
+pub fn set_group_volume(group: &Mutex<Vec<GroupMember>>, vol: i8, delta: Option<i16>) -> bool {
+    run_on_members(group, |client| {
+        let new_vol = if let Some(delta) = delta {
+            let member_vol = client.status()?.volume;
+            if member_vol < 0 {
+                return Ok(());
+            }
+            (member_vol as i16 + delta).clamp(0, 100) as i8
+        } else {
+            vol
+        };
+        client.volume(new_vol)
+    })
+}
+
+/// Blocking. Run a command on every connected member. Each member's client is taken out of
+/// the group while in use, so that the group lock is never held across a round trip and
+/// the main thread can always read the group right away. Members being worked on by
+/// another thread meanwhile are skipped. Lost connections are dropped here and reflected
+/// in the members' states. Returns whether any member was lost along the way.
+pub fn run_on_members<T>(
+    group: &Mutex<Vec<GroupMember>>,
+    f: impl Fn(&mut Client<StreamWrapper>) -> Result<T, MpdError>,
+) -> bool {
+    let addresses: Vec<String> = group
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|member| member.address.clone())
+        .collect();
+    let mut lost = false;
+    for address in addresses.into_iter() {
+        let taken = group
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|member| member.address == address)
+            .and_then(|member| member.client.take());
+        let Some(mut client) = taken else {
+            continue;
+        };
+        let res = f(&mut client);
+        let mut group = group.lock().unwrap();
+        // The member might have been removed while we were busy with it.
+        let Some(member) = group.iter_mut().find(|member| member.address == address) else {
+            let _ = client.close();
+            continue;
+        };
+        match res {
+            Err(MpdError::Io(e)) => {
+                println!("[Group] Lost connection to {}: {e:?}", &member.address);
+                member.state = ConnectionState::NotConnected;
+                lost = true;
+            }
+            res => {
+                if let Err(e) = res {
+                    println!("[Group] {} returned an error: {e:?}", &member.address);
+                }
+                member.client = Some(client);
+            }
+        }
+    }
+    lost
+}
+
+/// Queue a command to be run on every connected member, after any previously queued ones.
+/// Lost members are reported back to the main thread through the given sender.
+pub fn fan_out<T>(
+    group: Arc<Mutex<Vec<GroupMember>>>,
+    sender: Sender<AsyncClientMessage>,
+    f: impl Fn(&mut Client<StreamWrapper>) -> Result<T, MpdError> + Send + 'static,
+) {
+    GROUP_THREADPOOL
+        .push(move || {
+            if run_on_members(&group, f) {
+                let _ = sender.send_blocking(AsyncClientMessage::GroupChanged);
+            }
+        })
+        .expect("Failed to queue group command");
+}
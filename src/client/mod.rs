@@ -1,14 +1,21 @@
 mod stream;
 mod background;
+mod group;
 pub mod state;
 pub mod wrapper;
 pub mod password;
 
 use mpd::{lsinfo::LsInfoEntry, Query, Subsystem, error::Error as MpdError};
 pub use state::{ClientState, ConnectionState, ClientError};
-pub use wrapper::MpdWrapper;
+pub use wrapper::{connect_main_server, MpdWrapper};
+pub use group::split_credentials;
+pub use stream::StreamWrapper;
+pub use background::{
+    download_embedded_cover_inner, download_folder_cover_inner, list_album_songs,
+    list_all_albums, list_all_artists,
+};
 
-use crate::common::{AlbumInfo, ArtistInfo, DynamicPlaylist, SongInfo, Stickers};
+use crate::common::{AlbumInfo, ArtistInfo, AutoDjSource, DynamicPlaylist, SongInfo, Stickers};
 
 /// Messages to be sent from child thread or asynchronous methods.
 pub enum AsyncClientMessage {
@@ -127,9 +134,52 @@ pub enum AsyncClientMessage {
         Vec<SongInfo>,
     ),
 
+    /// Returns songs, albums & artists often played in the same listening sessions as an album.
+    AlbumCoListenedDownloaded(
+        /// Album tag.
+        String,
+        /// Recommended songs, most relevant first.
+        Vec<SongInfo>,
+        /// Recommended albums, most relevant first.
+        Vec<AlbumInfo>,
+        /// Recommended artists, most relevant first.
+        Vec<ArtistInfo>,
+    ),
+
+    /// Returns songs, albums & artists often played in the same listening sessions as an artist.
+    ArtistCoListenedDownloaded(
+        /// Artist name (the one originally used to query).
+        String,
+        /// Recommended songs, most relevant first.
+        Vec<SongInfo>,
+        /// Recommended albums, most relevant first.
+        Vec<AlbumInfo>,
+        /// Recommended artists, most relevant first.
+        Vec<ArtistInfo>,
+    ),
+
+    /// Returns queue IDs of songs matching a queue search.
+    QueueSearchResults(
+        /// The search term, for discarding outdated results.
+        String,
+        /// Queue IDs of matching songs, in queue order.
+        Vec<u32>,
+    ),
+
+    /// Returns library songs matching a search, such as a radio stream title.
+    LibrarySearchResults(
+        /// The search term, for discarding outdated results.
+        String,
+        /// Matching songs.
+        Vec<SongInfo>,
+    ),
+
     /// Notifies that the MPD database has finished updating.
     DBUpdated,
 
+    /// Notifies that some group members were lost while the child client was handling them.
+    GroupChanged,
+
     /// Reports an error that occurred in a background task.
     BackgroundError(
         /// The underlying error from rust-mpd.
@@ -147,6 +197,20 @@ pub enum BackgroundTask {
     /// Triggers an MPD database update.
     Update,
 
+    /// Searches the queue server-side (case-insensitive substring match on any tag).
+    /// Used for long queues where filtering locally would be slow.
+    SearchQueue(
+        /// The search term.
+        String,
+    ),
+
+    /// Searches the library for songs matching a free-form title. Titles in the usual
+    /// "Artist - Title" form of stream metadata are matched on both tags.
+    SearchLibrary(
+        /// The search term.
+        String,
+    ),
+
     /// Queues a list of song URIs for playback.
     QueueUris(
         /// A list of URIs to add to the queue.
@@ -159,6 +223,37 @@ pub enum BackgroundTask {
         Option<u32>,
     ),
 
+    /// Queues a previous queue again (for undoing a queue clear or replacement), then
+    /// optionally resumes playback where it was left.
+    RestoreQueue(
+        /// URIs of the previous queue, in order.
+        Vec<String>,
+        /// Optional queue pos to resume playing from and the elapsed seconds to seek to.
+        Option<(u32, f64)>,
+        /// Whether to pause again right after resuming.
+        bool,
+    ),
+
+    /// Copies the queue & play position over to every group member.
+    ResyncGroup,
+
+    /// Sets the volume of every group member.
+    SetGroupVolume(
+        /// The leader's new volume.
+        i8,
+        /// If set, shift each member's volume by this much instead, keeping them relative
+        /// to the leader's.
+        Option<i16>,
+    ),
+
+    /// Moves songs (by queue ID) to new queue positions, in the given order. All moves are
+    /// sent as one command list, so that MPD applies them in one go (one queue version bump)
+    /// and stops at the first failing one.
+    MoveIds(
+        /// (queue ID, new queue pos) pairs.
+        Vec<(u32, u32)>,
+    ),
+
     /// Finds songs matching a specific query and adds them to the queue.
     QueueQuery(
         /// The search query to execute.
@@ -244,6 +339,28 @@ pub enum BackgroundTask {
         u32,
     ),
 
+    /// Mines local listening history for songs, albums & artists often played together
+    /// with an album.
+    FetchAlbumCoListened(
+        /// Album tag.
+        String,
+        /// Maximum gap between two plays of the same session, in minutes.
+        u32,
+        /// Maximum number of songs, albums & artists each to return.
+        u32,
+    ),
+
+    /// Mines local listening history for songs, albums & artists often played together
+    /// with an artist.
+    FetchArtistCoListened(
+        /// Artist name.
+        String,
+        /// Maximum gap between two plays of the same session, in minutes.
+        u32,
+        /// Maximum number of songs, albums & artists each to return.
+        u32,
+    ),
+
     /// Fetches songs for a dynamic playlist based on its rules,
     /// optionally updating the cache.
     FetchDynamicPlaylistSongs(
@@ -259,6 +376,20 @@ pub enum BackgroundTask {
         String,
     ),
 
+    /// Samples songs from an Auto-DJ source, then appends them to the queue. Songs are
+    /// weighted by their ratings (and by artist similarity for artist radios). Recently
+    /// played songs are avoided unless the source would otherwise run dry.
+    TopUpQueue(
+        /// Where to get the songs from.
+        AutoDjSource,
+        /// Number of songs to queue.
+        u32,
+        /// URIs to never pick (usually those already in the queue).
+        Vec<String>,
+        /// Optional queue pos to start playing from.
+        Option<u32>,
+    ),
+
     /// Queues songs from a cached DP.
     QueueDynamicPlaylist(
         /// DP name.
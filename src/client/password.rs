@@ -6,6 +6,8 @@ use crate::config::APPLICATION_ID;
 pub fn get_mpd_password_schema() -> Schema {
     let mut attributes = HashMap::new();
     attributes.insert("type", SchemaAttributeType::String);
+    // Only set for other servers (handoff targets & group members), as host:port or socket path
+    attributes.insert("server", SchemaAttributeType::String);
 
     Schema::new(APPLICATION_ID, SchemaFlags::NONE, attributes)
 }
@@ -24,6 +26,21 @@ pub async fn get_mpd_password() -> Result<Option<String>, String> {
         .map_err(|ge| format!("{ge:?}"))
 }
 
+/// Blocking version of get_mpd_password() for threads without a main loop.
+pub fn get_mpd_password_blocking() -> Result<Option<String>, String> {
+    let schema = get_mpd_password_schema();
+    let mut attributes = HashMap::new();
+    attributes.insert("type", "mpd");
+
+    libsecret::password_lookup_sync(
+        Some(&schema),
+        attributes,
+        libsecret::gio::Cancellable::NONE
+    )
+        .map(|op| op.map(|gs| gs.as_str().to_owned()))
+        .map_err(|ge| format!("{ge:?}"))
+}
+
 pub async fn set_mpd_password(maybe_password: Option<&str>) -> Result<(), String> {
     let schema = get_mpd_password_schema();
     let mut attributes = HashMap::new();
@@ -48,3 +65,46 @@ pub async fn set_mpd_password(maybe_password: Option<&str>) -> Result<(), String
             .map_err(|ge| format!("{ge:?}"))
     }
 }
+
+/// Password saved for another server than the one in the connection settings, such as one
+/// we've handed playback off from.
+pub async fn get_server_password(address: &str) -> Result<Option<String>, String> {
+    let schema = get_mpd_password_schema();
+    let mut attributes = HashMap::new();
+    attributes.insert("type", "mpd-server");
+    attributes.insert("server", address);
+
+    libsecret::password_lookup_future(
+        Some(&schema),
+        attributes
+    )
+        .await
+        .map(|op| op.map(|gs| gs.as_str().to_owned()))
+        .map_err(|ge| format!("{ge:?}"))
+}
+
+pub async fn set_server_password(address: &str, maybe_password: Option<&str>) -> Result<(), String> {
+    let schema = get_mpd_password_schema();
+    let mut attributes = HashMap::new();
+    attributes.insert("type", "mpd-server");
+    attributes.insert("server", address);
+
+    if let Some(password) = maybe_password {
+        libsecret::password_store_future(
+            Some(&schema),
+            attributes,
+            None,
+            &format!("Euphonica MPD password for {address}"),
+            password
+        )
+            .await
+            .map_err(|ge| format!("{ge:?}"))
+    } else {
+        libsecret::password_clear_future(
+            Some(&schema),
+            attributes
+        )
+            .await
+            .map_err(|ge| format!("{ge:?}"))
+    }
+}
@@ -23,6 +23,22 @@ pub enum ConnectionState {
     Connected,
 }
 
+impl ConnectionState {
+    // TODO: translatable
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::NotConnected => "Not connected",
+            Self::ConnectionRefused => "Connection refused",
+            Self::SocketNotFound => "Socket not found",
+            Self::Connecting => "Connecting...",
+            Self::Unauthenticated | Self::PasswordNotAvailable => "Authentication failed",
+            Self::CredentialStoreError => "Credential store error",
+            Self::WrongPassword => "Incorrect password",
+            Self::Connected => "Connected",
+        }
+    }
+}
+
 #[derive(Default, Debug, Copy, Clone, PartialEq, Eq, glib::Enum, PartialOrd, Ord)]
 #[enum_type(name = "EuphonicaStickersSupportLevel")]
 pub enum StickersSupportLevel {
@@ -36,7 +52,8 @@ pub enum StickersSupportLevel {
 #[derive(Debug, Copy, Clone, PartialEq, Eq, glib::Enum)]
 #[enum_type(name = "EuphonicaClientError")]
 pub enum ClientError {
-    Queuing
+    Queuing,
+    HandOff
 }
 
 mod imp {
@@ -180,6 +197,24 @@ mod imp {
                             BoxedAnyObject::static_type(), // Vec<Song>
                         ])
                         .build(),
+                    // Songs, albums & artists often played together with an album or
+                    // artist, mined from local listening history.
+                    Signal::builder("album-co-listened-downloaded")
+                        .param_types([
+                            String::static_type(),
+                            BoxedAnyObject::static_type(), // Vec<Song>
+                            BoxedAnyObject::static_type(), // Vec<Album>
+                            BoxedAnyObject::static_type(), // Vec<Artist>
+                        ])
+                        .build(),
+                    Signal::builder("artist-co-listened-downloaded")
+                        .param_types([
+                            String::static_type(),
+                            BoxedAnyObject::static_type(), // Vec<Song>
+                            BoxedAnyObject::static_type(), // Vec<Album>
+                            BoxedAnyObject::static_type(), // Vec<Artist>
+                        ])
+                        .build(),
                     Signal::builder("recent-songs-downloaded")
                         .param_types([
                             BoxedAnyObject::static_type(), // Vec<Song>
@@ -194,7 +229,21 @@ mod imp {
                         .param_types([
                             BoxedAnyObject::static_type(), // Vec<Song>
                         ])
-                        .build()
+                        .build(),
+                    Signal::builder("queue-search-results")
+                        .param_types([
+                            String::static_type(),
+                            BoxedAnyObject::static_type(), // Vec<u32> of queue IDs
+                        ])
+                        .build(),
+                    Signal::builder("library-search-results")
+                        .param_types([
+                            String::static_type(),
+                            BoxedAnyObject::static_type(), // Vec<Song>
+                        ])
+                        .build(),
+                    // Playback group members or their connection states changed
+                    Signal::builder("group-changed").build()
                 ]
             })
         }
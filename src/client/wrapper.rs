@@ -17,7 +17,7 @@ use zbus::{Connection as ZConnection, Proxy as ZProxy};
 use std::net::TcpStream;
 use std::os::unix::net::UnixStream;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::{
     cell::{Cell, RefCell},
     rc::Rc
@@ -26,7 +26,7 @@ use uuid::Uuid;
 
 use crate::common::Stickers;
 use crate::{
-    common::{Album, AlbumInfo, Artist, INode, Song, SongInfo},
+    common::{Album, AlbumInfo, Artist, ArtistInfo, INode, Song, SongInfo},
     meta_providers::ProviderMessage,
     player::PlaybackFlow,
     utils,
@@ -35,8 +35,9 @@ use crate::{
 use super::{AsyncClientMessage, BackgroundTask, StickerSetMode};
 use super::state::{ClientState, ConnectionState, StickersSupportLevel};
 use super::stream::StreamWrapper;
-use super::password::get_mpd_password;
+use super::password::{get_mpd_password, get_server_password, set_mpd_password, set_server_password};
 use super::background;
+use super::group::{self, connect_member, copy_queue, GroupMember};
 use super::ClientError;
 
 // Thin wrapper around the blocking mpd::Client. It contains two separate client
@@ -119,7 +120,31 @@ pub struct MpdWrapper {
     // expected_queue version, we are out of sync and must perform a refresh
     // using the old logic. Else do nothing.
     queue_version: Cell<u32>,
-    expected_queue_version: Cell<u32>
+    expected_queue_version: Cell<u32>,
+
+    // Other servers to fan playback commands out to (multi-room playback). The main
+    // server acts as the group leader. Shared with the child thread, which handles the
+    // heavier group operations (queue copies & relative volume changes).
+    group: Arc<Mutex<Vec<GroupMember>>>,
+    // Last known volume of the leader, for keeping members' volumes relative to it.
+    leader_volume: Cell<i8>,
+    // Set when the leader's queue is being replaced, so that members get a copy of the
+    // new queue once queuing has finished.
+    group_resync_pending: Cell<bool>
+}
+
+/// Blocking. Open an extra connection to the main server using the current connection
+/// settings, for code running outside of the background client such as metadata providers.
+pub fn connect_main_server(password: Option<&str>) -> Result<Client<StreamWrapper>, ConnectionState> {
+    let conn = utils::settings_manager().child("client");
+    let address = if conn.boolean("mpd-use-unix-socket") {
+        let path = conn.string("mpd-unix-socket");
+        path.try_resolve()
+            .map_or(path.to_string(), |resolved| resolved.to_string_lossy().into_owned())
+    } else {
+        format!("{}:{}", conn.string("mpd-host"), conn.uint("mpd-port"))
+    };
+    connect_member(&address, password)
 }
 
 impl MpdWrapper {
@@ -140,7 +165,10 @@ impl MpdWrapper {
             pending_idle: Arc::new(AtomicBool::new(false)),
             meta_sender,
             queue_version: Cell::new(0),
-            expected_queue_version: Cell::new(0)
+            expected_queue_version: Cell::new(0),
+            group: Arc::new(Mutex::new(Vec::new())),
+            leader_volume: Cell::new(-1),
+            group_resync_pending: Cell::new(false)
         });
 
         // For future noob self: these are shallow
@@ -169,6 +197,7 @@ impl MpdWrapper {
         self.bg_sender.replace(Some(bg_sender));
         self.bg_sender_high.replace(Some(bg_sender_high));
         let bg_channel = self.bg_channel.clone();
+        let group = self.group.clone();
 
         let bg_handle = gio::spawn_blocking(move || {
             // Create a new connection for the child thread
@@ -297,9 +326,44 @@ impl MpdWrapper {
                         BackgroundTask::FetchRecentSongs(count) => {
                             background::fetch_last_n_songs(&mut client, &sender_to_fg, count);
                         }
+                        BackgroundTask::FetchAlbumCoListened(tag, gap, count) => {
+                            background::fetch_album_co_listened(&mut client, &sender_to_fg, tag, gap, count);
+                        }
+                        BackgroundTask::FetchArtistCoListened(name, gap, count) => {
+                            background::fetch_artist_co_listened(&mut client, &sender_to_fg, name, gap, count);
+                        }
+                        BackgroundTask::SearchQueue(term) => {
+                            background::search_queue(&mut client, &sender_to_fg, term);
+                        }
+                        BackgroundTask::SearchLibrary(term) => {
+                            background::search_library(&mut client, &sender_to_fg, term);
+                        }
                         BackgroundTask::QueueUris(uris, recursive, play_from, insert_pos) => {
                             background::add_multi(&mut client, &sender_to_fg, &uris, recursive, play_from, insert_pos);
                         }
+                        BackgroundTask::RestoreQueue(uris, resume, paused) => {
+                            background::restore_queue(&mut client, &sender_to_fg, &uris, resume, paused);
+                        }
+                        BackgroundTask::ResyncGroup => {
+                            match group::resync_group(&mut client, &group) {
+                                Ok(lost) => {
+                                    if lost {
+                                        let _ = sender_to_fg.send_blocking(AsyncClientMessage::GroupChanged);
+                                    }
+                                }
+                                Err(mpd_error) => {
+                                    let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+                                }
+                            }
+                        }
+                        BackgroundTask::SetGroupVolume(vol, delta) => {
+                            if group::set_group_volume(&group, vol, delta) {
+                                let _ = sender_to_fg.send_blocking(AsyncClientMessage::GroupChanged);
+                            }
+                        }
+                        BackgroundTask::MoveIds(moves) => {
+                            background::move_ids(&mut client, &sender_to_fg, &moves);
+                        }
                         BackgroundTask::QueueQuery(query, play_from) => {
                             background::find_add(&mut client, &sender_to_fg, query, play_from);
                         }
@@ -315,6 +379,9 @@ impl MpdWrapper {
                         BackgroundTask::QueueDynamicPlaylist(name, play) => {
                             background::queue_cached_dynamic_playlist(&mut client, &sender_to_fg, &name, play);
                         }
+                        BackgroundTask::TopUpQueue(source, count, exclude, play_from) => {
+                            background::top_up_queue(&mut client, &sender_to_fg, &source, count, &exclude, play_from);
+                        }
                     }
                 } else {
                     // If not, go into idle mode
@@ -394,6 +461,7 @@ impl MpdWrapper {
                     else {
                         println!("[KeepAlive] There is no client currently running. Won't ping.");
                     }
+                    this.fan_out(|client| client.ping());
                     glib::timeout_future_seconds(ping_interval).await;
                 }
             }));
@@ -483,8 +551,15 @@ impl MpdWrapper {
             AsyncClientMessage::Status(n_tasks) => self.state.set_n_background_tasks(n_tasks as u64),
             AsyncClientMessage::RecentSongInfoDownloaded(songs) => self
                 .on_songs_downloaded("recent-songs-downloaded", None, songs),
+            AsyncClientMessage::AlbumCoListenedDownloaded(tag, songs, albums, artists) => self
+                .on_co_listened_downloaded("album-co-listened-downloaded", tag, songs, albums, artists),
+            AsyncClientMessage::ArtistCoListenedDownloaded(name, songs, albums, artists) => self
+                .on_co_listened_downloaded("artist-co-listened-downloaded", name, songs, albums, artists),
             AsyncClientMessage::Queuing(block) => {
                 self.state.set_queuing(block);
+                if !block && self.group_resync_pending.take() {
+                    self.resync_group();
+                }
             }
             AsyncClientMessage::BackgroundError(error, or) => {
                 self.handle_common_mpd_error(&error, or);
@@ -492,6 +567,18 @@ impl MpdWrapper {
             AsyncClientMessage::DynamicPlaylistSongInfoDownloaded(name, songs) => {
                 self.on_songs_downloaded("dynamic-playlist-songs-downloaded", Some(name), songs)
             }
+            AsyncClientMessage::QueueSearchResults(term, ids) => {
+                self.state.emit_by_name::<()>(
+                    "queue-search-results",
+                    &[&term, &BoxedAnyObject::new(ids)]
+                );
+            }
+            AsyncClientMessage::LibrarySearchResults(term, songs) => {
+                self.on_songs_downloaded("library-search-results", Some(term), songs)
+            }
+            AsyncClientMessage::GroupChanged => {
+                self.state.emit_by_name::<()>("group-changed", &[]);
+            }
         }
         glib::ControlFlow::Continue
     }
@@ -675,6 +762,171 @@ impl MpdWrapper {
         }
     }
 
+    /// Address & password of another configured server. Passwords are only ever kept in
+    /// the keyring, never in the server lists themselves.
+    async fn server_credentials(server: &str) -> (String, Option<String>) {
+        let password = get_server_password(server).await.ok().flatten();
+        (server.to_owned(), password)
+    }
+
+    /// Hand playback off to another server: recreate the given queue there and resume
+    /// playback (staying paused if `paused` is set), then switch the connection settings over to it (putting the current server
+    /// in its place in the handoff list so that we can come back later) and reconnect.
+    /// Nothing changes on our side unless the target could be reached first.
+    pub async fn hand_off_async(
+        &self,
+        target: String,
+        uris: Vec<String>,
+        resume: Option<(u32, f64)>,
+        paused: bool,
+        stop_source: bool
+    ) {
+        let (address, password) = Self::server_credentials(&target).await;
+        let (target_address, target_password) = (address.clone(), password.clone());
+        let res: Result<Result<(), ConnectionState>, _> = gio::spawn_blocking(move || {
+            let mut client = connect_member(&target_address, target_password.as_deref())?;
+            let res = copy_queue(&mut client, &uris, resume, paused);
+            let _ = client.close();
+            res.map_err(|e| {
+                println!("[Handoff] Could not copy the queue over to {target_address}: {e:?}");
+                ConnectionState::NotConnected
+            })
+        }).await;
+        if !matches!(res, Ok(Ok(()))) {
+            self.state.emit_error(ClientError::HandOff);
+            return;
+        }
+        if stop_source {
+            self.stop();
+        }
+
+        let conn = utils::settings_manager().child("client");
+        let use_unix_socket = conn.boolean("mpd-use-unix-socket");
+        let socket = conn.string("mpd-unix-socket");
+        let host = conn.string("mpd-host");
+        let port = conn.uint("mpd-port");
+        let current = if use_unix_socket {
+            socket.to_string()
+        } else {
+            format!("{host}:{port}")
+        };
+        let current_password = get_mpd_password().await.ok().flatten();
+        if address.starts_with('/') || address.starts_with('~') {
+            let _ = conn.set_string("mpd-unix-socket", &address);
+            let _ = conn.set_boolean("mpd-use-unix-socket", true);
+        } else {
+            let (target_host, target_port) = match address.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse::<u32>().unwrap_or(6600)),
+                None => (address.as_str(), 6600),
+            };
+            let _ = conn.set_string("mpd-host", target_host);
+            let _ = conn.set_uint("mpd-port", target_port);
+            let _ = conn.set_boolean("mpd-use-unix-socket", false);
+        }
+        let _ = set_mpd_password(password.as_deref()).await;
+
+        self.connect_async().await;
+        if self.state.get_connection_state() != ConnectionState::Connected {
+            // Go back to the previous server rather than leaving the user stranded.
+            let _ = conn.set_string("mpd-unix-socket", &socket);
+            let _ = conn.set_string("mpd-host", &host);
+            let _ = conn.set_uint("mpd-port", port);
+            let _ = conn.set_boolean("mpd-use-unix-socket", use_unix_socket);
+            let _ = set_mpd_password(current_password.as_deref()).await;
+            self.connect_async().await;
+            self.state.emit_error(ClientError::HandOff);
+            return;
+        }
+
+        // Remember how to log back into the previous server without writing its password
+        // into the (plain text) handoff list.
+        let _ = set_server_password(&current, current_password.as_deref()).await;
+        let servers: Vec<String> = conn
+            .value("handoff-servers")
+            .array_iter_str()
+            .unwrap()
+            .map(|server| if server == target { current.clone() } else { server.to_owned() })
+            .collect();
+        let _ = conn.set_value("handoff-servers", &servers.to_variant());
+    }
+
+    /// Run a command on every connected group member. The round trips are left to the
+    /// group thread, as members might be slow to respond or have silently gone away.
+    fn fan_out<T>(&self, f: impl Fn(&mut Client<StreamWrapper>) -> Result<T, MpdError> + Send + 'static) {
+        if self.group.lock().unwrap().is_empty() {
+            return;
+        }
+        group::fan_out(self.group.clone(), self.main_sender.clone(), f);
+    }
+
+    /// Addresses & connection states of the current group members (not including the leader).
+    pub fn group_members(&self) -> Vec<(String, ConnectionState)> {
+        self.group
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|member| (member.address.clone(), member.state))
+            .collect()
+    }
+
+    /// Add another server to the playback group, then give it a copy of our queue.
+    pub async fn add_group_member(&self, address: String) {
+        if self.group.lock().unwrap().iter().any(|member| member.address == address) {
+            return;
+        }
+        let mut member = GroupMember::new(address.clone());
+        member.state = ConnectionState::Connecting;
+        self.group.lock().unwrap().push(member);
+        self.state.emit_by_name::<()>("group-changed", &[]);
+
+        let (member_address, password) = Self::server_credentials(&address).await;
+        let res = gio::spawn_blocking(move || connect_member(&member_address, password.as_deref())).await;
+        // The member might have been removed while we were connecting.
+        if let Some(member) = self
+            .group
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|member| member.address == address)
+        {
+            match res {
+                Ok(Ok(client)) => {
+                    member.client = Some(client);
+                    member.state = ConnectionState::Connected;
+                }
+                Ok(Err(state)) => {
+                    member.state = state;
+                }
+                Err(_) => {
+                    member.state = ConnectionState::NotConnected;
+                }
+            }
+        }
+        self.state.emit_by_name::<()>("group-changed", &[]);
+        self.resync_group();
+    }
+
+    pub fn remove_group_member(&self, address: &str) {
+        let mut group = self.group.lock().unwrap();
+        if let Some(idx) = group.iter().position(|member| member.address == address) {
+            let mut member = group.remove(idx);
+            if let Some(mut client) = member.client.take() {
+                let _ = client.close();
+            }
+        }
+        drop(group);
+        self.state.emit_by_name::<()>("group-changed", &[]);
+    }
+
+    /// Make every member's queue identical to the leader's & play from the same position.
+    /// Done by the child client, as this means copying the whole queue over to each member.
+    pub fn resync_group(&self) {
+        if self.group.lock().unwrap().is_empty() || self.main_client.borrow().is_none() {
+            return;
+        }
+        self.queue_background(BackgroundTask::ResyncGroup, true);
+    }
+
     fn force_idle(&self) {
         if !self.pending_idle.load(Ordering::Relaxed) {
             self.pending_idle.store(true, Ordering::Relaxed);
@@ -751,8 +1003,21 @@ impl MpdWrapper {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             // Don't attempt reconnection here since this thing can rapid-fire.
             let _ = client.volume(vol);
-            self.force_idle();
+        } else {
+            return;
+        }
+        self.force_idle();
+        let old_vol = self.leader_volume.replace(vol);
+        if self.group.lock().unwrap().is_empty() {
+            return;
         }
+        // Members' volumes have to be read before they can be shifted, so leave the
+        // round trips to the child client. A leader without a mixer reports -1, in which
+        // case there's nothing to be relative to.
+        let delta = (old_vol >= 0
+            && utils::settings_manager().child("client").boolean("group-volume-relative"))
+            .then(|| vol as i16 - old_vol as i16);
+        self.queue_background(BackgroundTask::SetGroupVolume(vol, delta), true);
     }
 
     pub fn get_outputs(&self) -> Option<Vec<Output>> {
@@ -1013,6 +1278,7 @@ impl MpdWrapper {
         }
         match res {
             Some(Ok(status)) => {
+                self.leader_volume.set(status.volume);
                 // Check whether we need to sync queue with server side (inefficient)
                 if sync_queue {
                     let old_version = self.queue_version.replace(status.queue_version);
@@ -1140,24 +1406,28 @@ impl MpdWrapper {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             self.handle_set_error(client.pause(is_pause));
         }
+        self.fan_out(move |client| client.pause(is_pause));
     }
 
     pub fn stop(&self) {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             self.handle_set_error(client.stop());
         }
+        self.fan_out(|client| client.stop());
     }
 
     pub fn prev(&self) {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             self.handle_set_error(client.prev());
         }
+        self.fan_out(|client| client.prev());
     }
 
     pub fn next(&self) {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             self.handle_set_error(client.next());
         }
+        self.fan_out(|client| client.next());
     }
 
     pub fn play_at(&self, id_or_pos: u32, is_id: bool) {
@@ -1168,6 +1438,18 @@ impl MpdWrapper {
                 client.switch(id_or_pos).map(|_| ())
             };
             self.handle_set_error(res);
+            // Queue IDs are per-server, so members can only follow by position.
+            let pos = if is_id {
+                client
+                    .songs(Id(id_or_pos))
+                    .ok()
+                    .and_then(|songs| songs.first().and_then(|song| song.place.map(|place| place.pos)))
+            } else {
+                Some(id_or_pos)
+            };
+            if let Some(pos) = pos {
+                self.fan_out(move |member| member.switch(pos).map(|_| ()));
+            }
         }
     }
 
@@ -1183,6 +1465,51 @@ impl MpdWrapper {
         }
     }
 
+    /// Move a contiguous range of queued songs [start, end) such that the first one ends up at
+    /// the given position.
+    pub fn move_range(&self, start: u32, end: u32, to: u32) {
+        if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            self.handle_set_error(client.shift(start..end, to as usize));
+        }
+    }
+
+    /// Move songs (by queue ID) to new queue positions, in the given order. Done by the
+    /// background client as a single command list, as there can be one move per queued song.
+    pub fn move_ids(&self, moves: &[(u32, u32)]) {
+        if moves.is_empty() {
+            return;
+        }
+        self.queue_background(BackgroundTask::MoveIds(moves.to_vec()), true);
+    }
+
+    /// Set the priority of songs (by queue ID). MPD only follows priorities in random mode,
+    /// where songs with higher priorities are played first.
+    pub fn set_priority(&self, ids: &[u32], prio: u8) {
+        background::forget_queued_songs(ids);
+        if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            let mut res = Ok(());
+            for id in ids.iter() {
+                res = client.priority(Id(*id), prio);
+                if res.is_err() {
+                    break;
+                }
+            }
+            self.handle_set_error(res);
+        }
+    }
+
+    /// Only play part of a queued song (by queue ID). Range is given as start and optional
+    /// end in seconds. Passing None plays the whole song again.
+    pub fn set_range(&self, id: u32, range: Option<(f64, Option<f64>)>) {
+        background::forget_queued_songs(&[id]);
+        if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            self.handle_set_error(client.rangeid(
+                Id(id),
+                background::to_mpd_range(range.unwrap_or((0.0, None)))
+            ));
+        }
+    }
+
     pub fn delete_at(&self, id_or_pos: u32, is_id: bool) {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             let res = if is_id {
@@ -1196,10 +1523,29 @@ impl MpdWrapper {
         }
     }
 
+    /// Insert songs at the given queue positions, in the given order.
+    pub fn insert_at(&self, songs: &[(u32, String)]) {
+        if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            let mut res = Ok(());
+            for (pos, uri) in songs.iter() {
+                res = client.insert(uri, *pos as usize).map(|_| ());
+                if res.is_err() {
+                    break;
+                }
+            }
+            self.handle_set_error(res);
+        }
+    }
+
     pub fn clear_queue(&self) {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             self.handle_set_error(client.clear());
         }
+        // Clearing is how the queue gets replaced. Members follow once the new songs are in.
+        if !self.group.lock().unwrap().is_empty() {
+            self.fan_out(|client| client.clear());
+            self.group_resync_pending.set(true);
+        }
     }
 
     pub fn register_local_queue_changes(&self, n_changes: u32) {
@@ -1210,6 +1556,7 @@ impl MpdWrapper {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             self.handle_set_error(client.rewind(position));
         }
+        self.fan_out(move |client| client.rewind(position));
     }
 
     fn on_songs_downloaded(&self, signal_name: &str, tag: Option<String>, songs: Vec<SongInfo>) {
@@ -1232,6 +1579,25 @@ impl MpdWrapper {
         }
     }
 
+    fn on_co_listened_downloaded(
+        &self,
+        signal_name: &str,
+        tag: String,
+        songs: Vec<SongInfo>,
+        albums: Vec<AlbumInfo>,
+        artists: Vec<ArtistInfo>,
+    ) {
+        self.state.emit_by_name::<()>(
+            signal_name,
+            &[
+                &tag,
+                &BoxedAnyObject::new(songs.into_iter().map(Song::from).collect::<Vec<Song>>()),
+                &BoxedAnyObject::new(albums.into_iter().map(Album::from).collect::<Vec<Album>>()),
+                &BoxedAnyObject::new(artists.into_iter().map(Artist::from).collect::<Vec<Artist>>()),
+            ]
+        );
+    }
+
     fn on_album_downloaded(&self, signal_name: &str, tag: Option<&str>, info: AlbumInfo) {
         let album = Album::from(info);
         {
@@ -37,6 +37,23 @@ impl Default for ArtistInfo {
     }
 }
 
+/// Seed for an artist radio session: the artist the user started the radio from, plus
+/// similar artists (most similar first) as reported by metadata providers. Similar artists
+/// not present in the local library are filtered out when sampling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArtistRadio {
+    pub seed: String,
+    pub similar: Vec<String>,
+}
+
+impl ArtistRadio {
+    /// Relative weight of an artist at the given rank in the similar list. The seed artist
+    /// itself always has a weight of 1.0.
+    pub fn similarity_weight(&self, rank: usize) -> f64 {
+        1.0 - 0.9 * (rank as f64 + 1.0) / (self.similar.len() as f64 + 1.0)
+    }
+}
+
 /// Utility function to create a list of ArtistInfo objects from a MusicBrainz Artist tag.
 /// Can be used with AlbumArtist tag too, but NOT with with ArtistSort or AlbumArtistSort tags.
 /// Internally, we rely on two passes of the Aho-Corasick algorithm, with the first used to
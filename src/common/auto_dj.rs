@@ -0,0 +1,30 @@
+use super::ArtistRadio;
+
+/// Where Auto-DJ should pull songs from when topping up the queue.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AutoDjSource {
+    /// Songs by an artist and similar artists found in the library.
+    ArtistRadio(ArtistRadio),
+    /// Songs matching a dynamic playlist's rules (its ordering and limit are ignored).
+    DynamicPlaylist(String),
+    /// Songs from a stored (MPD-side) playlist.
+    Playlist(String),
+    /// Songs with the given genre tag.
+    Genre(String),
+    /// Any song in the library.
+    Library,
+}
+
+impl AutoDjSource {
+    /// Short human-readable description of this source, for display in the queue view.
+    pub fn description(&self) -> String {
+        // TODO: l10n
+        match self {
+            Self::ArtistRadio(radio) => format!("Artist radio: {}", radio.seed),
+            Self::DynamicPlaylist(name) => format!("Auto-DJ: {name}"),
+            Self::Playlist(name) => format!("Auto-DJ: {name}"),
+            Self::Genre(genre) => format!("Auto-DJ: {genre}"),
+            Self::Library => "Auto-DJ: whole library".to_owned(),
+        }
+    }
+}
@@ -13,20 +13,28 @@ pub mod song;
 pub mod sticker;
 pub mod theme_selector;
 pub mod dynamic_playlist;
+pub mod auto_dj;
+pub mod queue_snapshot;
+pub mod radio_station;
+pub mod podcast;
 
-pub use song_row::SongRow;
+pub use song_row::{DraggedUris, SongRow};
 pub use content_view::ContentView;
 pub use row_add_buttons::RowAddButtons;
 pub use row_edit_buttons::RowEditButtons;
 pub use sticker::Stickers;
 pub use album::{Album, AlbumInfo};
-pub use artist::{artists_to_string, parse_mb_artist_tag, Artist, ArtistInfo};
+pub use artist::{artists_to_string, parse_mb_artist_tag, Artist, ArtistInfo, ArtistRadio};
 pub use inode::{INode, INodeType};
 pub use marquee::Marquee;
 pub use rating::Rating;
 pub use song::{QualityGrade, Song, SongInfo};
 pub use theme_selector::ThemeSelector;
 pub use dynamic_playlist::DynamicPlaylist;
+pub use auto_dj::AutoDjSource;
+pub use queue_snapshot::QueueSnapshot;
+pub use radio_station::{is_stream_uri, stream_title, RadioStation};
+pub use podcast::{PodcastEpisode, PodcastFeed};
 
 
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
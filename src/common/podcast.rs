@@ -0,0 +1,317 @@
+use std::time::Duration;
+
+use quick_xml::{events::{BytesStart, Event}, Reader};
+use reqwest::{blocking::Client, header::USER_AGENT};
+use time::OffsetDateTime;
+
+use crate::{config::APPLICATION_USER_AGENT, utils::parse_duration_secs};
+
+/// Give up on a feed download after this long.
+const FETCH_TIMEOUT_S: u64 = 30;
+
+/// A podcast subscription. Like radio stations, subscriptions are stored locally and
+/// their episodes are played by adding the enclosure URLs to the queue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PodcastFeed {
+    pub url: String,
+    /// Falls back to the feed URL until the feed has been downloaded once.
+    pub title: String,
+    pub description: Option<String>,
+    pub link: Option<String>,
+    pub image_url: Option<String>,
+    pub last_checked: Option<OffsetDateTime>,
+    /// Why the last download or parse failed, if it did.
+    pub last_error: Option<String>,
+}
+
+impl PodcastFeed {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            title: url.to_owned(),
+            description: None,
+            link: None,
+            image_url: None,
+            last_checked: None,
+            last_error: None,
+        }
+    }
+
+    /// Whether the feed should be downloaded again. Feeds that have never been checked
+    /// are always due, while an interval of 0 disables scheduled refreshes.
+    pub fn is_due(&self, interval_mins: u32, now: OffsetDateTime) -> bool {
+        match self.last_checked {
+            None => true,
+            Some(_) if interval_mins == 0 => false,
+            Some(last) => now - last >= time::Duration::minutes(interval_mins as i64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PodcastEpisode {
+    pub feed_url: String,
+    /// Unique within the feed. Taken from the item's GUID (or Atom ID), falling back to
+    /// the enclosure URL for feeds that don't provide one.
+    pub guid: String,
+    pub title: String,
+    /// Enclosure URL, which is what actually gets queued.
+    pub url: String,
+    pub description: Option<String>,
+    pub published: Option<OffsetDateTime>,
+    /// In seconds.
+    pub duration: Option<f64>,
+    pub listened: bool,
+    /// Resume position in seconds.
+    pub position: f64,
+}
+
+impl PodcastEpisode {
+    fn new(feed_url: &str) -> Self {
+        Self {
+            feed_url: feed_url.to_owned(),
+            guid: String::new(),
+            title: String::new(),
+            url: String::new(),
+            description: None,
+            published: None,
+            duration: None,
+            listened: false,
+            position: 0.0,
+        }
+    }
+}
+
+/// Download a feed. Local feeds can be given as file:// URLs.
+pub fn fetch_feed(url: &str) -> Result<String, String> {
+    if let Some(path) = url.strip_prefix("file://") {
+        let path = urlencoding::decode(path).map_err(|e| e.to_string())?;
+        return std::fs::read_to_string(path.as_ref()).map_err(|e| e.to_string());
+    }
+    Client::builder()
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_S))
+        .build()
+        .map_err(|e| e.to_string())?
+        .get(url)
+        .header(USER_AGENT, APPLICATION_USER_AGENT)
+        .send()
+        .and_then(|res| res.error_for_status())
+        .and_then(|res| res.text())
+        .map_err(|e| e.to_string())
+}
+
+/// Parse an RSS (0.9x, 1.0 or 2.0) or Atom feed. Only items with an audio or video
+/// enclosure are returned as episodes.
+pub fn parse_feed(feed_url: &str, contents: &str) -> Result<(PodcastFeed, Vec<PodcastEpisode>), String> {
+    let mut reader = Reader::from_str(contents);
+    reader.config_mut().trim_text(true);
+
+    let mut feed = PodcastFeed::new(feed_url);
+    let mut feed_title: Option<String> = None;
+    let mut episodes: Vec<PodcastEpisode> = Vec::new();
+    // The item or entry currently being read, if any
+    let mut episode: Option<PodcastEpisode> = None;
+    // Lowercased qualified names of the currently open elements
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut is_feed = false;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = element_name(&e);
+                if path.is_empty() {
+                    is_feed = matches!(name.as_str(), "rss" | "rdf:rdf" | "feed");
+                }
+                if name == "item" || name == "entry" {
+                    episode = Some(PodcastEpisode::new(feed_url));
+                }
+                read_attributes(&e, &name, path.last().map(String::as_str), &mut feed, episode.as_mut());
+                path.push(name);
+                text.clear();
+            }
+            Ok(Event::Empty(e)) => {
+                let name = element_name(&e);
+                read_attributes(&e, &name, path.last().map(String::as_str), &mut feed, episode.as_mut());
+            }
+            Ok(Event::Text(t)) => {
+                match t.unescape() {
+                    Ok(val) => text.push_str(&val),
+                    Err(_) => text.push_str(&String::from_utf8_lossy(&t)),
+                }
+            }
+            Ok(Event::CData(t)) => {
+                text.push_str(&String::from_utf8_lossy(&t));
+            }
+            Ok(Event::End(_)) => {
+                let Some(name) = path.pop() else {
+                    continue;
+                };
+                let parent = path.last().map_or("", String::as_str);
+                let val = std::mem::take(&mut text).trim().to_owned();
+                if name == "item" || name == "entry" {
+                    if let Some(mut ep) = episode.take() {
+                        if !ep.url.is_empty() {
+                            if ep.guid.is_empty() {
+                                ep.guid = ep.url.clone();
+                            }
+                            if ep.title.is_empty() {
+                                ep.title = ep.guid.clone();
+                            }
+                            episodes.push(ep);
+                        }
+                    }
+                } else if val.is_empty() {
+                    continue;
+                } else if let Some(ep) = episode.as_mut() {
+                    read_episode_text(ep, &name, parent, val);
+                } else {
+                    match (name.as_str(), parent) {
+                        ("title", "channel" | "feed") => {
+                            feed_title = Some(val);
+                        }
+                        ("description" | "subtitle" | "itunes:summary", "channel" | "feed") => {
+                            feed.description.get_or_insert(val);
+                        }
+                        ("link", "channel") => {
+                            feed.link.get_or_insert(val);
+                        }
+                        ("url", "image") | ("logo" | "icon", "feed") => {
+                            feed.image_url.get_or_insert(val);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                return Err(format!("Invalid feed XML at byte {}: {e}", reader.error_position()));
+            }
+            _ => {}
+        }
+    }
+
+    if !is_feed {
+        return Err("Not an RSS or Atom feed".to_owned());
+    }
+    if let Some(title) = feed_title {
+        feed.title = title;
+    }
+    Ok((feed, episodes))
+}
+
+fn element_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.name().as_ref()).to_lowercase()
+}
+
+fn attribute(e: &BytesStart, key: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| attr.key.as_ref().eq_ignore_ascii_case(key.as_bytes()))
+        .and_then(|attr| attr.unescape_value().ok().map(|val| val.trim().to_owned()))
+        .filter(|val| !val.is_empty())
+}
+
+/// Whether an enclosure's MIME type (if given) is something MPD can play.
+fn is_playable_type(mime: Option<&str>) -> bool {
+    mime.is_none_or(|mime| mime.starts_with("audio/") || mime.starts_with("video/"))
+}
+
+/// Handle the parts of a feed that are given as attributes rather than text.
+fn read_attributes(
+    e: &BytesStart,
+    name: &str,
+    parent: Option<&str>,
+    feed: &mut PodcastFeed,
+    episode: Option<&mut PodcastEpisode>,
+) {
+    match (name, episode) {
+        // RSS enclosures. Media RSS content is only used if there's no proper enclosure.
+        ("enclosure" | "media:content", Some(ep)) => {
+            if ep.url.is_empty() && is_playable_type(attribute(e, "type").as_deref()) {
+                if let Some(url) = attribute(e, "url") {
+                    ep.url = url;
+                }
+            }
+        }
+        // Atom enclosures
+        ("link", Some(ep)) => {
+            if ep.url.is_empty()
+                && attribute(e, "rel").as_deref() == Some("enclosure")
+                && is_playable_type(attribute(e, "type").as_deref())
+            {
+                if let Some(url) = attribute(e, "href") {
+                    ep.url = url;
+                }
+            }
+        }
+        ("link", None) if parent == Some("feed") => {
+            if attribute(e, "rel").is_none_or(|rel| rel == "alternate") {
+                if let Some(href) = attribute(e, "href") {
+                    feed.link.get_or_insert(href);
+                }
+            }
+        }
+        ("itunes:image", None) => {
+            if let Some(href) = attribute(e, "href") {
+                // Usually larger than the plain RSS image
+                feed.image_url = Some(href);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn read_episode_text(ep: &mut PodcastEpisode, name: &str, parent: &str, val: String) {
+    match name {
+        // Skip titles of nested elements such as Media RSS groups
+        "title" if parent == "item" || parent == "entry" => {
+            ep.title = val;
+        }
+        "guid" | "id" => {
+            ep.guid = val;
+        }
+        "pubdate" | "published" | "dc:date" => {
+            ep.published = parse_date(&val).or(ep.published);
+        }
+        "updated" => {
+            if ep.published.is_none() {
+                ep.published = parse_date(&val);
+            }
+        }
+        "itunes:duration" => {
+            ep.duration = parse_duration_secs(&val).filter(|secs| *secs > 0.0);
+        }
+        "description" | "summary" | "itunes:summary" => {
+            ep.description.get_or_insert(val);
+        }
+        _ => {}
+    }
+}
+
+/// Parse the RFC 2822 dates of RSS and the RFC 3339 ones of Atom.
+fn parse_date(val: &str) -> Option<OffsetDateTime> {
+    let dt = chrono::DateTime::parse_from_rfc2822(val)
+        .or_else(|_| chrono::DateTime::parse_from_rfc3339(val))
+        .ok()?;
+    OffsetDateTime::from_unix_timestamp(dt.timestamp()).ok()
+}
+
+/// Feed and episode descriptions are usually HTML. Strip it down to plain text for
+/// display.
+pub fn plain_text(html: &str) -> String {
+    let mut res = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                res.push(' ');
+            }
+            _ if !in_tag => res.push(c),
+            _ => {}
+        }
+    }
+    res.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
@@ -0,0 +1,16 @@
+use crate::player::PlaybackFlow;
+
+/// A named copy of the queue along with where we were in it & how it was being played.
+/// Unlike MPD stored playlists, these keep the play position and playback flags too.
+#[derive(Debug, Clone)]
+pub struct QueueSnapshot {
+    pub name: String,
+    pub uris: Vec<String>,
+    /// Queue position of the song that was playing, if any.
+    pub pos: Option<u32>,
+    /// Elapsed time of that song, in seconds.
+    pub elapsed: f64,
+    pub flow: PlaybackFlow,
+    pub random: bool,
+    pub consume: bool,
+}
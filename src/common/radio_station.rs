@@ -0,0 +1,131 @@
+use super::SongInfo;
+
+/// An internet radio station. Stations are stored locally (not on the MPD server) and
+/// played by adding their stream URL to the queue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RadioStation {
+    /// Also used as the key for the station's logo in the image cache.
+    pub name: String,
+    pub url: String,
+    pub genre: Option<String>,
+}
+
+/// Whether a queue URI is a remote stream rather than a file in the MPD library.
+pub fn is_stream_uri(uri: &str) -> bool {
+    uri.contains("://") && !uri.starts_with("file://")
+}
+
+/// Parse the stations in an M3U or PLS playlist. Entries without a title are named
+/// after the playlist file instead.
+pub fn parse_station_list(contents: &str, fallback_name: &str) -> Vec<RadioStation> {
+    let is_pls = contents
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| line.trim().eq_ignore_ascii_case("[playlist]"));
+    let mut res: Vec<RadioStation> = if is_pls {
+        parse_pls(contents)
+    } else {
+        parse_m3u(contents)
+    };
+    let n_unnamed = res.iter().filter(|station| station.name.is_empty()).count();
+    let mut idx: usize = 0;
+    for station in res.iter_mut() {
+        if station.name.is_empty() {
+            idx += 1;
+            station.name = if n_unnamed > 1 {
+                format!("{fallback_name} ({idx})")
+            } else {
+                fallback_name.to_owned()
+            };
+        }
+    }
+    res
+}
+
+fn parse_m3u(contents: &str) -> Vec<RadioStation> {
+    let mut res: Vec<RadioStation> = Vec::new();
+    let mut name: Option<String> = None;
+    let mut genre: Option<String> = None;
+    for line in contents.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:<duration> [attributes],<title>
+            name = info
+                .split_once(',')
+                .map(|(_, title)| title.trim().to_owned())
+                .filter(|title| !title.is_empty());
+            genre = extinf_attribute(info, "group-title");
+        } else if let Some(val) = line.strip_prefix("#EXTGENRE:") {
+            genre = Some(val.trim().to_owned()).filter(|val| !val.is_empty());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            if is_stream_uri(line) {
+                res.push(RadioStation {
+                    name: name.take().unwrap_or_default(),
+                    url: line.to_owned(),
+                    genre: genre.take(),
+                });
+            } else {
+                name = None;
+                genre = None;
+            }
+        }
+    }
+    res
+}
+
+/// Get a key="value" attribute from the part of an #EXTINF line before the title.
+fn extinf_attribute(info: &str, key: &str) -> Option<String> {
+    let attribs = info.split_once(',').map_or(info, |(attribs, _)| attribs);
+    let start = attribs.find(&format!("{key}=\""))? + key.len() + 2;
+    let len = attribs[start..].find('"')?;
+    Some(attribs[start..start + len].to_owned()).filter(|val| !val.is_empty())
+}
+
+fn parse_pls(contents: &str) -> Vec<RadioStation> {
+    // Entries are numbered (File1, Title1, ...) and may come in any order.
+    let mut entries: Vec<(u32, RadioStation)> = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        let Some((key, val)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let (field, num) = if let Some(num) = key.strip_prefix("file") {
+            ("file", num)
+        } else if let Some(num) = key.strip_prefix("title") {
+            ("title", num)
+        } else {
+            continue;
+        };
+        let Ok(num) = num.parse::<u32>() else {
+            continue;
+        };
+        let idx = entries.iter().position(|(n, _)| *n == num).unwrap_or_else(|| {
+            entries.push((
+                num,
+                RadioStation {
+                    name: String::new(),
+                    url: String::new(),
+                    genre: None,
+                },
+            ));
+            entries.len() - 1
+        });
+        match field {
+            "file" => entries[idx].1.url = val.trim().to_owned(),
+            _ => entries[idx].1.name = val.trim().to_owned(),
+        }
+    }
+    entries.sort_by_key(|(num, _)| *num);
+    entries
+        .into_iter()
+        .map(|(_, station)| station)
+        .filter(|station| is_stream_uri(&station.url))
+        .collect()
+}
+
+/// The title a stream is currently reporting through its ICY metadata, if any.
+pub fn stream_title(song: &SongInfo) -> Option<&str> {
+    (is_stream_uri(&song.uri)
+        && song.title != song.uri
+        && song.stream_name.as_ref() != Some(&song.title))
+        .then_some(song.title.as_str())
+}
@@ -16,7 +16,7 @@ use crate::cache::{get_image_cache_path, sqlite};
 use crate::utils::get_time_ago_desc;
 
 use super::Stickers;
-use super::{artists_to_string, parse_mb_artist_tag, AlbumInfo, ArtistInfo};
+use super::{artists_to_string, is_stream_uri, parse_mb_artist_tag, AlbumInfo, ArtistInfo};
 
 // Mostly for eyecandy
 #[derive(Clone, Copy, Debug, glib::Enum, PartialEq, Default)]
@@ -86,13 +86,15 @@ pub struct SongInfo {
     pub uri: String,
     #[derivative(Default(value = "String::from(\"Untitled Song\")"))]
     pub title: String, // Might just be filename
+    pub stream_name: Option<String>, // Name reported by an internet radio stream
     // last_mod: RefCell<Option<u64>>,
     pub artists: Vec<ArtistInfo>,
     pub artist_tag: Option<String>, // Original tag, with all the linkages and formatting
     pub duration: Option<Duration>, // Default to 0 if somehow the option in mpd's Song is None
     pub queue_id: Option<u32>,
     pub queue_pos: Option<u32>,  // Only set once at creation. Subsequent updates are kept in the Song GObject.
-    // range: Option<Range>,
+    pub queue_prio: u8,  // MPD queue priority (0-255). Only meaningful in random mode.
+    pub range: Option<(f64, Option<f64>)>,  // Queue play range (start, optional end) in seconds
     pub album: Option<AlbumInfo>,
     #[derivative(Default(value = "Cell::new(-1)"))]
     pub track: Cell<i64>,
@@ -179,6 +181,7 @@ mod imp {
                     ParamSpecChar::builder("rating").read_only().build(),
                     ParamSpecUInt::builder("queue-id").build(),
                     ParamSpecUInt::builder("queue-pos").build(),
+                    ParamSpecUInt::builder("queue-priority").read_only().build(),
                     ParamSpecBoolean::builder("is-queued").read_only().build(),
                     ParamSpecBoolean::builder("is-playing").read_only().build(),
                     ParamSpecString::builder("album").read_only().build(),
@@ -214,6 +217,7 @@ mod imp {
                 "rating" => obj.get_rating().unwrap_or(-1).to_value(),
                 "queue-id" => obj.get_queue_id().to_value(),
                 "queue-pos" => obj.get_queue_pos().to_value(),
+                "queue-priority" => (obj.get_queue_priority() as u32).to_value(),
                 "is-queued" => obj.is_queued().to_value(),
                 "is-playing" => obj.is_playing().to_value(),
                 "album" => obj.get_album_title().to_value(),
@@ -323,6 +327,14 @@ impl Song {
         self.get_info().queue_pos.unwrap_or(0)
     }
 
+    pub fn get_queue_priority(&self) -> u8 {
+        self.get_info().queue_prio
+    }
+
+    pub fn get_range(&self) -> Option<(f64, Option<f64>)> {
+        self.get_info().range
+    }
+
     pub fn is_queued(&self) -> bool {
         self.get_info().queue_id.is_some()
     }
@@ -439,6 +451,10 @@ impl From<mpd::song::Song> for SongInfo {
         if let Some(title) = song.title {
             name = title;
         }
+        // Streams have no meaningful filename. Fall back to whatever the station calls itself.
+        else if is_stream_uri(&song.file) {
+            name = song.name.clone().unwrap_or_else(|| song.file.clone());
+        }
         // Else extract from URI
         else if let Some(stem) = Path::new(&song.file).file_stem() {
             name = String::from(stem.to_str().unwrap());
@@ -448,11 +464,14 @@ impl From<mpd::song::Song> for SongInfo {
         let mut res = Self {
             uri: song.file,
             title: name,
+            stream_name: song.name,
             artists,
             artist_tag: song.artist,
             duration: song.duration,
             queue_id: None,
             queue_pos: None,
+            queue_prio: 0,
+            range: song.range.map(|range| (range.0.as_secs_f64(), range.1.map(|end| end.as_secs_f64()))),
             album: None,
             track: Cell::new(-1),
             disc: Cell::new(-1),
@@ -466,6 +485,7 @@ impl From<mpd::song::Song> for SongInfo {
         if let Some(place) = song.place {
             let _ = res.queue_id.replace(place.id.0);
             let _ = res.queue_pos.replace(place.pos);
+            res.queue_prio = place.prio;
         }
 
         // Search tags vector for additional fields we can use.
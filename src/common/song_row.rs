@@ -22,6 +22,9 @@ use crate::{
 
 use super::QualityGrade;
 
+/// Drag payload for library songs dropped onto the queue: their URIs, in list order.
+pub struct DraggedUris(pub Vec<String>);
+
 // Wrapper around the common row object to implement song thumbnail fetch logic.
 mod imp {
     use super::*;
@@ -314,11 +317,59 @@ impl SongRow {
                 ))
             );
             res.update_playing_indicator(player);
+        } else {
+            // Rows outside the queue can be dragged into it
+            res.setup_uri_drag();
         }
 
         res
     }
 
+    fn setup_uri_drag(&self) {
+        let drag_source = gtk::DragSource::new();
+        drag_source.set_actions(gdk::DragAction::COPY);
+        drag_source.connect_prepare(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[upgrade_or]
+            None,
+            move |_, _, _| {
+                let uris = this.drag_uris();
+                if uris.is_empty() {
+                    return None;
+                }
+                Some(gdk::ContentProvider::for_value(
+                    &glib::BoxedAnyObject::new(DraggedUris(uris)).to_value()
+                ))
+            }
+        ));
+        self.add_controller(drag_source);
+    }
+
+    /// URIs of the songs to drag from this row: those of the whole selection of the list
+    /// containing it if this row is part of that selection, or just this row's otherwise.
+    fn drag_uris(&self) -> Vec<String> {
+        let Some(song) = self.song().clone() else {
+            return Vec::new();
+        };
+        let selected: Vec<Song> = self
+            .ancestor(gtk::ListView::static_type())
+            .and_downcast::<gtk::ListView>()
+            .and_then(|list| list.model())
+            .map(|model| {
+                let sel = model.selection();
+                (0..sel.size() as u32)
+                    .filter_map(|idx| model.item(sel.nth(idx)).and_downcast::<Song>())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if selected.contains(&song) {
+            selected.iter().map(|song| song.get_uri().to_owned()).collect()
+        } else {
+            vec![song.get_uri().to_owned()]
+        }
+    }
+
     fn update_playing_indicator(&self, player: &Player) {
         match (
             player.queue_id(),
@@ -9,7 +9,7 @@ use std::{
 use time::{format_description, Date};
 use derivative::Derivative;
 use ashpd::desktop::file_chooser::SelectedFiles;
-use super::{artist_tag::ArtistTag, Library};
+use super::{artist_tag::ArtistTag, CoListenedBox, CoverPicker, Library};
 use crate::{
     cache::{placeholders::{ALBUMART_PLACEHOLDER, EMPTY_ALBUM_STRING}, Cache, CacheState},
     client::{state::StickersSupportLevel, ClientState},
@@ -74,6 +74,8 @@ mod imp {
         pub content_spinner: TemplateChild<gtk::Stack>,
         #[template_child]
         pub content: TemplateChild<gtk::ListView>,
+        #[template_child]
+        pub co_listened: TemplateChild<CoListenedBox>,
 
         #[derivative(Default(value = "gio::ListStore::new::<Song>()"))]
         pub song_list: gio::ListStore,
@@ -225,6 +227,23 @@ mod imp {
                     }
                 ))
                 .build();
+            let action_find_album_art = ActionEntry::builder("find-album-art")
+                .activate(clone!(
+                    #[weak]
+                    obj,
+                    #[upgrade_or]
+                    (),
+                    move |_, _, _| {
+                        if let (Some(album), Some(cache)) = (
+                            obj.imp().album.borrow().as_ref(),
+                            obj.imp().cache.get()
+                        ) {
+                            CoverPicker::new(cache.clone(), album.get_info())
+                                .present(obj.imp().window.upgrade().as_ref());
+                        }
+                    }
+                ))
+                .build();
             let action_clear_album_art = ActionEntry::builder("clear-album-art")
                 .activate(clone!(
                     #[weak]
@@ -304,6 +323,7 @@ mod imp {
             actions.add_action_entries([
                 action_clear_rating,
                 action_set_album_art,
+                action_find_album_art,
                 action_refetch_metadata,
                 action_clear_album_art,
                 action_insert_queue,
@@ -422,6 +442,9 @@ impl AlbumContentView {
             .add_to_playlist
             .setup(library, &self.imp().sel_model);
         self.imp().library.set(Some(library));
+        self.imp()
+            .co_listened
+            .setup(library, self.imp().cache.get().unwrap().clone(), client_state);
         cache_state.connect_closure(
             "album-art-downloaded",
             false,
@@ -537,6 +560,51 @@ impl AlbumContentView {
                 }
             ),
         );
+        client_state.connect_closure(
+            "album-co-listened-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState,
+                      tag: String,
+                      songs: glib::BoxedAnyObject,
+                      albums: glib::BoxedAnyObject,
+                      artists: glib::BoxedAnyObject| {
+                    if let Some(album) = this.imp().album.borrow().as_ref() {
+                        if album.get_title() == tag {
+                            this.imp().co_listened.set_content(
+                                songs.borrow::<Vec<Song>>().as_ref(),
+                                albums.borrow::<Vec<Album>>().as_ref(),
+                                artists.borrow::<Vec<Artist>>().as_ref(),
+                            );
+                        }
+                    }
+                }
+            ),
+        );
+        self.imp().co_listened.connect_closure(
+            "album-clicked",
+            false,
+            closure_local!(
+                #[weak]
+                window,
+                move |_: CoListenedBox, album: Album| {
+                    window.goto_album(&album);
+                }
+            ),
+        );
+        self.imp().co_listened.connect_closure(
+            "artist-clicked",
+            false,
+            closure_local!(
+                #[weak]
+                window,
+                move |_: CoListenedBox, artist: Artist| {
+                    window.goto_artist(&artist);
+                }
+            ),
+        );
 
         let replace_queue_btn = self.imp().replace_queue.get();
         client_state
@@ -831,6 +899,7 @@ impl AlbumContentView {
         
         // Unset metadata widgets
         self.imp().song_list.remove_all();
+        self.imp().co_listened.clear();
         let content_spinner = self.imp().content_spinner.get();
         if content_spinner.visible_child_name().unwrap() != "spinner" {
             content_spinner.set_visible_child_name("spinner");
@@ -11,7 +11,7 @@ use std::{
 use ashpd::desktop::file_chooser::SelectedFiles;
 use derivative::Derivative;
 
-use super::{AlbumCell, Library};
+use super::{AlbumCell, CoListenedBox, Library};
 use crate::{
     cache::{placeholders::EMPTY_ARTIST_STRING, Cache, CacheState},
     client::ClientState,
@@ -50,8 +50,12 @@ mod imp {
         // pub runtime: TemplateChild<gtk::Label>,
         //
         #[template_child]
+        pub discography_btn: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
         pub all_songs_btn: TemplateChild<gtk::ToggleButton>,
         #[template_child]
+        pub together_btn: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
         pub subview_stack: TemplateChild<gtk::Stack>,
 
         // All songs sub-view
@@ -86,6 +90,12 @@ mod imp {
         #[derivative(Default(value = "gio::ListStore::new::<Album>()"))]
         pub album_list: gio::ListStore,
 
+        // Played together sub-view
+        #[template_child]
+        pub together_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub co_listened: TemplateChild<CoListenedBox>,
+
         pub library: WeakRef<Library>,
         pub artist: RefCell<Option<Artist>>,
         pub bindings: RefCell<Vec<Binding>>,
@@ -169,24 +179,23 @@ mod imp {
                 }
             ));
 
-            self.all_songs_btn
-                .bind_property(
-                    "active",
-                    &self.subview_stack.get(),
-                    "visible-child-name"
-                )
-                .transform_to(
-                    |_, active| {
-                        if active {
-                            Some("songs")
-                        }
-                        else {
-                            Some("albums")
+            // Only react to the newly-activated button, as the previously active one
+            // in the group will also emit a toggled signal.
+            for (btn, subview) in [
+                (self.discography_btn.get(), "albums"),
+                (self.all_songs_btn.get(), "songs"),
+                (self.together_btn.get(), "together"),
+            ] {
+                btn.connect_toggled(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    move |btn| {
+                        if btn.is_active() {
+                            this.subview_stack.set_visible_child_name(subview);
                         }
                     }
-                )
-                .sync_create()
-                .build();
+                ));
+            }
 
             // Edit actions
             let obj = self.obj();
@@ -277,12 +286,30 @@ mod imp {
                 ))
                 .build();
 
+            let action_start_radio = ActionEntry::builder("start-radio")
+                .activate(clone!(
+                    #[weak]
+                    obj,
+                    #[upgrade_or]
+                    (),
+                    move |_, _, _| {
+                        if let (Some(artist), Some(library)) = (
+                            obj.imp().artist.borrow().as_ref(),
+                            obj.imp().library.upgrade()
+                        ) {
+                            library.start_artist_radio(artist);
+                        }
+                    }
+                ))
+                .build();
+
             // Create a new action group and add actions to it
             let actions = SimpleActionGroup::new();
             actions.add_action_entries([
                 action_set_avatar,
                 action_clear_avatar,
-                action_refetch_metadata
+                action_refetch_metadata,
+                action_start_radio
             ]);
             self.obj().insert_action_group("artist-content-view", Some(&actions));
         }
@@ -290,9 +317,14 @@ mod imp {
         fn signals() -> &'static [Signal] {
             static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
             SIGNALS.get_or_init(|| {
-                vec![Signal::builder("album-clicked")
-                    .param_types([Album::static_type()])
-                    .build()]
+                vec![
+                    Signal::builder("album-clicked")
+                        .param_types([Album::static_type()])
+                        .build(),
+                    Signal::builder("artist-clicked")
+                        .param_types([Artist::static_type()])
+                        .build(),
+                ]
             })
         }
     }
@@ -660,6 +692,61 @@ impl ArtistContentView {
             .build();
     }
 
+    fn setup_together_subview(&self, library: &Library, client_state: &ClientState) {
+        self.imp()
+            .co_listened
+            .setup(library, self.imp().cache.get().unwrap().clone(), client_state);
+        client_state.connect_closure(
+            "artist-co-listened-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState,
+                      name: String,
+                      songs: glib::BoxedAnyObject,
+                      albums: glib::BoxedAnyObject,
+                      artists: glib::BoxedAnyObject| {
+                    if let Some(artist) = this.imp().artist.borrow().as_ref() {
+                        if name == artist.get_name() {
+                            let co_listened = this.imp().co_listened.get();
+                            co_listened.set_content(
+                                songs.borrow::<Vec<Song>>().as_ref(),
+                                albums.borrow::<Vec<Album>>().as_ref(),
+                                artists.borrow::<Vec<Artist>>().as_ref(),
+                            );
+                            this.imp().together_stack.set_visible_child_name(
+                                if co_listened.is_empty() { "empty" } else { "content" }
+                            );
+                        }
+                    }
+                }
+            ),
+        );
+        self.imp().co_listened.connect_closure(
+            "album-clicked",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: CoListenedBox, album: Album| {
+                    this.emit_by_name::<()>("album-clicked", &[&album]);
+                }
+            ),
+        );
+        self.imp().co_listened.connect_closure(
+            "artist-clicked",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: CoListenedBox, artist: Artist| {
+                    this.emit_by_name::<()>("artist-clicked", &[&artist]);
+                }
+            ),
+        );
+    }
+
     pub fn setup(&self, library: &Library, cache: Rc<Cache>, client_state: &ClientState) {
         self.imp().cache.set(cache).expect("Could not register artist content view with cache controller");
         self.imp().library.set(Some(library));
@@ -667,6 +754,7 @@ impl ArtistContentView {
         self.setup_info_box();
         self.setup_song_subview(client_state.clone());
         self.setup_album_subview(client_state);
+        self.setup_together_subview(library, client_state);
 
         self.imp()
             .add_to_playlist
@@ -772,5 +860,7 @@ impl ArtistContentView {
     fn clear_content(&self) {
         self.imp().song_list.remove_all();
         self.imp().album_list.remove_all();
+        self.imp().co_listened.clear();
+        self.imp().together_stack.set_visible_child_name("empty");
     }
 }
@@ -0,0 +1,306 @@
+use glib::{clone, closure_local, subclass::Signal, WeakRef};
+use gtk::{gio, glib, prelude::*, subclass::prelude::*, CompositeTemplate, ListItem, SignalListItemFactory};
+use std::{rc::Rc, sync::OnceLock};
+use derivative::Derivative;
+
+use crate::{
+    cache::Cache,
+    client::ClientState,
+    common::{marquee::MarqueeWrapMode, Album, Artist, RowAddButtons, Song, SongRow},
+    utils::format_secs_as_duration
+};
+
+use super::{AlbumCell, ArtistCell, Library};
+
+// Shows songs, albums & artists that were often played in the same listening
+// sessions as the album or artist currently being viewed. They are mined from
+// the local playback history, so this works fully offline.
+mod imp {
+    use super::*;
+
+    #[derive(Debug, CompositeTemplate, Derivative)]
+    #[derivative(Default)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/library/co-listened-box.ui")]
+    pub struct CoListenedBox {
+        #[template_child]
+        pub continue_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub list: TemplateChild<gtk::ListView>,
+        #[template_child]
+        pub album_section: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub album_row: TemplateChild<gtk::GridView>,
+        #[template_child]
+        pub artist_section: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub artist_row: TemplateChild<gtk::GridView>,
+        #[derivative(Default(value = "gio::ListStore::new::<Song>()"))]
+        pub songs: gio::ListStore,
+        #[derivative(Default(value = "gio::ListStore::new::<Album>()"))]
+        pub albums: gio::ListStore,
+        #[derivative(Default(value = "gio::ListStore::new::<Artist>()"))]
+        pub artists: gio::ListStore,
+        pub library: WeakRef<Library>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for CoListenedBox {
+        const NAME: &'static str = "EuphonicaCoListenedBox";
+        type Type = super::CoListenedBox;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for CoListenedBox {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.list.set_model(Some(&gtk::NoSelection::new(Some(self.songs.clone()))));
+            self.album_row.set_model(Some(&gtk::SingleSelection::new(Some(self.albums.clone()))));
+            self.artist_row.set_model(Some(&gtk::SingleSelection::new(Some(self.artists.clone()))));
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![
+                    Signal::builder("album-clicked")
+                        .param_types([Album::static_type()])
+                        .build(),
+                    Signal::builder("artist-clicked")
+                        .param_types([Artist::static_type()])
+                        .build(),
+                ]
+            })
+        }
+    }
+
+    impl WidgetImpl for CoListenedBox {}
+    impl BoxImpl for CoListenedBox {}
+}
+
+glib::wrapper! {
+    pub struct CoListenedBox(ObjectSubclass<imp::CoListenedBox>)
+        @extends gtk::Box, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Orientable;
+}
+
+impl Default for CoListenedBox {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl CoListenedBox {
+    pub fn setup(&self, library: &Library, cache: Rc<Cache>, client_state: &ClientState) {
+        self.imp().library.set(Some(library));
+
+        let continue_btn = self.imp().continue_btn.get();
+        client_state
+            .bind_property("is-queuing", &continue_btn, "sensitive")
+            .invert_boolean()
+            .sync_create()
+            .build();
+        continue_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[upgrade_or]
+            (),
+            move |_| {
+                if let Some(library) = this.imp().library.upgrade() {
+                    let songs: Vec<Song> = this
+                        .imp()
+                        .songs
+                        .iter::<Song>()
+                        .filter_map(|item| item.ok())
+                        .collect();
+                    if !songs.is_empty() {
+                        library.insert_songs_next(&songs);
+                    }
+                }
+            }
+        ));
+
+        let factory = SignalListItemFactory::new();
+        factory.connect_setup(clone!(
+            #[weak]
+            library,
+            #[weak]
+            cache,
+            move |_, list_item| {
+                let item = list_item
+                    .downcast_ref::<ListItem>()
+                    .expect("Needs to be ListItem");
+                let row = SongRow::new(Some(cache), None);
+                item.property_expression("item")
+                    .chain_property::<Song>("name")
+                    .bind(&row, "name", gtk::Widget::NONE);
+
+                row.set_first_attrib_icon_name(Some("music-artist-symbolic"));
+                item.property_expression("item")
+                    .chain_property::<Song>("artist")
+                    .bind(&row, "first-attrib-text", gtk::Widget::NONE);
+
+                row.set_second_attrib_icon_name(Some("hourglass-symbolic"));
+                item.property_expression("item")
+                    .chain_property::<Song>("duration")
+                    .chain_closure::<String>(closure_local!(|_: Option<glib::Object>, dur: u64| {
+                        format_secs_as_duration(dur as f64)
+                    }))
+                    .bind(&row, "second-attrib-text", gtk::Widget::NONE);
+
+                item.property_expression("item")
+                    .chain_property::<Song>("quality-grade")
+                    .bind(&row, "quality-grade", gtk::Widget::NONE);
+                let end_widget = RowAddButtons::new(&library);
+                row.set_end_widget(Some(&end_widget.into()));
+                item.set_child(Some(&row));
+            }
+        ));
+        factory.connect_bind(move |_, list_item| {
+            let item: Song = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .item()
+                .and_downcast::<Song>()
+                .expect("The item has to be a common::Song.");
+            let child: SongRow = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .child()
+                .and_downcast::<SongRow>()
+                .expect("The child has to be a `SongRow`.");
+            child.end_widget().and_downcast::<RowAddButtons>().unwrap().set_song(Some(&item));
+            child.on_bind(&item);
+        });
+        factory.connect_unbind(move |_, list_item| {
+            let child: SongRow = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .child()
+                .and_downcast::<SongRow>()
+                .expect("The child has to be a `SongRow`.");
+            child.end_widget().and_downcast::<RowAddButtons>().unwrap().set_song(None);
+            child.on_unbind();
+        });
+        self.imp().list.set_factory(Some(&factory));
+
+        self.setup_album_row(cache.clone());
+        self.setup_artist_row(cache);
+        self.set_visible(false);
+    }
+
+    fn setup_album_row(&self, cache: Rc<Cache>) {
+        let factory = SignalListItemFactory::new();
+        factory.connect_setup(move |_, list_item| {
+            let item = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem");
+            item.set_child(Some(&AlbumCell::new(item, cache.clone(), Some(MarqueeWrapMode::Scroll))));
+        });
+        factory.connect_bind(move |_, list_item| {
+            let item = list_item.downcast_ref::<ListItem>().expect("Needs to be ListItem");
+            let album: Album = item.item().and_downcast::<Album>().expect("The item has to be a common::Album.");
+            item.child()
+                .and_downcast::<AlbumCell>()
+                .expect("The child has to be an `AlbumCell`.")
+                .bind(&album);
+        });
+        factory.connect_unbind(move |_, list_item| {
+            list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .child()
+                .and_downcast::<AlbumCell>()
+                .expect("The child has to be an `AlbumCell`.")
+                .unbind();
+        });
+        self.imp().album_row.set_factory(Some(&factory));
+        self.imp().album_row.connect_activate(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |grid_view, position| {
+                let album = grid_view
+                    .model()
+                    .expect("The model has to exist.")
+                    .item(position)
+                    .and_downcast::<Album>()
+                    .expect("The item has to be a `common::Album`.");
+                this.emit_by_name::<()>("album-clicked", &[&album]);
+            }
+        ));
+    }
+
+    fn setup_artist_row(&self, cache: Rc<Cache>) {
+        let factory = SignalListItemFactory::new();
+        factory.connect_setup(move |_, list_item| {
+            let item = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem");
+            item.set_child(Some(&ArtistCell::new(item, cache.clone())));
+        });
+        factory.connect_bind(move |_, list_item| {
+            let item = list_item.downcast_ref::<ListItem>().expect("Needs to be ListItem");
+            let artist: Artist = item.item().and_downcast::<Artist>().expect("The item has to be a common::Artist.");
+            item.child()
+                .and_downcast::<ArtistCell>()
+                .expect("The child has to be an `ArtistCell`.")
+                .bind(&artist);
+        });
+        factory.connect_unbind(move |_, list_item| {
+            list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .child()
+                .and_downcast::<ArtistCell>()
+                .expect("The child has to be an `ArtistCell`.")
+                .unbind();
+        });
+        self.imp().artist_row.set_factory(Some(&factory));
+        self.imp().artist_row.connect_activate(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |grid_view, position| {
+                let artist = grid_view
+                    .model()
+                    .expect("The model has to exist.")
+                    .item(position)
+                    .and_downcast::<Artist>()
+                    .expect("The item has to be a `common::Artist`.");
+                this.emit_by_name::<()>("artist-clicked", &[&artist]);
+            }
+        ));
+    }
+
+    /// Replace the shown recommendations. Each section hides itself when empty, and the
+    /// whole box does when there is nothing to show at all.
+    pub fn set_content(&self, songs: &[Song], albums: &[Album], artists: &[Artist]) {
+        let imp = self.imp();
+        imp.songs.remove_all();
+        imp.songs.extend_from_slice(songs);
+        imp.albums.remove_all();
+        imp.albums.extend_from_slice(albums);
+        imp.artists.remove_all();
+        imp.artists.extend_from_slice(artists);
+        imp.list.set_visible(!songs.is_empty());
+        imp.continue_btn.set_visible(!songs.is_empty());
+        imp.album_section.set_visible(!albums.is_empty());
+        imp.artist_section.set_visible(!artists.is_empty());
+        self.set_visible(!self.is_empty());
+    }
+
+    pub fn clear(&self) {
+        self.set_content(&[], &[], &[]);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let imp = self.imp();
+        imp.songs.n_items() == 0 && imp.albums.n_items() == 0 && imp.artists.n_items() == 0
+    }
+}
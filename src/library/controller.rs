@@ -1,16 +1,20 @@
 use crate::{
-    cache::{sqlite, Cache}, client::{BackgroundTask, ClientState, MpdWrapper, StickerSetMode}, common::{Album, Artist, DynamicPlaylist, INode, Song, Stickers}, player::Player, utils::settings_manager
+    cache::{sqlite, Cache}, client::{BackgroundTask, ClientState, MpdWrapper, StickerSetMode}, common::{podcast, Album, Artist, ArtistRadio, AutoDjSource, DynamicPlaylist, INode, PodcastEpisode, RadioStation, Song, Stickers}, player::Player, utils::settings_manager
 };
 use glib::{closure_local, subclass::Signal, clone};
 use gtk::{gio, glib, prelude::*};
 use std::{borrow::Cow, cell::OnceCell, rc::Rc, sync::OnceLock, vec::Vec};
 use derivative::Derivative;
 use chrono::Local;
+use time::OffsetDateTime;
 
 use adw::subclass::prelude::*;
 
 use mpd::{error::Error as MpdError, search::Operation, EditAction, Query, SaveMode, Term};
 
+/// How often to look for podcast feeds due for a refresh.
+const PODCAST_CHECK_PERIOD_S: u32 = 60;
+
 mod imp {
     use std::cell::{Cell, RefCell};
 
@@ -61,6 +65,8 @@ mod imp {
 
         pub cache: OnceCell<Rc<Cache>>,
         pub player: OnceCell<Player>,
+        // Number of podcast refreshes still running in the background
+        pub podcast_refreshes: Cell<u32>,
     }
 
     #[glib::object_subclass]
@@ -104,9 +110,13 @@ mod imp {
         fn signals() -> &'static [Signal] {
             static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
             SIGNALS.get_or_init(|| {
-                vec![Signal::builder("album-clicked")
-                    .param_types([Album::static_type(), gio::ListStore::static_type()])
-                    .build()]
+                vec![
+                    Signal::builder("album-clicked")
+                        .param_types([Album::static_type(), gio::ListStore::static_type()])
+                        .build(),
+                    // Emitted after podcast feeds have been refreshed.
+                    Signal::builder("podcasts-changed").build(),
+                ]
             })
         }
     }
@@ -217,6 +227,23 @@ impl Library {
                 }
             ),
         );
+
+        // Check for new podcast episodes now and then. The interval is re-read every
+        // time so that changing it doesn't require a restart.
+        self.refresh_due_podcasts();
+        glib::timeout_add_seconds_local(
+            PODCAST_CHECK_PERIOD_S,
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || {
+                    this.refresh_due_podcasts();
+                    glib::ControlFlow::Continue
+                }
+            ),
+        );
     }
 
     pub fn clear(&self) {
@@ -262,6 +289,13 @@ impl Library {
             .queue_background(BackgroundTask::FetchAlbumSongs(
                 album.get_title().to_owned(),
             ), true);
+        let settings = settings_manager().child("library");
+        self.client()
+            .queue_background(BackgroundTask::FetchAlbumCoListened(
+                album.get_title().to_owned(),
+                settings.uint("co-listening-session-gap-mins"),
+                settings.uint("n-co-listened-songs"),
+            ), true);
     }
 
     pub fn refetch_album_metadata(&self, album: &Album) {
@@ -274,7 +308,7 @@ impl Library {
     pub fn queue_songs(&self, songs: &[Song], replace: bool, play: bool) {
         // TODO: support executing this atomically as a command list
         if replace {
-            self.client().clear_queue();
+            self.player().clear_queue();
         }
         self.client().queue_background(
             BackgroundTask::QueueUris(
@@ -319,7 +353,7 @@ impl Library {
     /// Queue all songs in a given album by track order.
     pub fn queue_album(&self, album: Album, replace: bool, play: bool, play_from: Option<u32>) {
         if replace {
-            self.client().clear_queue();
+            self.player().clear_queue();
         }
         let mut query = Query::new();
         query.and(
@@ -357,7 +391,7 @@ impl Library {
     /// Queue all songs of an artist. TODO: allow specifying order.
     pub fn queue_artist(&self, artist: Artist, use_albumartist: bool, replace: bool, play: bool) {
         if replace {
-            self.client().clear_queue();
+            self.player().clear_queue();
         }
         let mut query = Query::new();
         query.and_with_op(
@@ -382,6 +416,25 @@ impl Library {
         );
     }
 
+    /// Start an artist radio seeded from the given artist. Similar artists are taken from
+    /// the locally-cached metadata; if none has been fetched yet, the radio will only
+    /// play songs by the seed artist.
+    pub fn start_artist_radio(&self, artist: &Artist) {
+        let similar: Vec<String> = self
+            .cache()
+            .load_cached_artist_meta(artist.get_info())
+            .map_or(Vec::with_capacity(0), |meta| {
+                meta.similar.into_iter().map(|similar| similar.name).collect()
+            });
+        self.player().start_auto_dj(
+            AutoDjSource::ArtistRadio(ArtistRadio {
+                seed: artist.get_name().to_owned(),
+                similar
+            }),
+            true
+        );
+    }
+
     /// Get all the information available about an artist (won't block;
     /// UI will get notified of result later via signals).
     pub fn init_artist(&self, artist: &Artist) {
@@ -390,6 +443,13 @@ impl Library {
         }
         self.client()
             .get_artist_content(artist.get_name().to_owned());
+        let settings = settings_manager().child("library");
+        self.client()
+            .queue_background(BackgroundTask::FetchArtistCoListened(
+                artist.get_name().to_owned(),
+                settings.uint("co-listening-session-gap-mins"),
+                settings.uint("n-co-listened-songs"),
+            ), true);
     }
 
     pub fn refetch_artist_metadata(&self, artist: &Artist) {
@@ -460,7 +520,7 @@ impl Library {
     /// Queue a song or folder (when recursive == true) for playback.
     pub fn queue_uri(&self, uri: &str, replace: bool, play: bool, recursive: bool) {
         if replace {
-            self.client().clear_queue();
+            self.player().clear_queue();
         }
         self.client().queue_background(
             BackgroundTask::QueueUris(
@@ -569,7 +629,7 @@ impl Library {
     /// Queue a playlist for playback.
     pub fn queue_playlist(&self, name: &str, replace: bool, play: bool) {
         if replace {
-            self.client().clear_queue();
+            self.player().clear_queue();
         }
         self.client().queue_background(
             BackgroundTask::QueuePlaylist(
@@ -631,7 +691,7 @@ impl Library {
     /// Get last cached results of a dynamic playlist
     pub fn queue_cached_dynamic_playlist(&self, name: &str, replace: bool, play: bool) {
         if replace {
-            self.client().clear_queue();
+            self.player().clear_queue();
         }
         self.client().queue_background(
             BackgroundTask::QueueDynamicPlaylist(name.to_string(), play), true
@@ -746,6 +806,94 @@ impl Library {
         self.cache().clear_playlist_cover(playlist_name);
     }
 
+    /// Play a radio station right after the current song, keeping the rest of the queue.
+    pub fn play_station(&self, station: &RadioStation) {
+        let pos = self.player().queue_pos().map_or(0, |pos| pos + 1);
+        self.client().queue_background(
+            BackgroundTask::QueueUris(vec![station.url.clone()], false, Some(pos), Some(pos)),
+            true
+        );
+    }
+
+    pub fn set_station_logo(&self, station_name: &str, path: &str) -> glib::JoinHandle<()> {
+        self.cache().set_station_logo(station_name, path)
+    }
+
+    pub fn clear_station_logo(&self, station_name: &str) -> glib::JoinHandle<()> {
+        self.cache().clear_station_logo(station_name)
+    }
+
+    /// Look for a song in the library, such as one heard on the radio. Results are
+    /// returned via the library-search-results client state signal.
+    pub fn search_library(&self, term: &str) {
+        self.client().queue_background(BackgroundTask::SearchLibrary(term.to_owned()), true);
+    }
+
+    /// Subscribe to a podcast feed and download it right away.
+    pub fn subscribe_podcast(&self, url: &str) -> Result<(), sqlite::Error> {
+        sqlite::add_podcast_feed(url)?;
+        self.refresh_podcasts(vec![url.to_owned()]);
+        Ok(())
+    }
+
+    /// Refresh the feeds whose refresh interval has passed.
+    pub fn refresh_due_podcasts(&self) {
+        // Don't pile up scheduled refreshes behind slow feeds
+        if self.imp().podcast_refreshes.get() > 0 {
+            return;
+        }
+        let interval = settings_manager().child("library").uint("podcast-refresh-interval-mins");
+        let now = OffsetDateTime::now_utc();
+        let urls: Vec<String> = sqlite::get_podcast_feeds()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|feed| feed.is_due(interval, now))
+            .map(|feed| feed.url)
+            .collect();
+        if !urls.is_empty() {
+            self.refresh_podcasts(urls);
+        }
+    }
+
+    /// Download the given feeds in the background, then emit podcasts-changed.
+    pub fn refresh_podcasts(&self, urls: Vec<String>) {
+        self.imp().podcast_refreshes.set(self.imp().podcast_refreshes.get() + 1);
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                let _ = gio::spawn_blocking(move || {
+                    for url in urls.iter() {
+                        refresh_podcast_feed(url);
+                    }
+                }).await;
+                this.imp().podcast_refreshes.set(this.imp().podcast_refreshes.get() - 1);
+                this.emit_by_name::<()>("podcasts-changed", &[]);
+            }
+        ));
+    }
+
+    pub fn is_refreshing_podcasts(&self) -> bool {
+        self.imp().podcast_refreshes.get() > 0
+    }
+
+    /// Play an episode right after the current song. The player resumes it from where
+    /// it was left off.
+    pub fn play_podcast_episode(&self, episode: &PodcastEpisode) {
+        let pos = self.player().queue_pos().map_or(0, |pos| pos + 1);
+        self.client().queue_background(
+            BackgroundTask::QueueUris(vec![episode.url.clone()], false, Some(pos), Some(pos)),
+            true
+        );
+    }
+
+    pub fn queue_podcast_episode(&self, episode: &PodcastEpisode) {
+        self.client().queue_background(
+            BackgroundTask::QueueUris(vec![episode.url.clone()], false, None, None),
+            true
+        );
+    }
+
     pub fn fetch_recent_songs(&self) {
         self.imp().recent_songs.remove_all();
         let settings = settings_manager().child("library");
@@ -753,3 +901,21 @@ impl Library {
             .queue_background(BackgroundTask::FetchRecentSongs(settings.uint("n-recent-songs")), true);
     }
 }
+
+/// Download and parse a feed, then store its details and episodes. Runs on a
+/// background thread.
+fn refresh_podcast_feed(url: &str) {
+    let res = podcast::fetch_feed(url).and_then(|contents| podcast::parse_feed(url, &contents));
+    let db_res = match res {
+        Ok((feed, episodes)) => sqlite::update_podcast_feed(&feed)
+            .and_then(|_| sqlite::write_podcast_episodes(&episodes))
+            .map(|n_new| println!("Podcast feed {url}: {n_new} new episode(s)")),
+        Err(e) => {
+            println!("Unable to refresh podcast feed {url}: {e}");
+            sqlite::set_podcast_feed_error(url, &e)
+        }
+    };
+    if let Err(e) = db_res {
+        println!("{e:?}");
+    }
+}
@@ -0,0 +1,260 @@
+use adw::{prelude::*, subclass::prelude::*};
+use glib::clone;
+use gtk::{glib, CompositeTemplate};
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    rc::Rc,
+};
+
+use crate::{
+    cache::{Cache, CoverCandidate},
+    common::AlbumInfo,
+    meta_providers::musicbrainz::ReleaseCandidate,
+};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/library/cover-picker.ui")]
+    pub struct CoverPicker {
+        #[template_child]
+        pub searching: TemplateChild<adw::Spinner>,
+        #[template_child]
+        pub release_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub release_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub releases: TemplateChild<gtk::StringList>,
+        #[template_child]
+        pub stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub candidates: TemplateChild<gtk::FlowBox>,
+
+        pub cache: OnceCell<Rc<Cache>>,
+        pub album: OnceCell<AlbumInfo>,
+        // MBIDs of the releases listed after the first ("as tagged") entry of release_row
+        pub release_ids: RefCell<Vec<String>>,
+        // Candidates currently shown, in the same order as their FlowBox children
+        pub shown: RefCell<Vec<CoverCandidate>>,
+        // Bumped on every new search, so that late results of older ones can be dropped
+        pub generation: Cell<u32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for CoverPicker {
+        const NAME: &'static str = "EuphonicaCoverPicker";
+        type Type = super::CoverPicker;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for CoverPicker {}
+    impl WidgetImpl for CoverPicker {}
+
+    impl AdwDialogImpl for CoverPicker {
+        fn closed(&self) {
+            // Stop accepting results & clean up temporary files.
+            self.generation.set(self.generation.get().wrapping_add(1));
+            self.obj().clear_candidates();
+            self.parent_closed();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct CoverPicker(ObjectSubclass<imp::CoverPicker>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl CoverPicker {
+    /// Dialog for choosing the album's cover among those found by all enabled providers.
+    pub fn new(cache: Rc<Cache>, album: &AlbumInfo) -> Self {
+        let res: Self = glib::Object::new();
+        let imp = res.imp();
+        let _ = imp.cache.set(cache.clone());
+        let _ = imp.album.set(album.clone());
+
+        imp.candidates.connect_child_activated(clone!(
+            #[weak(rename_to = this)]
+            res,
+            move |_, child| {
+                this.pick(child.index());
+            }
+        ));
+
+        res.search(None);
+
+        // Only offer release disambiguation when there's something to disambiguate.
+        let handle = cache.search_releases(album);
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            res,
+            async move {
+                if let Ok(releases) = handle.await {
+                    this.set_releases(releases);
+                }
+            }
+        ));
+
+        res
+    }
+
+    fn set_releases(&self, releases: Vec<ReleaseCandidate>) {
+        if releases.len() < 2 {
+            return;
+        }
+        let imp = self.imp();
+        let tagged = if imp.album.get().unwrap().mbid.is_some() {
+            "As tagged"
+        } else {
+            "Best match"
+        };
+        imp.releases.append(tagged);
+        for release in releases.iter() {
+            let mut label = release.title.clone();
+            if let Some(artist) = release.artist.as_ref() {
+                label.push_str(" – ");
+                label.push_str(artist);
+            }
+            if !release.details.is_empty() {
+                label.push_str(&format!(" ({})", &release.details));
+            }
+            imp.releases.append(&label);
+        }
+        imp.release_ids
+            .replace(releases.into_iter().map(|release| release.mbid).collect());
+        imp.release_row.set_selected(0);
+        // Connect only now to avoid re-searching while populating the list.
+        imp.release_row.connect_selected_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |row| {
+                let mbid = (row.selected() as usize)
+                    .checked_sub(1)
+                    .and_then(|idx| this.imp().release_ids.borrow().get(idx).cloned());
+                this.search(mbid);
+            }
+        ));
+        imp.release_group.set_visible(true);
+    }
+
+    /// Start a new search, optionally pinned to a MusicBrainz release, replacing the
+    /// current candidates.
+    fn search(&self, mbid: Option<String>) {
+        let imp = self.imp();
+        let generation = imp.generation.get().wrapping_add(1);
+        imp.generation.set(generation);
+        self.clear_candidates();
+        imp.stack.set_visible_child_name("loading");
+        imp.searching.set_visible(true);
+        let receiver = imp
+            .cache
+            .get()
+            .unwrap()
+            .find_cover_candidates(imp.album.get().unwrap(), mbid);
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                while let Ok(candidate) = receiver.recv().await {
+                    if this.imp().generation.get() == generation {
+                        this.add_candidate(candidate);
+                    } else {
+                        let _ = std::fs::remove_file(&candidate.path);
+                    }
+                }
+                let imp = this.imp();
+                if imp.generation.get() == generation {
+                    imp.searching.set_visible(false);
+                    if imp.shown.borrow().is_empty() {
+                        imp.stack.set_visible_child_name("empty");
+                    }
+                }
+            }
+        ));
+    }
+
+    fn add_candidate(&self, candidate: CoverCandidate) {
+        let imp = self.imp();
+        let picture = gtk::Picture::builder()
+            .paintable(&candidate.texture)
+            .content_fit(gtk::ContentFit::Cover)
+            .width_request(128)
+            .height_request(128)
+            .css_classes(["card"])
+            .overflow(gtk::Overflow::Hidden)
+            .build();
+        let source = gtk::Label::builder()
+            .label(&candidate.source)
+            .ellipsize(gtk::pango::EllipsizeMode::End)
+            .css_classes(["heading"])
+            .build();
+        let resolution = gtk::Label::builder()
+            .label(format!("{} × {}", candidate.width, candidate.height))
+            .css_classes(["caption", "dim-label"])
+            .build();
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .tooltip_text(format!("Use this cover from {}", &candidate.source))
+            .build();
+        content.append(&picture);
+        content.append(&source);
+        content.append(&resolution);
+        imp.candidates.append(&content);
+        imp.shown.borrow_mut().push(candidate);
+        imp.stack.set_visible_child_name("candidates");
+    }
+
+    /// Remove all shown candidates along with their temporary files.
+    fn clear_candidates(&self) {
+        let imp = self.imp();
+        imp.candidates.remove_all();
+        for candidate in imp.shown.borrow_mut().drain(..) {
+            let _ = std::fs::remove_file(&candidate.path);
+        }
+    }
+
+    /// Store the chosen candidate as the album's cover, then close. If a release was
+    /// picked too, the album's metadata is refetched from it.
+    fn pick(&self, idx: i32) {
+        let imp = self.imp();
+        let Some(path) = imp
+            .shown
+            .borrow()
+            .get(idx as usize)
+            .and_then(|candidate| candidate.path.to_str().map(str::to_owned))
+        else {
+            return;
+        };
+        // Stop picking another while this one is being stored.
+        imp.candidates.set_sensitive(false);
+        imp.release_row.set_sensitive(false);
+        let cache = imp.cache.get().unwrap();
+        let album = imp.album.get().unwrap();
+        if let Some(mbid) = (imp.release_row.selected() as usize)
+            .checked_sub(1)
+            .and_then(|idx| imp.release_ids.borrow().get(idx).cloned())
+        {
+            cache.pin_album_release(album, &mbid);
+        }
+        let handle = cache.set_cover(&album.folder_uri, &path);
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                let _ = handle.await;
+                this.close();
+            }
+        ));
+    }
+}
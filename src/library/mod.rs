@@ -15,6 +15,10 @@ mod playlist_content_view;
 mod playlist_view;
 mod playlist_row;
 
+mod radio_view;
+
+mod podcast_view;
+
 mod dynamic_playlist_view;
 mod dynamic_playlist_content_view;
 mod dynamic_playlist_editor_view;
@@ -24,12 +28,17 @@ mod ordering_button;
 // Common stuff shared between views
 mod add_to_playlist;
 mod generic_row;
+mod co_listened_box;
+mod cover_picker;
 
 // The Library controller itself
 mod controller;
 
 pub use recent_view::RecentView;
 
+use co_listened_box::CoListenedBox;
+use cover_picker::CoverPicker;
+
 use album_cell::AlbumCell;
 pub use album_content_view::AlbumContentView;
 pub use album_view::AlbumView;
@@ -47,4 +56,8 @@ pub use dynamic_playlist_editor_view::DynamicPlaylistEditorView;
 pub use playlist_content_view::PlaylistContentView;
 pub use playlist_view::PlaylistView;
 
+pub use radio_view::RadioView;
+
+pub use podcast_view::PodcastView;
+
 pub use controller::Library;
@@ -0,0 +1,519 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::{clone, closure_local, subclass::Signal, Properties, WeakRef};
+use gtk::{glib, CompositeTemplate};
+use rustc_hash::FxHashMap;
+use std::{
+    cell::{Cell, RefCell},
+    sync::OnceLock,
+};
+
+use super::Library;
+use crate::{
+    cache::sqlite,
+    common::{podcast::plain_text, PodcastEpisode, PodcastFeed},
+    player::Player,
+    utils::{format_datetime_local_tz, format_secs_as_duration},
+    window::EuphonicaWindow,
+};
+
+/// Number of latest episodes shown for each feed.
+const EPISODES_SHOWN: u32 = 100;
+/// Episode descriptions are shown as tooltips, so keep them short.
+const DESCRIPTION_MAX_CHARS: usize = 400;
+
+/// Widgets of a feed row that are updated after it has been built.
+struct FeedRow {
+    row: adw::ExpanderRow,
+    feed: PodcastFeed,
+    // Rows added for the feed's episodes, so that they can be replaced later.
+    episodes: Vec<gtk::Widget>,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(CompositeTemplate, Properties, Default)]
+    #[properties(wrapper_type = super::PodcastView)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/library/podcast-view.ui")]
+    pub struct PodcastView {
+        #[template_child]
+        pub show_sidebar: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub add_btn: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub add_popover: TemplateChild<gtk::Popover>,
+        #[template_child]
+        pub feed_url: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub subscribe_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub refresh_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub content_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub feed_list: TemplateChild<gtk::ListBox>,
+
+        pub rows: RefCell<FxHashMap<String, FeedRow>>,
+        pub library: WeakRef<Library>,
+        pub window: WeakRef<EuphonicaWindow>,
+        #[property(get, set)]
+        pub collapsed: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PodcastView {
+        const NAME: &'static str = "EuphonicaPodcastView";
+        type Type = super::PodcastView;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+            klass.set_layout_manager_type::<gtk::BinLayout>();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for PodcastView {
+        fn dispose(&self) {
+            while let Some(child) = self.obj().first_child() {
+                child.unparent();
+            }
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.obj()
+                .bind_property("collapsed", &self.show_sidebar.get(), "visible")
+                .sync_create()
+                .build();
+
+            self.show_sidebar.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.obj().emit_by_name::<()>("show-sidebar-clicked", &[]);
+                }
+            ));
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| vec![Signal::builder("show-sidebar-clicked").build()])
+        }
+    }
+
+    impl WidgetImpl for PodcastView {}
+}
+
+glib::wrapper! {
+    pub struct PodcastView(ObjectSubclass<imp::PodcastView>)
+        @extends gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for PodcastView {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl PodcastView {
+    pub fn setup(&self, library: &Library, player: &Player, window: &EuphonicaWindow) {
+        let imp = self.imp();
+        imp.library.set(Some(library));
+        imp.window.set(Some(window));
+
+        imp.feed_url.connect_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |entry| {
+                let url = entry.text();
+                let url = url.trim();
+                this.imp().subscribe_btn.set_sensitive(
+                    ["http://", "https://", "file://"]
+                        .iter()
+                        .any(|scheme| url.len() > scheme.len() && url.starts_with(scheme)),
+                );
+            }
+        ));
+        imp.subscribe_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.subscribe();
+            }
+        ));
+        imp.add_popover.connect_closed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.imp().feed_url.set_text("");
+            }
+        ));
+        imp.refresh_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |btn| {
+                if let Some(library) = this.imp().library.upgrade() {
+                    let urls: Vec<String> = this.imp().rows.borrow().keys().cloned().collect();
+                    if !urls.is_empty() {
+                        btn.set_sensitive(false);
+                        library.refresh_podcasts(urls);
+                    }
+                }
+            }
+        ));
+
+        library.connect_closure(
+            "podcasts-changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |library: Library| {
+                    this.imp().refresh_btn.set_sensitive(!library.is_refreshing_podcasts());
+                    this.refresh();
+                }
+            ),
+        );
+        player.connect_closure(
+            "podcast-progress-changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: Player, feed_url: String| {
+                    this.update_feed(&feed_url);
+                }
+            ),
+        );
+
+        imp.refresh_btn.set_sensitive(!library.is_refreshing_podcasts());
+        self.refresh();
+    }
+
+    /// Rebuild the feed list from the database, keeping expanded feeds expanded.
+    pub fn refresh(&self) {
+        let imp = self.imp();
+        let expanded: Vec<String> = imp
+            .rows
+            .borrow()
+            .iter()
+            .filter(|(_, feed_row)| feed_row.row.is_expanded())
+            .map(|(url, _)| url.clone())
+            .collect();
+        let list = imp.feed_list.get();
+        list.remove_all();
+        imp.rows.borrow_mut().clear();
+
+        let feeds = sqlite::get_podcast_feeds().unwrap_or_default();
+        imp.content_stack
+            .set_visible_child_name(if feeds.is_empty() { "empty" } else { "list" });
+        for feed in feeds.into_iter() {
+            let row = self.build_row(&feed);
+            list.append(&row);
+            let url = feed.url.clone();
+            imp.rows.borrow_mut().insert(
+                url.clone(),
+                FeedRow {
+                    row: row.clone(),
+                    feed,
+                    episodes: Vec::new(),
+                },
+            );
+            self.update_subtitle(&url);
+            if expanded.contains(&url) {
+                // Loads the episodes via the expanded notify handler
+                row.set_expanded(true);
+            }
+        }
+    }
+
+    fn build_row(&self, feed: &PodcastFeed) -> adw::ExpanderRow {
+        let url = feed.url.clone();
+        let row = adw::ExpanderRow::builder()
+            .title(glib::markup_escape_text(&feed.title))
+            .subtitle_lines(1)
+            .build();
+        row.add_prefix(&adw::Avatar::new(40, Some(&feed.title), true));
+        if let Some(description) = feed.description.as_ref() {
+            row.set_tooltip_text(Some(&truncate(&plain_text(description))));
+        }
+
+        // Feed management, shown above the episodes
+        let manage_row = adw::ActionRow::builder().title("Episodes").build();
+        if let Some(link) = feed.link.as_ref() {
+            manage_row.set_subtitle(&glib::markup_escape_text(link));
+        }
+        let listened_btn = gtk::Button::builder()
+            .icon_name("check-round-outline-symbolic")
+            .tooltip_text("Mark all as listened")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        listened_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            url,
+            move |_| {
+                if let Err(e) = sqlite::set_podcast_feed_listened(&url, true) {
+                    println!("{e:?}");
+                }
+                this.update_feed(&url);
+            }
+        ));
+        let refresh_btn = gtk::Button::builder()
+            .icon_name("view-refresh-symbolic")
+            .tooltip_text("Check for new episodes")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        refresh_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            url,
+            move |btn| {
+                if let Some(library) = this.imp().library.upgrade() {
+                    btn.set_sensitive(false);
+                    this.imp().refresh_btn.set_sensitive(false);
+                    library.refresh_podcasts(vec![url.clone()]);
+                }
+            }
+        ));
+        let delete_btn = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Unsubscribe")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat", "error"])
+            .build();
+        delete_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            url,
+            move |_| {
+                if let Err(e) = sqlite::delete_podcast_feed(&url) {
+                    println!("{e:?}");
+                }
+                this.refresh();
+            }
+        ));
+        manage_row.add_suffix(&listened_btn);
+        manage_row.add_suffix(&refresh_btn);
+        manage_row.add_suffix(&delete_btn);
+        row.add_row(&manage_row);
+
+        // Only read the episodes when they're actually shown
+        row.connect_expanded_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            url,
+            move |row| {
+                if row.is_expanded() {
+                    this.load_episodes(&url);
+                }
+            }
+        ));
+
+        row
+    }
+
+    /// Show a feed's unlistened episode count, or why it couldn't be refreshed.
+    fn update_subtitle(&self, url: &str) {
+        let rows = self.imp().rows.borrow();
+        let Some(feed_row) = rows.get(url) else {
+            return;
+        };
+        let feed = &feed_row.feed;
+        let subtitle = if let Some(error) = feed.last_error.as_ref() {
+            format!("Refresh failed: {error}")
+        } else if feed.last_checked.is_none() {
+            "Not downloaded yet".to_owned()
+        } else {
+            match sqlite::count_unlistened_podcast_episodes(url).unwrap_or_default() {
+                0 => "No new episodes".to_owned(),
+                1 => "1 new episode".to_owned(),
+                n => format!("{n} new episodes"),
+            }
+        };
+        feed_row.row.set_subtitle(&glib::markup_escape_text(&subtitle));
+    }
+
+    /// Update a feed after the progress of its episodes changed.
+    fn update_feed(&self, url: &str) {
+        self.update_subtitle(url);
+        let expanded = self
+            .imp()
+            .rows
+            .borrow()
+            .get(url)
+            .is_some_and(|feed_row| feed_row.row.is_expanded());
+        if expanded {
+            self.load_episodes(url);
+        }
+    }
+
+    fn load_episodes(&self, url: &str) {
+        let mut rows = self.imp().rows.borrow_mut();
+        let Some(feed_row) = rows.get_mut(url) else {
+            return;
+        };
+        for widget in feed_row.episodes.drain(..) {
+            feed_row.row.remove(&widget);
+        }
+        let episodes = sqlite::get_podcast_episodes(url, EPISODES_SHOWN).unwrap_or_default();
+        if episodes.is_empty() {
+            let row = adw::ActionRow::builder()
+                .title("No episodes")
+                .css_classes(["dim-label"])
+                .build();
+            feed_row.row.add_row(&row);
+            feed_row.episodes.push(row.upcast());
+        }
+        for episode in episodes.into_iter() {
+            let row = self.build_episode_row(episode);
+            feed_row.row.add_row(&row);
+            feed_row.episodes.push(row.upcast());
+        }
+    }
+
+    fn build_episode_row(&self, episode: PodcastEpisode) -> adw::ActionRow {
+        let mut details: Vec<String> = Vec::with_capacity(2);
+        if let Some(published) = episode.published {
+            details.push(format_datetime_local_tz(published));
+        }
+        if episode.listened {
+            details.push("Listened".to_owned());
+        } else if episode.position > 0.0 {
+            details.push(if let Some(dur) = episode.duration {
+                format!("{} left", format_secs_as_duration((dur - episode.position).max(0.0)))
+            } else {
+                format!("Stopped at {}", format_secs_as_duration(episode.position))
+            });
+        } else if let Some(dur) = episode.duration {
+            details.push(format_secs_as_duration(dur));
+        }
+        let row = adw::ActionRow::builder()
+            .title(glib::markup_escape_text(&episode.title))
+            .subtitle(glib::markup_escape_text(&details.join(" · ")))
+            .build();
+        if episode.listened {
+            row.add_css_class("dim-label");
+        }
+        if let Some(description) = episode.description.as_ref() {
+            row.set_tooltip_text(Some(&truncate(&plain_text(description))));
+        }
+
+        let play_btn = gtk::Button::builder()
+            .icon_name("media-playback-start-symbolic")
+            .tooltip_text(if episode.position > 0.0 && !episode.listened { "Resume" } else { "Play" })
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        play_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            episode,
+            move |_| {
+                if let Some(library) = this.imp().library.upgrade() {
+                    library.play_podcast_episode(&episode);
+                }
+            }
+        ));
+        let append_btn = gtk::Button::builder()
+            .icon_name("list-add-symbolic")
+            .tooltip_text("Append to queue")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        append_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            episode,
+            move |_| {
+                if let Some(library) = this.imp().library.upgrade() {
+                    library.queue_podcast_episode(&episode);
+                }
+            }
+        ));
+        let listened_btn = gtk::Button::builder()
+            .icon_name(if episode.listened { "edit-undo-symbolic" } else { "check-round-outline-symbolic" })
+            .tooltip_text(if episode.listened { "Mark as not listened" } else { "Mark as listened" })
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        listened_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            episode,
+            move |_| {
+                if let Err(e) = sqlite::set_podcast_episode_progress(
+                    &episode.feed_url,
+                    &episode.guid,
+                    0.0,
+                    !episode.listened,
+                ) {
+                    println!("{e:?}");
+                }
+                // Rebuilds this row, so don't hold on to anything from it
+                let feed_url = episode.feed_url.clone();
+                glib::idle_add_local_once(clone!(
+                    #[weak]
+                    this,
+                    move || {
+                        this.update_feed(&feed_url);
+                    }
+                ));
+            }
+        ));
+        row.add_suffix(&play_btn);
+        row.add_suffix(&append_btn);
+        row.add_suffix(&listened_btn);
+        row
+    }
+
+    fn subscribe(&self) {
+        let imp = self.imp();
+        let Some(library) = imp.library.upgrade() else {
+            return;
+        };
+        let url = imp.feed_url.text().trim().to_owned();
+        match library.subscribe_podcast(&url) {
+            Ok(()) => {
+                imp.add_popover.popdown();
+                imp.refresh_btn.set_sensitive(false);
+                self.refresh();
+            }
+            Err(sqlite::Error::KeyAlreadyExists) => {
+                if let Some(window) = imp.window.upgrade() {
+                    window.send_simple_toast("Already subscribed to this feed", 3);
+                }
+            }
+            Err(e) => {
+                println!("{e:?}");
+            }
+        }
+    }
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() > DESCRIPTION_MAX_CHARS {
+        let mut res: String = text.chars().take(DESCRIPTION_MAX_CHARS).collect();
+        res.push('…');
+        res
+    } else {
+        text.to_owned()
+    }
+}
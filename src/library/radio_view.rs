@@ -0,0 +1,673 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use ashpd::desktop::file_chooser::SelectedFiles;
+use glib::{clone, closure_local, subclass::Signal, BoxedAnyObject, Properties, WeakRef};
+use gtk::{glib, CompositeTemplate};
+use rustc_hash::FxHashMap;
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    path::Path,
+    rc::Rc,
+    sync::OnceLock,
+};
+
+use super::Library;
+use crate::{
+    cache::{sqlite, Cache},
+    client::ClientState,
+    common::{radio_station::parse_station_list, RadioStation, Song},
+    player::Player,
+    utils::tokio_runtime,
+    window::EuphonicaWindow,
+};
+
+/// Number of past stream titles shown for each station.
+const HISTORY_LEN: u32 = 50;
+
+/// Widgets of a station row that are updated after it has been built.
+struct StationRow {
+    row: adw::ExpanderRow,
+    avatar: adw::Avatar,
+    // Rows added for the station's title history, so that they can be replaced later.
+    history: Vec<gtk::Widget>,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(CompositeTemplate, Properties, Default)]
+    #[properties(wrapper_type = super::RadioView)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/library/radio-view.ui")]
+    pub struct RadioView {
+        #[template_child]
+        pub show_sidebar: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub add_btn: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub edit_popover: TemplateChild<gtk::Popover>,
+        #[template_child]
+        pub station_name: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub station_url: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub station_genre: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub station_save: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub import_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub content_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub station_list: TemplateChild<gtk::ListBox>,
+
+        pub rows: RefCell<FxHashMap<String, StationRow>>,
+        // Library search currently shown in the results dialog, if any.
+        pub search: RefCell<Option<(String, WeakRef<gtk::ListBox>)>>,
+        pub library: WeakRef<Library>,
+        pub cache: OnceCell<Rc<Cache>>,
+        pub window: WeakRef<EuphonicaWindow>,
+        #[property(get, set)]
+        pub collapsed: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for RadioView {
+        const NAME: &'static str = "EuphonicaRadioView";
+        type Type = super::RadioView;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+            klass.set_layout_manager_type::<gtk::BinLayout>();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for RadioView {
+        fn dispose(&self) {
+            while let Some(child) = self.obj().first_child() {
+                child.unparent();
+            }
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.obj()
+                .bind_property("collapsed", &self.show_sidebar.get(), "visible")
+                .sync_create()
+                .build();
+
+            self.show_sidebar.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.obj().emit_by_name::<()>("show-sidebar-clicked", &[]);
+                }
+            ));
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| vec![Signal::builder("show-sidebar-clicked").build()])
+        }
+    }
+
+    impl WidgetImpl for RadioView {}
+}
+
+glib::wrapper! {
+    pub struct RadioView(ObjectSubclass<imp::RadioView>)
+        @extends gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for RadioView {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl RadioView {
+    pub fn setup(
+        &self,
+        library: &Library,
+        cache: Rc<Cache>,
+        player: &Player,
+        client_state: &ClientState,
+        window: &EuphonicaWindow,
+    ) {
+        let imp = self.imp();
+        imp.library.set(Some(library));
+        let _ = imp.cache.set(cache);
+        imp.window.set(Some(window));
+
+        // Station editor. Both a name and a URL are required.
+        for entry in [imp.station_name.get(), imp.station_url.get()] {
+            entry.connect_changed(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    let imp = this.imp();
+                    imp.station_save.set_sensitive(
+                        !imp.station_name.text().trim().is_empty()
+                            && !imp.station_url.text().trim().is_empty(),
+                    );
+                }
+            ));
+        }
+        imp.station_save.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.save_station();
+            }
+        ));
+        imp.edit_popover.connect_closed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                // Back to adding a new station
+                let imp = this.imp();
+                imp.station_name.set_editable(true);
+                imp.station_name.set_text("");
+                imp.station_url.set_text("");
+                imp.station_genre.set_text("");
+            }
+        ));
+        imp.import_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.open_import_dialog();
+            }
+        ));
+
+        player.connect_closure(
+            "radio-history-changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: Player, name: String| {
+                    let expanded = this
+                        .imp()
+                        .rows
+                        .borrow()
+                        .get(&name)
+                        .is_some_and(|station_row| station_row.row.is_expanded());
+                    if expanded {
+                        this.load_history(&name);
+                    }
+                }
+            ),
+        );
+        client_state.connect_closure(
+            "library-search-results",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, term: String, songs: BoxedAnyObject| {
+                    this.on_search_results(&term, &songs.borrow::<Vec<Song>>());
+                }
+            ),
+        );
+
+        self.refresh();
+    }
+
+    /// Rebuild the station list from the database.
+    pub fn refresh(&self) {
+        let imp = self.imp();
+        let list = imp.station_list.get();
+        list.remove_all();
+        imp.rows.borrow_mut().clear();
+
+        let stations = sqlite::get_radio_stations().unwrap_or_default();
+        imp.content_stack
+            .set_visible_child_name(if stations.is_empty() { "empty" } else { "list" });
+        for station in stations.into_iter() {
+            let station_row = self.build_row(&station);
+            list.append(&station_row.row);
+            self.load_logo(&station.name, &station_row.avatar);
+            imp.rows.borrow_mut().insert(station.name, station_row);
+        }
+    }
+
+    fn build_row(&self, station: &RadioStation) -> StationRow {
+        let name = station.name.clone();
+        let subtitle = if let Some(genre) = station.genre.as_ref() {
+            format!("{genre} · {}", &station.url)
+        } else {
+            station.url.clone()
+        };
+        let row = adw::ExpanderRow::builder()
+            .title(glib::markup_escape_text(&station.name))
+            .subtitle(glib::markup_escape_text(&subtitle))
+            .subtitle_lines(1)
+            .build();
+        let avatar = adw::Avatar::new(40, Some(&station.name), true);
+        row.add_prefix(&avatar);
+
+        let play_btn = gtk::Button::builder()
+            .icon_name("media-playback-start-symbolic")
+            .tooltip_text("Play")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        play_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            station,
+            move |_| {
+                if let Some(library) = this.imp().library.upgrade() {
+                    library.play_station(&station);
+                }
+            }
+        ));
+        row.add_suffix(&play_btn);
+
+        // Station management, shown above the title history
+        let manage_row = adw::ActionRow::builder()
+            .title("Recently played titles")
+            .build();
+        let edit_btn = gtk::Button::builder()
+            .icon_name("document-edit-symbolic")
+            .tooltip_text("Edit station")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        edit_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            station,
+            move |_| {
+                let imp = this.imp();
+                imp.station_name.set_text(&station.name);
+                imp.station_name.set_editable(false);
+                imp.station_url.set_text(&station.url);
+                imp.station_genre.set_text(station.genre.as_deref().unwrap_or_default());
+                imp.add_btn.popup();
+            }
+        ));
+        let logo_btn = gtk::Button::builder()
+            .icon_name("image-x-generic-symbolic")
+            .tooltip_text("Set logo")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        logo_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            name,
+            move |_| {
+                this.open_logo_dialog(&name);
+            }
+        ));
+        let clear_btn = gtk::Button::builder()
+            .icon_name("edit-clear-all-symbolic")
+            .tooltip_text("Clear history")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        clear_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            name,
+            move |_| {
+                if let Err(e) = sqlite::clear_radio_titles(&name) {
+                    println!("{e:?}");
+                }
+                this.load_history(&name);
+            }
+        ));
+        let delete_btn = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Delete station")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat", "error"])
+            .build();
+        delete_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            name,
+            move |_| {
+                if let Err(e) = sqlite::delete_radio_station(&name) {
+                    println!("{e:?}");
+                }
+                if let Some(library) = this.imp().library.upgrade() {
+                    library.clear_station_logo(&name);
+                }
+                this.refresh();
+            }
+        ));
+        manage_row.add_suffix(&edit_btn);
+        manage_row.add_suffix(&logo_btn);
+        manage_row.add_suffix(&clear_btn);
+        manage_row.add_suffix(&delete_btn);
+        row.add_row(&manage_row);
+
+        // Only read the history when it's actually shown
+        row.connect_expanded_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            name,
+            move |row| {
+                if row.is_expanded() {
+                    this.load_history(&name);
+                }
+            }
+        ));
+
+        StationRow {
+            row,
+            avatar,
+            history: Vec::new(),
+        }
+    }
+
+    fn load_logo(&self, name: &str, avatar: &adw::Avatar) {
+        let Some(cache) = self.imp().cache.get() else {
+            return;
+        };
+        let handle = cache.load_cached_station_logo(name, true);
+        glib::spawn_future_local(clone!(
+            #[weak]
+            avatar,
+            async move {
+                if let Ok(Some(tex)) = handle.await {
+                    avatar.set_custom_image(Some(&tex));
+                }
+            }
+        ));
+    }
+
+    fn load_history(&self, name: &str) {
+        let mut rows = self.imp().rows.borrow_mut();
+        let Some(station_row) = rows.get_mut(name) else {
+            return;
+        };
+        for widget in station_row.history.drain(..) {
+            station_row.row.remove(&widget);
+        }
+        let titles = sqlite::get_radio_titles(name, HISTORY_LEN).unwrap_or_default();
+        if titles.is_empty() {
+            let row = adw::ActionRow::builder()
+                .title("No titles heard yet")
+                .css_classes(["dim-label"])
+                .build();
+            station_row.row.add_row(&row);
+            station_row.history.push(row.upcast());
+        }
+        for (title, time) in titles.into_iter() {
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(&title))
+                .subtitle(time)
+                .build();
+            let search_btn = gtk::Button::builder()
+                .icon_name("edit-find-symbolic")
+                .tooltip_text("Find in library")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            search_btn.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.search_title(&title);
+                }
+            ));
+            row.add_suffix(&search_btn);
+            station_row.row.add_row(&row);
+            station_row.history.push(row.upcast());
+        }
+    }
+
+    fn save_station(&self) {
+        let imp = self.imp();
+        let genre = imp.station_genre.text().trim().to_owned();
+        let station = RadioStation {
+            name: imp.station_name.text().trim().to_owned(),
+            url: imp.station_url.text().trim().to_owned(),
+            genre: if genre.is_empty() { None } else { Some(genre) },
+        };
+        // The name can only be edited when adding a new station.
+        if imp.station_name.is_editable() {
+            match sqlite::add_radio_station(&station) {
+                Ok(true) => {}
+                Ok(false) => {
+                    if let Some(window) = imp.window.upgrade() {
+                        window.send_simple_toast(
+                            &format!("A station named \"{}\" already exists", &station.name),
+                            3,
+                        );
+                    }
+                    return;
+                }
+                Err(e) => {
+                    println!("{e:?}");
+                }
+            }
+        } else if let Err(e) = sqlite::write_radio_station(&station) {
+            println!("{e:?}");
+        }
+        imp.edit_popover.popdown();
+        self.refresh();
+    }
+
+    /// Show the library songs matching a stream title in a dialog.
+    fn search_title(&self, title: &str) {
+        let Some(library) = self.imp().library.upgrade() else {
+            return;
+        };
+        let results = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .valign(gtk::Align::Start)
+            .css_classes(["boxed-list"])
+            .build();
+        results.append(&adw::ActionRow::builder().title("Searching…").build());
+        let content = adw::ToolbarView::new();
+        content.add_top_bar(&adw::HeaderBar::new());
+        content.set_content(Some(
+            &gtk::ScrolledWindow::builder()
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .propagate_natural_height(true)
+                .child(
+                    &adw::Clamp::builder()
+                        .margin_top(12)
+                        .margin_bottom(12)
+                        .margin_start(12)
+                        .margin_end(12)
+                        .child(&results)
+                        .build(),
+                )
+                .build(),
+        ));
+        let dialog = adw::Dialog::builder()
+            .title(title)
+            .content_width(480)
+            .content_height(480)
+            .child(&content)
+            .build();
+        self.imp()
+            .search
+            .replace(Some((title.to_owned(), results.downgrade())));
+        dialog.present(self.imp().window.upgrade().as_ref());
+        library.search_library(title);
+    }
+
+    fn on_search_results(&self, term: &str, songs: &[Song]) {
+        let results = match self.imp().search.borrow().as_ref() {
+            Some((searching, results)) if searching == term => results.upgrade(),
+            _ => None,
+        };
+        let Some(results) = results else {
+            return;
+        };
+        results.remove_all();
+        if songs.is_empty() {
+            results.append(
+                &adw::ActionRow::builder()
+                    .title("No matching songs in your library")
+                    .build(),
+            );
+            return;
+        }
+        for song in songs.iter() {
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(song.get_name()))
+                .subtitle(glib::markup_escape_text(&song.get_artist_str().unwrap_or_default()))
+                .build();
+            let next_btn = gtk::Button::builder()
+                .icon_name("music-queue-symbolic")
+                .tooltip_text("Play next")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            next_btn.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[strong]
+                song,
+                move |_| {
+                    if let Some(library) = this.imp().library.upgrade() {
+                        library.insert_songs_next(&[song.clone()]);
+                    }
+                }
+            ));
+            let append_btn = gtk::Button::builder()
+                .icon_name("list-add-symbolic")
+                .tooltip_text("Append to queue")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            append_btn.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[strong]
+                song,
+                move |_| {
+                    if let Some(library) = this.imp().library.upgrade() {
+                        library.queue_songs(&[song.clone()], false, false);
+                    }
+                }
+            ));
+            row.add_suffix(&next_btn);
+            row.add_suffix(&append_btn);
+            results.append(&row);
+        }
+    }
+
+    fn pick_file(&self, title: &'static str, on_picked: impl Fn(&Self, String) + 'static) {
+        let (sender, receiver) = async_channel::unbounded::<String>();
+        tokio_runtime().spawn(async move {
+            match SelectedFiles::open_file()
+                .title(title)
+                .modal(true)
+                .multiple(false)
+                .send()
+                .await
+                .expect("ashpd file open await failure")
+                .response()
+            {
+                Ok(files) => {
+                    let uris = files.uris();
+                    if !uris.is_empty() {
+                        let _ = sender.send_blocking(uris[0].to_string());
+                    }
+                }
+                Err(e) => {
+                    dbg!(e);
+                }
+            }
+        });
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                if let Ok(uri) = receiver.recv().await {
+                    on_picked(&this, uri);
+                }
+            }
+        ));
+    }
+
+    fn open_logo_dialog(&self, name: &str) {
+        let name = name.to_owned();
+        self.pick_file("Select a station logo", move |this, uri| {
+            let Some(library) = this.imp().library.upgrade() else {
+                return;
+            };
+            let handle = library.set_station_logo(&name, &uri);
+            let avatar = this.imp().rows.borrow().get(&name).map(|row| row.avatar.clone());
+            if let Some(avatar) = avatar {
+                let name = name.clone();
+                glib::spawn_future_local(clone!(
+                    #[weak]
+                    this,
+                    async move {
+                        let _ = handle.await;
+                        this.load_logo(&name, &avatar);
+                    }
+                ));
+            }
+        });
+    }
+
+    fn open_import_dialog(&self) {
+        self.pick_file("Import radio stations", |this, uri| {
+            // Assume ashpd always return filesystem spec
+            let path = urlencoding::decode(uri.strip_prefix("file://").unwrap_or(&uri))
+                .expect("Path must be in UTF-8")
+                .into_owned();
+            let fallback_name = Path::new(&path)
+                .file_stem()
+                .map_or(String::from("Imported station"), |stem| {
+                    stem.to_string_lossy().into_owned()
+                });
+            let stations = match std::fs::read_to_string(&path) {
+                Ok(contents) => parse_station_list(&contents, &fallback_name),
+                Err(e) => {
+                    println!("Unable to read station list: {e:?}");
+                    Vec::new()
+                }
+            };
+            let mut n_imported = 0;
+            let mut n_taken = 0;
+            for station in stations.iter() {
+                match sqlite::add_radio_station(station) {
+                    Ok(true) => n_imported += 1,
+                    Ok(false) => n_taken += 1,
+                    Err(e) => println!("{e:?}"),
+                }
+            }
+            if let Some(window) = this.imp().window.upgrade() {
+                if n_taken > 0 {
+                    // Don't silently replace stations the user already has.
+                    window.send_simple_toast(
+                        &format!(
+                            "Imported {n_imported} station(s), skipped {n_taken} whose names are already taken by other stations"
+                        ),
+                        5,
+                    );
+                } else if n_imported > 0 {
+                    window.send_simple_toast(&format!("Imported {n_imported} station(s)"), 3);
+                } else {
+                    window.send_simple_toast("No stations found in this file", 3);
+                }
+            }
+            this.refresh();
+        });
+    }
+}
@@ -1,18 +1,11 @@
 extern crate bson;
-use gtk::{prelude::*, gdk};
-use std::{thread, time::Duration};
+use gtk::{gdk, glib};
+use std::cmp::Reverse;
 use reqwest::blocking::Client;
-use crate::{common::{AlbumInfo, ArtistInfo, SongInfo}, utils::settings_manager};
+use crate::common::{AlbumInfo, ArtistInfo, SongInfo};
 
 use super::models;
 
-pub fn sleep_after_request() {
-    let settings = settings_manager().child("metaprovider");
-    thread::sleep(Duration::from_millis(
-        (settings.double("delay-between-requests-s") * 1000.0) as u64,
-    ));
-}
-
 /// Enum for communication with provider threads from the cache controller living on the main thread.
 /// Can be used for both request and response.
 pub enum ProviderMessage {
@@ -44,7 +37,19 @@ pub mod utils {
     use reqwest::header::USER_AGENT;
 
     /// Get a file from the given URL as bytes. Useful for downloading images.
+    /// Local files (such as those found by the local provider) can be given as file:// URIs.
     fn get_file(url: &str) -> Option<Vec<u8>> {
+        if url.starts_with("file://") {
+            return match glib::filename_from_uri(url) {
+                Ok((path, _)) => std::fs::read(path)
+                    .map_err(|e| println!("get_file: {e:?}"))
+                    .ok(),
+                Err(e) => {
+                    println!("get_file: {e:?}");
+                    None
+                }
+            };
+        }
         let client = Client::default();
         // This empty check comes in handy for certain metadata providers who, instead of
         // skipping the URL fields, opt to return an empty string instead.
@@ -75,6 +80,13 @@ pub mod utils {
         }
     }
 
+    /// Download and decode a single image.
+    pub fn download_image(url: &str) -> Option<DynamicImage> {
+        let bytes = get_file(url)?;
+        println!("Downloaded image from: {url:?}");
+        utils::read_image_from_bytes(bytes)
+    }
+
     pub fn get_best_image(metas: &[models::ImageMeta]) -> Result<DynamicImage, String> {
         // Get all image URLs, sorted by size in reverse.
         // Avoid cloning by sorting a mutable vector of references.
@@ -84,13 +96,12 @@ pub mod utils {
                 "This album's metadata does not provide any image.",
             ));
         }
-        images.sort_by_key(|img| img.size);
-        for image_meta in images.iter().rev() {
-            if let Some(bytes) = get_file(image_meta.url.as_ref()) {
-                println!("Downloaded image from: {:?}", &image_meta.url);
-                if let Some(image) = utils::read_image_from_bytes(bytes) {
-                    return Ok(image);
-                }
+        // Stable sort, so among images of the same size, those from earlier providers
+        // in the chain are tried first.
+        images.sort_by_key(|img| Reverse(img.size));
+        for image_meta in images.iter() {
+            if let Some(image) = download_image(image_meta.url.as_ref()) {
+                return Ok(image);
             }
         }
         Err(String::from(
@@ -1,6 +1,35 @@
-use crate::common::{AlbumInfo, ArtistInfo, SongInfo};
+use gio::prelude::SettingsExt;
 
-use super::{lastfm::LastfmWrapper, lrclib::LrcLibWrapper, models, musicbrainz::MusicBrainzWrapper, MetadataProvider};
+use crate::{common::{AlbumInfo, ArtistInfo, SongInfo}, utils::{meta_provider_settings, settings_manager}};
+
+use super::{embedded::EmbeddedWrapper, lastfm::LastfmWrapper, local::LocalWrapper, lrclib::LrcLibWrapper, models, musicbrainz::MusicBrainzWrapper, plugin::{plugin_keys, plugin_settings, PluginWrapper, KEY_PREFIX}, wikipedia::WikipediaWrapper, MetadataProvider};
+
+/// All built-in providers by key, in their default order.
+const BUILTIN_PROVIDERS: [(&str, fn() -> Box<dyn MetadataProvider>); 6] = [
+    ("embedded", boxed::<EmbeddedWrapper>),
+    ("local", boxed::<LocalWrapper>),
+    ("musicbrainz", boxed::<MusicBrainzWrapper>),
+    ("wikipedia", boxed::<WikipediaWrapper>),
+    ("lastfm", boxed::<LastfmWrapper>),
+    ("lrclib", boxed::<LrcLibWrapper>),
+];
+
+fn boxed<P: MetadataProvider + 'static>() -> Box<dyn MetadataProvider> {
+    Box::new(P::new())
+}
+
+fn is_builtin(key: &str) -> bool {
+    BUILTIN_PROVIDERS.iter().any(|(builtin_key, _)| *builtin_key == key)
+}
+
+/// Settings of any provider, built-in or plugin.
+pub fn provider_settings(key: &str) -> gio::Settings {
+    if let Some(id) = key.strip_prefix(KEY_PREFIX) {
+        plugin_settings(id)
+    } else {
+        meta_provider_settings(key)
+    }
+}
 
 /// A meta-MetadataProvider that works by daisy-chaining actual MetadataProviders.
 /// Think composite pattern.
@@ -9,6 +38,31 @@ use super::{lastfm::LastfmWrapper, lrclib::LrcLibWrapper, models, musicbrainz::M
 /// more accurately.
 pub struct MetadataChain {
     pub providers: Vec<Box<dyn MetadataProvider>>,
+    /// Keys of the above providers, in the same order
+    pub keys: Vec<String>,
+}
+
+impl MetadataChain {
+    /// Run the album through the chain as usual, but keep track of which provider each image
+    /// came from instead of merging them all together. Returns pairs of (provider name, image).
+    pub fn get_album_image_candidates(&self, key: &mut AlbumInfo) -> Vec<(String, models::ImageMeta)> {
+        let mut res: Vec<(String, models::ImageMeta)> = Vec::new();
+        let mut existing: Option<models::AlbumMeta> = None;
+        for (provider, provider_key) in self.providers.iter().zip(self.keys.iter()) {
+            let n_before = existing.as_ref().map_or(0, |meta| meta.image.len());
+            existing = provider.get_album_meta(key, existing);
+            if let Some(meta) = &existing {
+                if let Some(new_images) = meta.image.get(n_before..) {
+                    let name = provider_settings(provider_key).string("name").to_string();
+                    res.extend(new_images.iter().map(|image| (name.clone(), image.clone())));
+                }
+                if let (Some(id), None) = (&meta.mbid, &key.mbid) {
+                    key.mbid = Some(id.to_owned());
+                }
+            }
+        }
+        res
+    }
 }
 
 impl MetadataProvider for MetadataChain {
@@ -19,6 +73,7 @@ impl MetadataProvider for MetadataChain {
     {
         Self {
             providers: Vec::new(),
+            keys: Vec::new(),
         }
     }
 
@@ -70,12 +125,44 @@ impl MetadataProvider for MetadataChain {
 }
 
 /// Convenience method to construct a metadata provider instance by key with the given priority.
-/// When implementing a new provider, you must manually add it to this function too.
+/// When implementing a new provider, you must add it to BUILTIN_PROVIDERS too.
 pub fn get_provider(key: &str) -> Box<dyn MetadataProvider> {
-    match key {
-        "musicbrainz" => Box::new(MusicBrainzWrapper::new()),
-        "lastfm" => Box::new(LastfmWrapper::new()),
-        "lrclib" => Box::new(LrcLibWrapper::new()),
-        _ => unimplemented!(),
+    if let Some((_, constructor)) = BUILTIN_PROVIDERS.iter().find(|(builtin_key, _)| *builtin_key == key) {
+        constructor()
+    } else if key.starts_with(KEY_PREFIX) {
+        Box::new(PluginWrapper::with_key(key))
+    } else {
+        unimplemented!()
+    }
+}
+
+/// The configured provider order. Providers added since the order was last changed are
+/// missing from it, so they're inserted right after the provider preceding them in the
+/// default order (some depend on IDs found by earlier ones). New plugins go last.
+/// Unknown keys, including those of removed plugins, are skipped.
+pub fn provider_order() -> Vec<String> {
+    let plugins = plugin_keys();
+    let mut order: Vec<String> = settings_manager()
+        .child("metaprovider")
+        .strv("order")
+        .iter()
+        .map(|key| key.to_string())
+        .filter(|key| is_builtin(key) || plugins.contains(key))
+        .collect();
+    for (idx, (key, _)) in BUILTIN_PROVIDERS.iter().enumerate() {
+        if order.iter().any(|saved_key| saved_key == key) {
+            continue;
+        }
+        let pos = idx
+            .checked_sub(1)
+            .and_then(|prev| order.iter().position(|saved_key| saved_key == BUILTIN_PROVIDERS[prev].0))
+            .map_or(0, |prev_pos| prev_pos + 1);
+        order.insert(pos, key.to_string());
+    }
+    for key in plugins {
+        if !order.contains(&key) {
+            order.push(key);
+        }
     }
+    order
 }
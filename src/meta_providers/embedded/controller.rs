@@ -0,0 +1,115 @@
+use std::sync::Mutex;
+
+use gio::prelude::SettingsExt;
+use mpd::{error::Error as MpdError, Client};
+
+use crate::{
+    client::{connect_main_server, password::get_mpd_password_blocking, StreamWrapper},
+    common::{is_stream_uri, AlbumInfo, ArtistInfo, SongInfo},
+    utils::meta_provider_settings,
+};
+
+use super::{
+    super::{models, MetadataProvider},
+    PROVIDER_KEY,
+};
+
+/// Tags that may contain lyrics, in order of preference. Synced lyrics are preferred
+/// regardless of which tag they're found in, as many taggers put LRC into the plain ones.
+const LYRICS_TAGS: [&str; 4] = ["syncedlyrics", "lyrics", "unsyncedlyrics", "unsynced lyrics"];
+
+/// Reads lyrics embedded in the song files themselves through MPD's readcomments command,
+/// so it works fully offline. Only formats whose tags MPD exposes in full are supported,
+/// such as Vorbis comments in FLAC and Opus files.
+pub struct EmbeddedWrapper {
+    // Providers are called from worker threads, so we keep a connection of our own
+    // instead of going through the background client. Opened on first use.
+    client: Mutex<Option<Client<StreamWrapper>>>,
+}
+
+impl EmbeddedWrapper {
+    fn read_comments(&self, uri: &str) -> Option<Vec<(String, String)>> {
+        let mut client = self.client.lock().unwrap();
+        // Retry once, as MPD closes connections that have been idle for too long.
+        for _ in 0..2 {
+            if client.is_none() {
+                let password = get_mpd_password_blocking().ok().flatten();
+                match connect_main_server(password.as_deref()) {
+                    Ok(new_client) => {
+                        client.replace(new_client);
+                    }
+                    Err(state) => {
+                        println!("[Embedded] Unable to connect to MPD: {state:?}");
+                        return None;
+                    }
+                }
+            }
+            let res: Result<Vec<(String, String)>, MpdError> = client
+                .as_mut()
+                .unwrap()
+                .readcomments(uri)
+                .map(|pairs| pairs.flatten().collect());
+            match res {
+                Ok(comments) => {
+                    return Some(comments);
+                }
+                Err(MpdError::Io(_)) => {
+                    client.take();
+                }
+                Err(e) => {
+                    println!("[Embedded] Unable to read tags of {uri}: {e:?}");
+                    return None;
+                }
+            }
+        }
+        None
+    }
+}
+
+impl MetadataProvider for EmbeddedWrapper {
+    fn new() -> Self {
+        Self {
+            client: Mutex::new(None),
+        }
+    }
+
+    /// Album metadata is already available as song tags.
+    fn get_album_meta(
+        &self,
+        _key: &mut AlbumInfo,
+        existing: Option<models::AlbumMeta>,
+    ) -> Option<models::AlbumMeta> {
+        existing
+    }
+
+    /// Artist metadata is already available as song tags.
+    fn get_artist_meta(
+        &self,
+        _key: &mut ArtistInfo,
+        existing: Option<models::ArtistMeta>,
+    ) -> Option<models::ArtistMeta> {
+        existing
+    }
+
+    fn get_lyrics(&self, key: &SongInfo) -> Option<models::Lyrics> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") || is_stream_uri(&key.uri) {
+            return None;
+        }
+        let comments = self.read_comments(&key.uri)?;
+        let mut plain: Option<models::Lyrics> = None;
+        for tag in LYRICS_TAGS {
+            for (_, val) in comments.iter().filter(|(name, _)| name.eq_ignore_ascii_case(tag)) {
+                match models::Lyrics::try_from_lrc_or_plain_str(val) {
+                    Some(lyrics) if lyrics.synced => {
+                        return Some(lyrics);
+                    }
+                    Some(lyrics) => {
+                        plain.get_or_insert(lyrics);
+                    }
+                    None => {}
+                }
+            }
+        }
+        plain
+    }
+}
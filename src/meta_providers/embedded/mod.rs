@@ -0,0 +1,5 @@
+static PROVIDER_KEY: &str = "embedded";
+
+mod controller;
+
+pub use controller::EmbeddedWrapper;
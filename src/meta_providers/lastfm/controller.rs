@@ -27,17 +27,18 @@ impl LastfmWrapper {
         // Return None if there is no API key specified.
         if !key.is_empty() {
             println!("[Last.fm] Calling `{method}` with query {params:?}");
-            let resp = self
-                .client
-                .get(API_ROOT)
-                .query(&[
-                    ("format", "json"),
-                    ("method", method),
-                    ("api_key", key.as_ref()),
-                ])
-                .query(params)
-                .header(USER_AGENT, APPLICATION_USER_AGENT)
-                .send();
+            let resp = send_with_retry(
+                PROVIDER_KEY,
+                self.client
+                    .get(API_ROOT)
+                    .query(&[
+                        ("format", "json"),
+                        ("method", method),
+                        ("api_key", key.as_ref()),
+                    ])
+                    .query(params)
+                    .header(USER_AGENT, APPLICATION_USER_AGENT),
+            );
             match resp {
                 Ok(res) => {
                     return Some(res);
@@ -145,6 +145,8 @@ impl From<LastfmAlbum> for AlbumMeta {
             name: lfm.name,
             artist: Some(lfm.artist),
             mbid: lfm.mbid,
+            release_group_mbid: None,
+            pinned_mbid: None,
             tags,
             image,
             url: Some(lfm.url),
@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+
+use gio::prelude::SettingsExt;
+use gtk::glib;
+
+use crate::{
+    common::{is_stream_uri, AlbumInfo, ArtistInfo, SongInfo},
+    utils::meta_provider_settings,
+};
+
+use super::{
+    super::{models, prelude::*},
+    models::{read_album_nfo, read_artist_nfo},
+    PROVIDER_KEY,
+};
+
+const IMAGE_EXTS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+/// Front cover file names, in order of preference. MPD's albumart command only looks
+/// for cover.*, so the others would otherwise never be found.
+const ALBUM_COVER_NAMES: [&str; 4] = ["cover", "folder", "front", "album"];
+/// Back covers are only used when no other provider has anything better.
+const ALBUM_BACK_NAMES: [&str; 1] = ["back"];
+const ARTIST_IMAGE_NAMES: [&str; 2] = ["artist", "folder"];
+
+/// Reads sidecar files straight from the music folder, for users who have it mounted
+/// locally (whether MPD runs on the same machine or not). This covers LRC files next to
+/// songs, cover and artist images and Kodi-style album.nfo/artist.nfo files.
+pub struct LocalWrapper {}
+
+impl LocalWrapper {
+    /// The configured music folder, if it's set and exists.
+    fn music_root() -> Option<PathBuf> {
+        let setting = meta_provider_settings(PROVIDER_KEY).string("music-root");
+        let setting = setting.trim();
+        if setting.is_empty() {
+            return None;
+        }
+        let path = if let Some(rest) = setting.strip_prefix("~/") {
+            glib::home_dir().join(rest)
+        } else {
+            PathBuf::from(setting)
+        };
+        if path.is_dir() {
+            Some(path)
+        } else {
+            println!("[Local] Music folder {path:?} does not exist");
+            None
+        }
+    }
+
+    /// Find an artist's folder directly under the music folder, trying the name then the
+    /// sort tag. Falls back to a case-insensitive match as MPD's tags and the folder names
+    /// often differ in casing.
+    fn find_artist_dir(root: &Path, key: &ArtistInfo) -> Option<PathBuf> {
+        let names: Vec<&str> = std::iter::once(key.name.as_str())
+            .chain(key.sort_tag.as_deref())
+            // Names containing separators can't be a single folder
+            .filter(|name| !name.is_empty() && !name.contains('/') && *name != "..")
+            .collect();
+        if let Some(dir) = names.iter().map(|name| root.join(name)).find(|dir| dir.is_dir()) {
+            return Some(dir);
+        }
+        std::fs::read_dir(root)
+            .ok()?
+            .flatten()
+            .find(|entry| {
+                let file_name = entry.file_name();
+                let file_name = file_name.to_string_lossy();
+                names.iter().any(|name| file_name.eq_ignore_ascii_case(name))
+            })
+            .map(|entry| entry.path())
+            .filter(|dir| dir.is_dir())
+    }
+
+    /// Find images in the given folder whose names (sans extension) are one of the given
+    /// ones, case-insensitively. Results follow the order of the names.
+    fn find_images(dir: &Path, names: &[&str], size: models::ImageSize) -> Vec<models::ImageMeta> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::with_capacity(0);
+        };
+        let mut found: Vec<(usize, PathBuf)> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter_map(|path| {
+                let stem = path.file_stem()?.to_string_lossy().to_lowercase();
+                let ext = path.extension()?.to_string_lossy().to_lowercase();
+                if !IMAGE_EXTS.contains(&ext.as_str()) {
+                    return None;
+                }
+                let idx = names.iter().position(|name| *name == stem)?;
+                Some((idx, path))
+            })
+            .filter(|(_, path)| path.is_file())
+            .collect();
+        found.sort();
+        found
+            .into_iter()
+            .filter_map(|(_, path)| glib::filename_to_uri(&path, None).ok())
+            .map(|uri| models::ImageMeta {
+                size,
+                url: uri.to_string(),
+            })
+            .collect()
+    }
+
+    /// Read a sidecar file in the given folder, matching its name case-insensitively.
+    fn read_sidecar(dir: &Path, file_name: &str) -> Option<String> {
+        let path = std::fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .find(|entry| entry.file_name().to_string_lossy().eq_ignore_ascii_case(file_name))?
+            .path();
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Some(contents),
+            Err(e) => {
+                println!("[Local] Unable to read {path:?}: {e:?}");
+                None
+            }
+        }
+    }
+}
+
+impl MetadataProvider for LocalWrapper {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn get_album_meta(
+        &self,
+        key: &mut AlbumInfo,
+        existing: Option<models::AlbumMeta>,
+    ) -> Option<models::AlbumMeta> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return existing;
+        }
+        let Some(root) = Self::music_root() else {
+            return existing;
+        };
+        let dir = root.join(&key.folder_uri);
+        if !dir.is_dir() {
+            return existing;
+        }
+        let mut new = models::AlbumMeta::from_key(key);
+        new.artist = key.get_artist_tag().map(str::to_owned);
+        let has_nfo = Self::read_sidecar(&dir, "album.nfo")
+            .is_some_and(|contents| read_album_nfo(&contents, &mut new));
+        new.image = Self::find_images(&dir, &ALBUM_COVER_NAMES, models::ImageSize::Mega);
+        new.image.append(&mut Self::find_images(
+            &dir,
+            &ALBUM_BACK_NAMES,
+            models::ImageSize::Small,
+        ));
+        if !has_nfo && new.image.is_empty() {
+            return existing;
+        }
+        if let Some(old) = existing {
+            Some(old.merge(new))
+        } else {
+            Some(new)
+        }
+    }
+
+    fn get_artist_meta(
+        &self,
+        key: &mut ArtistInfo,
+        existing: Option<models::ArtistMeta>,
+    ) -> Option<models::ArtistMeta> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return existing;
+        }
+        let Some(dir) = Self::music_root().and_then(|root| Self::find_artist_dir(&root, key)) else {
+            return existing;
+        };
+        let mut new = models::ArtistMeta::from_key(key);
+        let has_nfo = Self::read_sidecar(&dir, "artist.nfo")
+            .is_some_and(|contents| read_artist_nfo(&contents, &mut new));
+        new.image = Self::find_images(&dir, &ARTIST_IMAGE_NAMES, models::ImageSize::Mega);
+        if !has_nfo && new.image.is_empty() {
+            return existing;
+        }
+        if let Some(old) = existing {
+            Some(old.merge(new))
+        } else {
+            Some(new)
+        }
+    }
+
+    /// Read the .lrc file with the same name as the song, if there is one.
+    fn get_lyrics(&self, key: &SongInfo) -> Option<models::Lyrics> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") || is_stream_uri(&key.uri) {
+            return None;
+        }
+        let path = Self::music_root()?.join(&key.uri);
+        let dir = path.parent()?;
+        let file_name = path.with_extension("lrc").file_name()?.to_string_lossy().into_owned();
+        models::Lyrics::try_from_lrc_or_plain_str(&Self::read_sidecar(dir, &file_name)?)
+    }
+}
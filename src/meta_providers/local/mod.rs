@@ -0,0 +1,6 @@
+static PROVIDER_KEY: &str = "local";
+
+mod controller;
+mod models;
+
+pub use controller::LocalWrapper;
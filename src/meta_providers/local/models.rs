@@ -0,0 +1,153 @@
+use chrono::NaiveDate;
+use musicbrainz_rs::entity::artist::ArtistType;
+use quick_xml::{events::Event, Reader};
+
+use super::super::models::{parse_artist_type, AlbumMeta, ArtistMeta, Tag, Wiki};
+
+// Kodi-style NFO files. These are XML documents with a single root element (<album>
+// or <artist>) whose children hold the actual fields. Only the top-level text fields
+// are read; nested ones such as track lists and discographies are skipped.
+
+const ATTRIBUTION: &str = "Local NFO file";
+
+/// The top-level fields of an NFO file, with lowercased names. Fields that may be
+/// repeated (genre, style, etc.) appear once per occurrence.
+struct NfoFields(Vec<(String, String)>);
+
+impl NfoFields {
+    /// Parse an NFO document, returning None if it's not XML with the given root element.
+    /// Kodi also allows NFO files to only contain a scraper URL, which we can't use.
+    fn parse(contents: &str, root: &str) -> Option<Self> {
+        let mut reader = Reader::from_str(contents);
+        reader.config_mut().trim_text(true);
+        let mut fields: Vec<(String, String)> = Vec::new();
+        let mut depth: usize = 0;
+        let mut field: Option<String> = None;
+        let mut text = String::new();
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(e)) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_lowercase();
+                    if depth == 0 && name != root {
+                        return None;
+                    }
+                    depth += 1;
+                    if depth == 2 {
+                        field = Some(name);
+                        text.clear();
+                    }
+                }
+                Ok(Event::Text(t)) if depth == 2 => match t.unescape() {
+                    Ok(val) => text.push_str(&val),
+                    Err(_) => text.push_str(&String::from_utf8_lossy(&t)),
+                },
+                Ok(Event::CData(t)) if depth == 2 => {
+                    text.push_str(&String::from_utf8_lossy(&t));
+                }
+                Ok(Event::End(_)) => {
+                    if depth == 2 {
+                        if let Some(name) = field.take() {
+                            let val = text.trim();
+                            if !val.is_empty() {
+                                fields.push((name, val.to_owned()));
+                            }
+                        }
+                    }
+                    depth = depth.saturating_sub(1);
+                }
+                Ok(Event::Eof) => break,
+                Err(_) => {
+                    return None;
+                }
+                _ => {}
+            }
+        }
+        if fields.is_empty() {
+            None
+        } else {
+            Some(Self(fields))
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, val)| val.as_str())
+    }
+
+    fn get_any(&self, names: &[&str]) -> Option<&str> {
+        names.iter().find_map(|name| self.get(name))
+    }
+
+    /// Genres and styles as tags. Some taggers put several genres in one field
+    /// separated by slashes or semicolons.
+    fn tags(&self) -> Vec<Tag> {
+        let mut tags: Vec<Tag> = Vec::new();
+        for (_, val) in self.0.iter().filter(|(key, _)| key == "genre" || key == "style") {
+            for name in val.split(['/', ';']).map(str::trim).filter(|name| !name.is_empty()) {
+                if !tags.iter().any(|tag| tag.name.eq_ignore_ascii_case(name)) {
+                    tags.push(Tag {
+                        url: None,
+                        name: name.to_owned(),
+                        count: None,
+                    });
+                }
+            }
+        }
+        tags
+    }
+
+    fn wiki(&self, names: &[&str]) -> Option<Wiki> {
+        self.get_any(names).map(|content| Wiki {
+            content: content.to_owned(),
+            url: None,
+            attribution: ATTRIBUTION.to_owned(),
+        })
+    }
+
+    fn date(&self, names: &[&str]) -> Option<NaiveDate> {
+        self.get_any(names).and_then(parse_date)
+    }
+}
+
+/// NFO dates are usually full ISO dates, but often just a year.
+fn parse_date(val: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(val, "%Y-%m-%d").ok().or_else(|| {
+        val.get(..4)
+            .and_then(|year| year.parse::<i32>().ok())
+            .and_then(|year| NaiveDate::from_ymd_opt(year, 1, 1))
+    })
+}
+
+/// Read an album.nfo into the given AlbumMeta. Returns false if the file couldn't be parsed.
+pub fn read_album_nfo(contents: &str, meta: &mut AlbumMeta) -> bool {
+    let Some(nfo) = NfoFields::parse(contents, "album") else {
+        return false;
+    };
+    if meta.mbid.is_none() {
+        meta.mbid = nfo
+            .get_any(&["musicbrainzreleaseid", "musicbrainzalbumid"])
+            .map(str::to_owned);
+    }
+    meta.tags = nfo.tags();
+    meta.wiki = nfo.wiki(&["review", "description"]);
+    true
+}
+
+/// Read an artist.nfo into the given ArtistMeta. Returns false if the file couldn't be parsed.
+pub fn read_artist_nfo(contents: &str, meta: &mut ArtistMeta) -> bool {
+    let Some(nfo) = NfoFields::parse(contents, "artist") else {
+        return false;
+    };
+    if meta.mbid.is_none() {
+        meta.mbid = nfo.get("musicbrainzartistid").map(str::to_owned);
+    }
+    meta.tags = nfo.tags();
+    meta.bio = nfo.wiki(&["biography"]);
+    meta.artist_type = nfo.get("type").map_or(ArtistType::Other, parse_artist_type);
+    meta.gender = nfo.get("gender").map(str::to_owned);
+    meta.begin_date = nfo.date(&["born", "formed"]);
+    meta.end_date = nfo.date(&["died", "disbanded"]);
+    true
+}
@@ -11,7 +11,7 @@ use reqwest::{
 };
 
 use super::{
-    super::{models, MetadataProvider},
+    super::{models, prelude::*},
     LrcLibResponse, PROVIDER_KEY,
 };
 
@@ -23,12 +23,13 @@ pub struct LrcLibWrapper {
 
 impl LrcLibWrapper {
     fn get_lrclib(&self, params: &[(&str, &str)]) -> Option<Response> {
-        let resp = self
-            .client
-            .get(format!("{API_ROOT}search"))
-            .query(params)
-            .header(USER_AGENT, APPLICATION_USER_AGENT)
-            .send();
+        let resp = send_with_retry(
+            PROVIDER_KEY,
+            self.client
+                .get(format!("{API_ROOT}search"))
+                .query(params)
+                .header(USER_AGENT, APPLICATION_USER_AGENT),
+        );
         if let Ok(res) = resp {
             return Some(res);
         }
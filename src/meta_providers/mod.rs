@@ -1,14 +1,20 @@
 mod base;
 mod chain;
+mod rate_limit;
+pub mod embedded;
 pub mod lastfm;
+pub mod local;
 pub mod models;
 pub mod musicbrainz;
+pub mod plugin;
 pub mod lrclib;
+pub mod wikipedia;
 
 pub use base::{utils, ProviderMessage, MetadataProvider};
-pub use chain::{get_provider, MetadataChain};
+pub use chain::{get_provider, provider_order, provider_settings, MetadataChain};
 
 pub mod prelude {
-    pub use super::base::{sleep_after_request, MetadataProvider};
+    pub use super::base::MetadataProvider;
+    pub use super::rate_limit::{send_with_retry, throttle};
     pub use super::models::Merge;
 }
@@ -75,6 +75,13 @@ pub struct AlbumMeta {
     pub artist: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mbid: Option<String>,
+    /// MBID of the release group the release belongs to, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_group_mbid: Option<String>,
+    /// MBID of the release the user picked for this album, if any. Metadata is fetched
+    /// from it rather than from the tagged (or best matching) release.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_mbid: Option<String>,
     pub tags: Vec<Tag>,
     pub image: Vec<ImageMeta>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -90,6 +97,8 @@ impl AlbumMeta {
         Self {
             name: key.title.to_owned(),
             mbid: key.mbid.clone(),
+            release_group_mbid: None,
+            pinned_mbid: None,
             artist: None,
             tags: Vec::with_capacity(0),
             image: Vec::with_capacity(0),
@@ -97,6 +106,11 @@ impl AlbumMeta {
             wiki: None,
         }
     }
+
+    /// Whether no provider has filled anything in, as with from_key().
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.image.is_empty() && self.url.is_none() && self.wiki.is_none()
+    }
 }
 
 impl Merge for AlbumMeta {
@@ -104,6 +118,8 @@ impl Merge for AlbumMeta {
         mut self,
         AlbumMeta {
             mbid,
+            release_group_mbid,
+            pinned_mbid,
             artist,
             mut tags,
             mut image,
@@ -115,6 +131,8 @@ impl Merge for AlbumMeta {
         self.tags.append(&mut tags);
         self.image.append(&mut image);
         self.mbid = Self::merge_option(self.mbid, mbid);
+        self.release_group_mbid = Self::merge_option(self.release_group_mbid, release_group_mbid);
+        self.pinned_mbid = Self::merge_option(self.pinned_mbid, pinned_mbid);
         self.artist = Self::merge_option(self.artist, artist);
         self.url = Self::merge_option(self.url, url);
         self.wiki = Self::merge_option(self.wiki, wiki);
@@ -162,6 +180,19 @@ pub struct ArtistMeta {
     pub country: Option<String>,
 }
 
+/// Parse an artist type as named by MusicBrainz (case-insensitive). Unknown types are
+/// left as Other.
+pub fn parse_artist_type(name: &str) -> ArtistType {
+    match name.to_lowercase().as_str() {
+        "person" => ArtistType::Person,
+        "group" => ArtistType::Group,
+        "orchestra" => ArtistType::Orchestra,
+        "choir" => ArtistType::Choir,
+        "character" => ArtistType::Character,
+        _ => ArtistType::Other,
+    }
+}
+
 impl ArtistMeta {
     /// Create a minimal ArtistMeta. Useful for blocking further calls to an artist
     /// whose information are unavailable on any remote source.
@@ -181,6 +212,17 @@ impl ArtistMeta {
             country: None,
         }
     }
+
+    /// Whether no provider has filled anything in, as with from_key().
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.similar.is_empty()
+            && self.image.is_empty()
+            && self.url.is_none()
+            && self.bio.is_none()
+            && self.begin_date.is_none()
+            && self.country.is_none()
+    }
 }
 
 impl Merge for ArtistMeta {
@@ -240,17 +282,98 @@ impl HasImage for ArtistMeta {
 
 pub struct Lyrics {
     pub lines: Vec<(f32, String)>, // timestamp (in seconds) and corresponding line. If not synced, set timestamp to 0.
+    /// Word-level timings (Enhanced LRC), as (timestamp, word) pairs including their spacing.
+    /// Either empty or parallel to lines, with empty entries for lines without them. A trailing
+    /// empty word marks when the last one ends.
+    pub words: Vec<Vec<(f32, String)>>,
+    /// ID tags such as ar, ti, al or length, in file order. Offsets are already applied.
+    pub tags: Vec<(String, String)>,
     pub synced: bool,
 }
 
 #[derive(Debug, Clone)]
 pub enum LyricsParseError {
     TimestampNotFoundError,
-    TimestampFormatError
 }
 
 pub type LyricsResult = Result<Lyrics, LyricsParseError>;
 
+/// Parse an mm:ss, mm:ss.xx or mm:ss:xx timestamp (without brackets) into seconds.
+fn parse_lrc_timestamp(ts: &str) -> Option<f32> {
+    let (mins, rest) = ts.trim().split_once(':')?;
+    // Some files separate the fraction with a colon instead of a dot.
+    let (secs, frac) = rest.split_once(['.', ':']).unwrap_or((rest, ""));
+    let is_number = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if mins.is_empty() || secs.is_empty() || !is_number(mins) || !is_number(secs) || !is_number(frac) {
+        return None;
+    }
+    let secs: f32 = format!("{secs}.{frac}0").parse().ok()?;
+    Some(mins.parse::<f32>().ok()? * 60.0 + secs)
+}
+
+pub fn format_lrc_timestamp(ts: f32) -> String {
+    let hundredths = (ts.max(0.0) * 100.0).round() as u32;
+    format!(
+        "{:02}:{:02}.{:02}",
+        hundredths / 6000,
+        hundredths / 100 % 60,
+        hundredths % 100
+    )
+}
+
+/// Whether a line starts with an [mm:ss] or [mm:ss.xx] timestamp.
+fn is_lrc_line(line: &str) -> bool {
+    line.trim_start()
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .is_some_and(|(ts, _)| parse_lrc_timestamp(ts).is_some())
+}
+
+/// Find the next <mm:ss.xx> word timestamp, returning the text before it, the timestamp
+/// and the text after it. Angle brackets not enclosing a timestamp are left alone.
+fn next_word_timestamp(text: &str) -> Option<(&str, f32, &str)> {
+    let mut from = 0;
+    while let Some(open) = text[from..].find('<').map(|pos| pos + from) {
+        if let Some((inner, after)) = text[open + 1..].split_once('>') {
+            if let Some(ts) = parse_lrc_timestamp(inner) {
+                return Some((&text[..open], ts, after));
+            }
+        }
+        from = open + 1;
+    }
+    None
+}
+
+/// Split the text of an Enhanced LRC line into timed words. Text before the first word
+/// timestamp starts with the line itself. Returns no words if the line has no word timestamps.
+fn parse_lrc_words(text: &str, line_start: f32) -> Vec<(f32, String)> {
+    let Some((leading, mut start, mut rest)) = next_word_timestamp(text) else {
+        return Vec::new();
+    };
+    let mut words: Vec<(f32, String)> = Vec::new();
+    if !leading.trim().is_empty() {
+        words.push((line_start, leading.to_owned()));
+    }
+    loop {
+        let (word, next) = match next_word_timestamp(rest) {
+            Some((word, ts, after)) => (word, Some((ts, after))),
+            None => (rest, None),
+        };
+        // Keep a trailing empty word as it marks the end of the last one.
+        if !word.is_empty() || next.is_none() {
+            words.push((start, word.to_owned()));
+        }
+        match next {
+            Some((ts, after)) => {
+                start = ts;
+                rest = after;
+            }
+            None => break,
+        }
+    }
+    words
+}
+
 impl Lyrics {
     pub fn try_from_plain_lrclib_str(lrclib: &str) -> LyricsResult {
         let lines: Vec<(f32, String)> = lrclib
@@ -259,86 +382,166 @@ impl Lyrics {
             .collect();
         Ok(Self {
             lines,
+            words: Vec::new(),
+            tags: Vec::new(),
             synced: false,
         })
     }
 
+    /// The same lyrics with new line timestamps (one per line), such as from hand-timing.
+    /// Word timestamps move along with their lines, and lines are sorted again as they
+    /// might now be out of order.
+    pub fn retimed(&self, stamps: &[f32]) -> Self {
+        let mut lines: Vec<(f32, String, Vec<(f32, String)>)> = self
+            .lines
+            .iter()
+            .zip(stamps)
+            .enumerate()
+            .map(|(idx, ((old_ts, text), new_ts))| {
+                let delta = new_ts - old_ts;
+                let words = self
+                    .line_words(idx)
+                    .iter()
+                    .map(|(word_ts, word)| ((word_ts + delta).max(0.0), word.clone()))
+                    .collect();
+                (*new_ts, text.clone(), words)
+            })
+            .collect();
+        lines.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let has_words = self.has_word_timings();
+        let (lines, words) = lines
+            .into_iter()
+            .map(|(ts, text, words)| ((ts, text), words))
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+        Self {
+            lines,
+            words: if has_words { words } else { Vec::new() },
+            tags: self.tags.clone(),
+            synced: true,
+        }
+    }
+
+    /// Parse LRC, including lines with several timestamps (for repeated verses), ID tags
+    /// and Enhanced LRC word timestamps. Lines without a timestamp, such as section markers,
+    /// are skipped.
     pub fn try_from_synced_lrclib_str(lrclib: &str) -> LyricsResult {
-        let raw_lines: Vec<&str> = lrclib.split('\n').collect();
-        let mut lines: Vec<(f32, String)> = Vec::with_capacity(raw_lines.len());
         let mut offset: f32 = 0.0;
-        for raw_line in raw_lines.iter() {
-            let line = raw_line.trim();
-            if !line.is_empty() {
-                // Extract timestamp
-                let ts_end_pos: usize = line
-                    .find(']')
-                    .ok_or(LyricsParseError::TimestampNotFoundError)?;
-                let ts_str: &str = &line[1..ts_end_pos];
-                let ts_parts: Vec<&str> = ts_str.split(':').collect();
-                if ts_parts.len() != 2 {
-                    Err(LyricsParseError::TimestampFormatError)?;
-                }
-                match ts_parts[0] {
-                    "offset" => {
-                        if let Ok(ms_offset) = ts_parts[1].parse::<f32>() {
-                            offset = ms_offset / 1000.0;
-                        }
-                    }
-                    "#" => {
-                        // Comment. Do nothing on these tags
-                    }
-                    _ => {
-                        // Only accept timestamp for now. Other tags are ignored
-                        if !ts_parts[0]
-                            .chars()
-                            .map(|c|c.is_numeric())
-                            .collect::<Vec<bool>>()
-                            .contains(&false)
-                        {
-                            let ts: f32 = (ts_parts[0]
-                                .parse::<f32>()
-                                .map_err(|_| LyricsParseError::TimestampFormatError)? * 60.0
-                                + ts_parts[1]
-                                .parse::<f32>()
-                                .map_err(|_| LyricsParseError::TimestampFormatError)? - offset).max(0.0);
-                            if line.len() <= ts_end_pos + 1 {
-                                lines.push((ts, "".to_owned()));
-                            } else {
-                                lines.push((ts, line[ts_end_pos + 1..].to_owned()));
+        let mut tags: Vec<(String, String)> = Vec::new();
+        // Timestamp, text & words, in file order
+        let mut lines: Vec<(f32, String, Vec<(f32, String)>)> = Vec::new();
+        for raw_line in lrclib.lines() {
+            let mut rest = raw_line.trim();
+            let mut stamps: Vec<f32> = Vec::new();
+            while let Some((inner, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+                if let Some(ts) = parse_lrc_timestamp(inner) {
+                    stamps.push(ts);
+                    rest = after.trim_start();
+                } else {
+                    // ID tags take up the whole line. Bracketed text following timestamps
+                    // is part of the lyrics.
+                    if stamps.is_empty() {
+                        if let Some((key, value)) = inner.split_once(':') {
+                            let (key, value) = (key.trim(), value.trim());
+                            match key {
+                                "offset" => {
+                                    if let Ok(ms_offset) = value.parse::<f32>() {
+                                        offset = ms_offset / 1000.0;
+                                    }
+                                }
+                                "#" => {
+                                    // Comment. Do nothing on these tags
+                                }
+                                _ if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphabetic()) => {
+                                    tags.push((key.to_owned(), value.to_owned()));
+                                }
+                                _ => {}
                             }
                         }
                     }
+                    break;
                 }
             }
+            let Some(first) = stamps.first().copied() else {
+                continue;
+            };
+            let words = parse_lrc_words(rest, first);
+            let text = if words.is_empty() {
+                rest.to_owned()
+            } else {
+                words.iter().map(|(_, word)| word.as_str()).collect::<String>().trim().to_owned()
+            };
+            for ts in stamps {
+                // Repeats keep the word timings of the first occurrence relative to the line.
+                let shifted = words.iter().map(|(word_ts, word)| (word_ts - first + ts, word.clone())).collect();
+                lines.push((ts, text.clone(), shifted));
+            }
         }
-
-        Ok(Self {
-            lines,
+        if lines.is_empty() {
+            return Err(LyricsParseError::TimestampNotFoundError);
+        }
+        // Positive offsets make lyrics appear sooner.
+        let apply_offset = |ts: f32| (ts - offset).max(0.0);
+        // Stable, so lines sharing a timestamp keep their order.
+        lines.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let has_words = lines.iter().any(|line| !line.2.is_empty());
+        let mut res = Self {
+            lines: Vec::with_capacity(lines.len()),
+            words: Vec::with_capacity(if has_words { lines.len() } else { 0 }),
+            tags,
             synced: true,
-        })
+        };
+        for (ts, text, words) in lines {
+            res.lines.push((apply_offset(ts), text));
+            if has_words {
+                res.words.push(
+                    words
+                        .into_iter()
+                        .map(|(word_ts, word)| (apply_offset(word_ts), word))
+                        .collect()
+                );
+            }
+        }
+        Ok(res)
+    }
+
+    /// Parse text that may be either LRC or plain lyrics, such as an embedded lyrics tag or
+    /// a sidecar file. It's treated as LRC if any line starts with a timestamp, falling back
+    /// to plain text if that fails. Returns None for blank text.
+    pub fn try_from_lrc_or_plain_str(text: &str) -> Option<Self> {
+        let text = text.replace("\r\n", "\n");
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        if text.lines().any(is_lrc_line) {
+            if let Ok(lyrics) = Self::try_from_synced_lrclib_str(text) {
+                if !lyrics.lines.is_empty() {
+                    return Some(lyrics);
+                }
+            }
+        }
+        Self::try_from_plain_lrclib_str(text).ok()
     }
 
     pub fn to_string(&self) -> String {
         if self.synced {
-            self.lines.iter().map(|line| {
-            let total_seconds = line.0.max(0.0);
-            let content = &line.1;
-
-            let minutes = (total_seconds / 60.0).floor() as u32;
-            let remaining_seconds = total_seconds % 60.0;
-            // Extract the integer part of the seconds
-            let seconds_integer = remaining_seconds.floor() as u32;
-
-            // Extract the fractional part (hundredths of a second)
-            // Multiply by 100, round to the nearest integer, and then cast to u32.
-            // Using `round()` to handle potential floating-point inaccuracies
-            // and ensure correct rounding for the hundredths.
-            let hundredths = (remaining_seconds.fract() * 100.0).round() as u32;
-
-            // Format the components into the desired string
-            format!("[{minutes:02}:{seconds_integer:02}.{hundredths:02}] {content}")
-        }).collect::<Vec<String>>().join("\n")
+            self.tags
+                .iter()
+                .map(|(key, value)| format!("[{key}:{value}]"))
+                .chain(self.lines.iter().enumerate().map(|(idx, line)| {
+                    let words = self.line_words(idx);
+                    if words.is_empty() {
+                        format!("[{}] {}", format_lrc_timestamp(line.0), &line.1)
+                    } else {
+                        let content = words
+                            .iter()
+                            .map(|(ts, word)| format!("<{}>{word}", format_lrc_timestamp(*ts)))
+                            .collect::<String>();
+                        format!("[{}] {content}", format_lrc_timestamp(line.0))
+                    }
+                }))
+                .collect::<Vec<String>>()
+                .join("\n")
         }
         else {
             self.lines.iter().map(|line| line.1.as_str()).collect::<Vec<&str>>().join("\n")
@@ -377,4 +580,105 @@ impl Lyrics {
     pub fn n_lines(&self) -> usize {
         self.lines.len()
     }
+
+    pub fn has_word_timings(&self) -> bool {
+        !self.words.is_empty()
+    }
+
+    /// Timed words of the given line. Empty if it has no word timings.
+    pub fn line_words(&self, line: usize) -> &[(f32, String)] {
+        self.words.get(line).map_or(&[][..], Vec::as_slice)
+    }
+
+    /// Index of the word being sung in the given line, if any.
+    pub fn get_word_at_timestamp(&self, line: usize, ts: f32) -> Option<usize> {
+        self.line_words(line)
+            .iter()
+            .rposition(|(word_ts, _)| *word_ts <= ts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(pairs: &[(f32, &str)]) -> Vec<(f32, String)> {
+        pairs.iter().map(|(ts, word)| (*ts, word.to_string())).collect()
+    }
+
+    #[test]
+    fn lrc_timestamps() {
+        assert_eq!(parse_lrc_timestamp("01:02.50"), Some(62.5));
+        assert_eq!(parse_lrc_timestamp("01:02:50"), Some(62.5));
+        assert_eq!(parse_lrc_timestamp("1:02"), Some(62.0));
+        assert_eq!(parse_lrc_timestamp("ar:Someone"), None);
+        assert_eq!(parse_lrc_timestamp("01:"), None);
+        assert_eq!(parse_lrc_timestamp("01:02.5x"), None);
+        assert_eq!(parse_lrc_timestamp("-1:02"), None);
+    }
+
+    #[test]
+    fn lrc_words() {
+        assert_eq!(
+            parse_lrc_words("Oh <00:01.50>la <00:02.00>la<00:02.50>", 1.0),
+            words(&[(1.0, "Oh "), (1.5, "la "), (2.0, "la"), (2.5, "")])
+        );
+        assert!(parse_lrc_words("No word timings", 1.0).is_empty());
+    }
+
+    #[test]
+    fn lrc_malformed_word_tags() {
+        // Angle brackets not enclosing a timestamp are part of the text.
+        assert_eq!(
+            parse_lrc_words("<00:01.00>Hello <oops>world <00:xx>!<00:02.00>", 1.0),
+            words(&[(1.0, "Hello <oops>world <00:xx>!"), (2.0, "")])
+        );
+        assert!(parse_lrc_words("<oops>Hello <00:1x>", 1.0).is_empty());
+    }
+
+    #[test]
+    fn lrc_repeated_stamps() {
+        let lyrics = Lyrics::try_from_synced_lrclib_str(
+            "[00:01.00][00:10.00]<00:01.00>La <00:01.50>la<00:02.00>\n[00:05.00]Verse",
+        )
+        .unwrap();
+        assert_eq!(
+            lyrics.lines,
+            vec![
+                (1.0, "La la".to_owned()),
+                (5.0, "Verse".to_owned()),
+                (10.0, "La la".to_owned()),
+            ]
+        );
+        assert_eq!(lyrics.line_words(0), words(&[(1.0, "La "), (1.5, "la"), (2.0, "")]));
+        assert!(lyrics.line_words(1).is_empty());
+        assert_eq!(lyrics.line_words(2), words(&[(10.0, "La "), (10.5, "la"), (11.0, "")]));
+    }
+
+    #[test]
+    fn lrc_offset() {
+        let lyrics = Lyrics::try_from_synced_lrclib_str(
+            "[ar:Someone]\n[offset:500]\n[00:00.20]Early\n[00:02.00]<00:02.00>Hi<00:03.00>",
+        )
+        .unwrap();
+        assert_eq!(lyrics.tags, vec![("ar".to_owned(), "Someone".to_owned())]);
+        // Never shifted before the start of the song
+        assert_eq!(lyrics.lines, vec![(0.0, "Early".to_owned()), (1.5, "Hi".to_owned())]);
+        assert_eq!(lyrics.line_words(1), words(&[(1.5, "Hi"), (2.5, "")]));
+    }
+
+    #[test]
+    fn retimed_keeps_words_and_tags() {
+        let lyrics = Lyrics::try_from_synced_lrclib_str(
+            "[ar:Someone]\n[00:01.00]<00:01.00>La <00:01.50>la\n[00:05.00]Verse",
+        )
+        .unwrap();
+        let retimed = lyrics.retimed(&[6.0, 2.0]);
+        assert_eq!(
+            retimed.lines,
+            vec![(2.0, "Verse".to_owned()), (6.0, "La la".to_owned())]
+        );
+        assert_eq!(retimed.line_words(1), words(&[(6.0, "La "), (6.5, "la")]));
+        assert_eq!(retimed.tags, lyrics.tags);
+    }
 }
@@ -10,9 +10,51 @@ use crate::{common::{AlbumInfo, ArtistInfo}, utils::meta_provider_settings};
 
 use super::{
     super::{models, prelude::*, MetadataProvider},
+    models::ReleaseCandidate,
     PROVIDER_KEY,
 };
 
+/// Search for every release matching the album's title & artist tag, so that the user
+/// can pick the right one when the tags carry no MBID (or the wrong one).
+/// Empty if MusicBrainz is disabled or the album has no artist tag.
+pub fn search_releases(key: &AlbumInfo) -> Vec<ReleaseCandidate> {
+    if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+        return Vec::new();
+    }
+    let Some(artist) = key.get_artist_tag() else {
+        return Vec::new();
+    };
+    println!(
+        "[MusicBrainz] Listing releases with title = {} and artist = {artist}",
+        &key.title
+    );
+    throttle(PROVIDER_KEY);
+    let res = Release::search(
+        ReleaseSearchQuery::query_builder()
+            .release(&key.title)
+            .artist(artist)
+            .build(),
+    )
+    .with_artist_credits()
+    .execute();
+    match res {
+        Ok(found) => found.entities.into_iter().map(ReleaseCandidate::from).collect(),
+        Err(e) => {
+            println!("[MusicBrainz] Could not search releases: {e:?}");
+            Vec::new()
+        }
+    }
+}
+
+/// Front cover of the given release on the Cover Art Archive. The URL might not resolve,
+/// as not every release has art uploaded.
+pub fn cover_art_archive_image(mbid: &str) -> models::ImageMeta {
+    models::ImageMeta {
+        size: models::ImageSize::Mega,
+        url: format!("https://coverartarchive.org/release/{mbid}/front-1200"),
+    }
+}
+
 pub struct MusicBrainzWrapper {}
 
 impl MetadataProvider for MusicBrainzWrapper {
@@ -30,9 +72,11 @@ impl MetadataProvider for MusicBrainzWrapper {
         if meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
             if let Some(mbid) = key.mbid.as_ref() {
                 println!("[MusicBrainz] Fetching release by MBID: {}", &mbid);
+                throttle(PROVIDER_KEY);
                 let res = Release::fetch()
                     .id(mbid)
                     .with_artist_credits()
+                    .with_release_groups()
                     .execute();
                 if let Ok(release) = res {
                     let new: models::AlbumMeta = release.into();
@@ -57,6 +101,7 @@ impl MetadataProvider for MusicBrainzWrapper {
                 println!(
                     "[MusicBrainz] Searching release with title = {title} and artist = {artist}"
                 );
+                throttle(PROVIDER_KEY);
                 let res = Release::search(
                     ReleaseSearchQuery::query_builder()
                         .release(title)
@@ -106,6 +151,7 @@ impl MetadataProvider for MusicBrainzWrapper {
         if meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
             if let Some(mbid) = key.mbid.as_ref() {
                 println!("[MusicBrainz] Fetching artist by MBID: {mbid}");
+                throttle(PROVIDER_KEY);
                 let res = Artist::fetch()
                     .id(mbid)
                     .with_url_relations()
@@ -131,6 +177,7 @@ impl MetadataProvider for MusicBrainzWrapper {
             else {
                 let name = &key.name;
                 println!("[MusicBrainz] Fetching artist with name = {}", &name);
+                throttle(PROVIDER_KEY);
                 let res = Artist::search(
                     ArtistSearchQuery::query_builder()
                         .artist(name)
@@ -2,4 +2,5 @@ static PROVIDER_KEY: &str = "musicbrainz";
 
 mod controller;
 mod models;
-pub use controller::MusicBrainzWrapper;
+pub use controller::{cover_art_archive_image, search_releases, MusicBrainzWrapper};
+pub use models::ReleaseCandidate;
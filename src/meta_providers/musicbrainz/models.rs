@@ -6,6 +6,7 @@ use chrono::NaiveDate;
 use gtk::prelude::SettingsExt;
 use musicbrainz_rs::entity::{
     artist::{Artist, ArtistType, Gender},
+    artist_credit::ArtistCredit,
     relations::RelationContent,
     release::Release,
     tag::Tag,
@@ -49,24 +50,23 @@ pub fn mb_gender_to_str(g: Gender) -> Option<String> {
     }
 }
 
+fn join_artist_credit(artists: &[ArtistCredit]) -> String {
+    let mut content = String::new();
+    for artist in artists.iter() {
+        content.push_str(&artist.name);
+        // Spaces should already be included.
+        // Last artist should not have one.
+        if let Some(delim) = &artist.joinphrase {
+            content.push_str(delim);
+        }
+    }
+    content
+}
+
 impl From<Release> for models::AlbumMeta {
     fn from(rel: Release) -> Self {
         // TODO: Keep more of the data in Release.
-        let artist_tag: Option<String>;
-        if let Some(artists) = rel.artist_credit {
-            let mut content = String::new();
-            for artist in artists.iter() {
-                content.push_str(&artist.name);
-                // Spaces should already be included.
-                // Last artist should not have one.
-                if let Some(delim) = &artist.joinphrase {
-                    content.push_str(delim);
-                }
-            }
-            artist_tag = Some(content);
-        } else {
-            artist_tag = None;
-        }
+        let artist_tag: Option<String> = rel.artist_credit.as_deref().map(join_artist_credit);
         let tags: Vec<models::Tag>;
         if let Some(mbtags) = rel.tags {
             tags = mbtags.into_iter().map(models::Tag::from).collect();
@@ -78,6 +78,8 @@ impl From<Release> for models::AlbumMeta {
             name: rel.title,
             artist: artist_tag,
             mbid: Some(rel.id.clone()),
+            release_group_mbid: rel.release_group.map(|group| group.id),
+            pinned_mbid: None,
             tags,
             image: Vec::new(), // acquired separately
             url: Some(format!("https://musicbrainz.org/release/{}", rel.id)),
@@ -86,6 +88,60 @@ impl From<Release> for models::AlbumMeta {
     }
 }
 
+/// A release matching an album's title & artist, for the user to tell apart from the
+/// others (different editions, regions, compilations sharing a title...).
+#[derive(Debug, Clone)]
+pub struct ReleaseCandidate {
+    pub mbid: String,
+    pub title: String,
+    pub artist: Option<String>,
+    /// Date, country, label, formats & disambiguation comment, in one line
+    pub details: String,
+}
+
+impl From<Release> for ReleaseCandidate {
+    fn from(rel: Release) -> Self {
+        let mut details: Vec<String> = Vec::new();
+        if let Some(date) = rel.date {
+            details.push(date.to_string());
+        }
+        if let Some(country) = rel.country {
+            details.push(country);
+        }
+        if let Some(label) = rel
+            .label_info
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|info| info.label)
+        {
+            details.push(label.name);
+        }
+        if let Some(media) = rel.media.filter(|media| !media.is_empty()) {
+            let mut formats: Vec<String> = Vec::new();
+            for format in media.iter().filter_map(|medium| medium.format.clone()) {
+                if !formats.contains(&format) {
+                    formats.push(format);
+                }
+            }
+            let n_tracks: u32 = media.iter().map(|medium| medium.track_count).sum();
+            if formats.is_empty() {
+                details.push(format!("{n_tracks} tracks"));
+            } else {
+                details.push(format!("{}, {n_tracks} tracks", formats.join(" + ")));
+            }
+        }
+        if let Some(comment) = rel.disambiguation.filter(|comment| !comment.is_empty()) {
+            details.push(comment);
+        }
+        Self {
+            artist: rel.artist_credit.as_deref().map(join_artist_credit),
+            mbid: rel.id,
+            title: rel.title,
+            details: details.join(" · "),
+        }
+    }
+}
+
 impl From<Artist> for models::ArtistMeta {
     fn from(artist: Artist) -> Self {
         // TODO: Keep more of the data in Artist.
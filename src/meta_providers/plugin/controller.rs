@@ -0,0 +1,271 @@
+use std::{
+    ffi::OsString,
+    io::{Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use gtk::{gio, glib, prelude::*};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    common::{AlbumInfo, ArtistInfo, SongInfo},
+    config::APPLICATION_ID,
+    utils::settings_manager,
+};
+
+use super::{
+    super::{models, prelude::*},
+    models::{
+        AlbumKey, AlbumResponse, ArtistKey, ArtistResponse, LyricsResponse, Request, RequestKind,
+        SongKey, PROTOCOL_VERSION,
+    },
+    KEY_PREFIX,
+};
+
+/// How often to check whether a plugin has exited.
+const POLL_INTERVAL_MS: u64 = 50;
+
+/// Runs an external command as a metadata provider. See the models module for the protocol.
+/// Each plugin has its own settings (name, command, timeout, etc.) under
+/// metaprovider/plugins/<id>/, with the IDs listed in metaprovider's "plugins" key.
+pub struct PluginWrapper {
+    /// Full provider key, i.e. "plugin:<id>"
+    key: String,
+}
+
+impl PluginWrapper {
+    pub fn with_key(key: &str) -> Self {
+        Self {
+            key: key.to_owned(),
+        }
+    }
+
+    fn settings(&self) -> gio::Settings {
+        plugin_settings(self.key.strip_prefix(KEY_PREFIX).unwrap_or_default())
+    }
+
+    fn call<K: Serialize, T: DeserializeOwned>(&self, kind: RequestKind, key: K) -> Option<T> {
+        if self.key.is_empty() {
+            return None;
+        }
+        let settings = self.settings();
+        if !settings.boolean("enabled") {
+            return None;
+        }
+        let name = settings.string("name");
+        let res = glib::shell_parse_argv(settings.string("command").as_str())
+            .map_err(|e| format!("Invalid command: {e}"))
+            .and_then(|argv| {
+                let request = serde_json::to_vec(&Request {
+                    version: PROTOCOL_VERSION,
+                    kind,
+                    key,
+                })
+                .map_err(|e| e.to_string())?;
+                println!("[Plugin {name}] Calling {argv:?}");
+                run(&argv, request, Duration::from_secs(settings.uint("timeout-s") as u64))
+            })
+            .and_then(|stdout| {
+                serde_json::from_slice::<Option<T>>(&stdout)
+                    .map_err(|e| format!("Invalid response: {e}"))
+            });
+        match res {
+            Ok(parsed) => {
+                if !settings.string("last-error").is_empty() {
+                    let _ = settings.set_string("last-error", "");
+                }
+                parsed
+            }
+            Err(msg) => {
+                println!("[Plugin {name}] {msg}");
+                let _ = settings.set_string("last-error", &msg);
+                None
+            }
+        }
+    }
+}
+
+/// Run the command with the given stdin, returning its stdout. Stderr is logged.
+fn run(argv: &[OsString], input: Vec<u8>, timeout: Duration) -> Result<Vec<u8>, String> {
+    let (program, args) = argv.split_first().ok_or("Empty command")?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Unable to start: {e}"))?;
+
+    // Feed & drain the pipes on their own threads so that a plugin that writes a lot
+    // (or doesn't read its stdin) can't deadlock us.
+    let mut stdin = child.stdin.take().unwrap();
+    thread::spawn(move || {
+        let _ = stdin.write_all(&input);
+    });
+    let mut stdout = child.stdout.take().unwrap();
+    let stdout_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stdout.read_to_end(&mut buf);
+        buf
+    });
+    let mut stderr = child.stderr.take().unwrap();
+    let stderr_reader = thread::spawn(move || {
+        let mut buf = String::new();
+        let _ = stderr.read_to_string(&mut buf);
+        buf
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if start.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("Timed out after {}s", timeout.as_secs()));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(POLL_INTERVAL_MS)),
+            Err(e) => return Err(e.to_string()),
+        }
+    };
+    let stdout = stdout_reader.join().unwrap_or_default();
+    let stderr = stderr_reader.join().unwrap_or_default();
+    let stderr = stderr.trim();
+    if !stderr.is_empty() {
+        println!("{stderr}");
+    }
+    if status.success() {
+        Ok(stdout)
+    } else if stderr.is_empty() {
+        Err(format!("Exited with {status}"))
+    } else {
+        // The last line is usually the most relevant one
+        Err(format!(
+            "Exited with {status}: {}",
+            stderr.lines().last().unwrap_or_default()
+        ))
+    }
+}
+
+/// Settings of the plugin with the given ID. Plugins share a relocatable schema, with one
+/// path per plugin.
+pub fn plugin_settings(id: &str) -> gio::Settings {
+    let settings = settings_manager().child("metaprovider");
+    // Trim the .Devel suffix if exists
+    let app_id = APPLICATION_ID.trim_end_matches(".Devel");
+    let path = format!("{}plugins/{id}/", settings.path());
+    gio::Settings::with_path(&format!("{app_id}.metaprovider.plugin"), &path)
+}
+
+/// Keys of all configured plugins.
+pub fn plugin_keys() -> Vec<String> {
+    settings_manager()
+        .child("metaprovider")
+        .strv("plugins")
+        .iter()
+        .map(|id| format!("{KEY_PREFIX}{id}"))
+        .collect()
+}
+
+/// Register a new plugin running the given command, named after its executable.
+/// Returns its provider key.
+pub fn add_plugin(command: &str) -> Result<String, String> {
+    let argv = glib::shell_parse_argv(command).map_err(|e| e.to_string())?;
+    let program = argv.first().ok_or("Empty command")?;
+    let name = Path::new(program)
+        .file_stem()
+        .unwrap_or(program.as_os_str())
+        .to_string_lossy()
+        .into_owned();
+    // IDs become part of a GSettings path, so only keep safe characters.
+    let slug: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let slug = slug.trim_matches('-');
+    let slug = if slug.is_empty() { "plugin" } else { slug };
+
+    let settings = settings_manager().child("metaprovider");
+    let mut ids: Vec<String> = settings.strv("plugins").iter().map(|id| id.to_string()).collect();
+    let id = (1..)
+        .map(|n| if n == 1 { slug.to_owned() } else { format!("{slug}-{n}") })
+        .find(|id| !ids.contains(id))
+        .unwrap();
+    let key = format!("{KEY_PREFIX}{id}");
+
+    let plugin_settings = plugin_settings(&id);
+    plugin_settings.set_string("name", &name).map_err(|e| e.to_string())?;
+    plugin_settings.set_string("command", command.trim()).map_err(|e| e.to_string())?;
+    ids.push(id);
+    settings
+        .set_value("plugins", &ids.to_variant())
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+/// Unregister a plugin and forget its settings.
+pub fn remove_plugin(key: &str) {
+    let Some(id) = key.strip_prefix(KEY_PREFIX) else {
+        return;
+    };
+    let plugin_settings = plugin_settings(id);
+    for setting in ["name", "enabled", "command", "timeout-s", "last-error"] {
+        plugin_settings.reset(setting);
+    }
+    let settings = settings_manager().child("metaprovider");
+    let ids: Vec<String> = settings
+        .strv("plugins")
+        .iter()
+        .filter(|saved_id| saved_id.as_str() != id)
+        .map(|saved_id| saved_id.to_string())
+        .collect();
+    let _ = settings.set_value("plugins", &ids.to_variant());
+}
+
+impl MetadataProvider for PluginWrapper {
+    /// Plugins need a key to locate their settings, so use with_key() instead.
+    /// Instances created this way do nothing.
+    fn new() -> Self {
+        Self { key: String::new() }
+    }
+
+    fn get_album_meta(
+        &self,
+        key: &mut AlbumInfo,
+        existing: Option<models::AlbumMeta>,
+    ) -> Option<models::AlbumMeta> {
+        let Some(res) = self.call::<_, AlbumResponse>(RequestKind::Album, AlbumKey::from(&*key)) else {
+            return existing;
+        };
+        let new = res.into_meta(key, &self.settings().string("name"));
+        if let Some(old) = existing {
+            Some(old.merge(new))
+        } else {
+            Some(new)
+        }
+    }
+
+    fn get_artist_meta(
+        &self,
+        key: &mut ArtistInfo,
+        existing: Option<models::ArtistMeta>,
+    ) -> Option<models::ArtistMeta> {
+        let Some(res) = self.call::<_, ArtistResponse>(RequestKind::Artist, ArtistKey::from(&*key)) else {
+            return existing;
+        };
+        let new = res.into_meta(key, &self.settings().string("name"));
+        if let Some(old) = existing {
+            Some(old.merge(new))
+        } else {
+            Some(new)
+        }
+    }
+
+    fn get_lyrics(&self, key: &SongInfo) -> Option<models::Lyrics> {
+        self.call::<_, LyricsResponse>(RequestKind::Lyrics, SongKey::from(key))?
+            .into_lyrics()
+    }
+}
@@ -0,0 +1,7 @@
+/// Plugin provider keys are this prefix followed by the plugin's ID.
+pub static KEY_PREFIX: &str = "plugin:";
+
+mod controller;
+pub mod models;
+
+pub use controller::{add_plugin, plugin_keys, plugin_settings, remove_plugin, PluginWrapper};
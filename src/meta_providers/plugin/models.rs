@@ -0,0 +1,258 @@
+//! Plugin protocol.
+//!
+//! A plugin is any executable. For each lookup, Euphonica runs it once, writes a single
+//! JSON request to its stdin and closes it:
+//!
+//! ```json
+//! { "version": 1, "kind": "album", "key": { "title": "...", "artist": "...", ... } }
+//! ```
+//!
+//! `kind` is one of `album`, `artist` or `lyrics`, with `key` being an [`AlbumKey`],
+//! [`ArtistKey`] or [`SongKey`] respectively. IDs found by earlier providers in the chain
+//! (such as MusicBrainz IDs) are included in the key.
+//!
+//! The plugin must then print a single JSON response to stdout and exit with status 0:
+//! an [`AlbumResponse`], [`ArtistResponse`] or [`LyricsResponse`], or `null` if it has
+//! nothing. Every response field is optional. Results are merged into those of earlier
+//! providers. Anything printed to stderr is logged, and a non-zero exit status is
+//! reported as an error in the preferences dialog.
+
+use chrono::NaiveDate;
+use musicbrainz_rs::entity::artist::ArtistType;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{AlbumInfo, ArtistInfo, SongInfo};
+
+use super::super::models::{self, ImageSize, Tag};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestKind {
+    Album,
+    Artist,
+    Lyrics,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Request<K: Serialize> {
+    pub version: u32,
+    pub kind: RequestKind,
+    pub key: K,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ArtistKey<'a> {
+    pub name: &'a str,
+    pub sort_tag: Option<&'a str>,
+    pub mbid: Option<&'a str>,
+    pub is_composer: bool,
+}
+
+impl<'a> From<&'a ArtistInfo> for ArtistKey<'a> {
+    fn from(info: &'a ArtistInfo) -> Self {
+        Self {
+            name: &info.name,
+            sort_tag: info.sort_tag.as_deref(),
+            mbid: info.mbid.as_deref(),
+            is_composer: info.is_composer,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct AlbumKey<'a> {
+    pub title: &'a str,
+    pub albumsort: Option<&'a str>,
+    /// The original AlbumArtist tag
+    pub artist: Option<&'a str>,
+    pub artistsort: Option<&'a str>,
+    /// Individual album artists, parsed from the above
+    pub artists: Vec<ArtistKey<'a>>,
+    /// Relative to MPD's music folder
+    pub folder_uri: &'a str,
+    pub example_uri: &'a str,
+    /// YYYY-MM-DD
+    pub release_date: Option<String>,
+    pub mbid: Option<&'a str>,
+}
+
+impl<'a> From<&'a AlbumInfo> for AlbumKey<'a> {
+    fn from(info: &'a AlbumInfo) -> Self {
+        Self {
+            title: &info.title,
+            albumsort: info.albumsort.as_deref(),
+            artist: info.albumartist.as_deref(),
+            artistsort: info.albumartistsort.as_deref(),
+            artists: info.artists.iter().map(ArtistKey::from).collect(),
+            folder_uri: &info.folder_uri,
+            example_uri: &info.example_uri,
+            release_date: info.release_date.map(|date| date.to_string()),
+            mbid: info.mbid.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct SongKey<'a> {
+    pub uri: &'a str,
+    pub title: &'a str,
+    /// The original Artist tag
+    pub artist: Option<&'a str>,
+    pub artists: Vec<ArtistKey<'a>>,
+    pub album: Option<AlbumKey<'a>>,
+    /// In seconds
+    pub duration: Option<f64>,
+    pub track: Option<i64>,
+    pub disc: Option<i64>,
+    /// YYYY-MM-DD
+    pub release_date: Option<String>,
+}
+
+impl<'a> From<&'a SongInfo> for SongKey<'a> {
+    fn from(info: &'a SongInfo) -> Self {
+        let positive = |val: i64| if val > 0 { Some(val) } else { None };
+        Self {
+            uri: &info.uri,
+            title: &info.title,
+            artist: info.artist_tag.as_deref(),
+            artists: info.artists.iter().map(ArtistKey::from).collect(),
+            album: info.album.as_ref().map(AlbumKey::from),
+            duration: info.duration.map(|dur| dur.as_secs_f64()),
+            track: positive(info.track.get()),
+            disc: positive(info.disc.get()),
+            release_date: info.release_date.map(|date| date.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Image {
+    pub url: String,
+    /// One of Small, Medium, Large, ExtraLarge or Mega. Defaults to ExtraLarge.
+    pub size: Option<ImageSize>,
+}
+
+impl From<Image> for models::ImageMeta {
+    fn from(img: Image) -> Self {
+        Self {
+            size: img.size.unwrap_or(ImageSize::ExtraLarge),
+            url: img.url,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Wiki {
+    pub content: String,
+    pub url: Option<String>,
+    /// Defaults to the plugin's name.
+    pub attribution: Option<String>,
+}
+
+impl Wiki {
+    fn into_wiki(self, plugin_name: &str) -> models::Wiki {
+        models::Wiki {
+            content: self.content,
+            url: self.url,
+            attribution: self.attribution.unwrap_or_else(|| plugin_name.to_owned()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct AlbumResponse {
+    pub mbid: Option<String>,
+    pub tags: Vec<Tag>,
+    pub image: Vec<Image>,
+    pub url: Option<String>,
+    pub wiki: Option<Wiki>,
+}
+
+impl AlbumResponse {
+    pub fn into_meta(self, key: &AlbumInfo, plugin_name: &str) -> models::AlbumMeta {
+        let mut meta = models::AlbumMeta::from_key(key);
+        // Keep our own names, else we won't be able to query the result back.
+        meta.artist = key.get_artist_tag().map(str::to_owned);
+        if meta.mbid.is_none() {
+            meta.mbid = self.mbid;
+        }
+        meta.tags = self.tags;
+        meta.image = self.image.into_iter().map(models::ImageMeta::from).collect();
+        meta.url = self.url;
+        meta.wiki = self.wiki.map(|wiki| wiki.into_wiki(plugin_name));
+        meta
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ArtistResponse {
+    pub mbid: Option<String>,
+    pub tags: Vec<Tag>,
+    pub image: Vec<Image>,
+    pub url: Option<String>,
+    pub bio: Option<Wiki>,
+    /// One of person, group, orchestra, choir or character.
+    pub artist_type: Option<String>,
+    pub gender: Option<String>,
+    /// YYYY-MM-DD
+    pub begin_date: Option<NaiveDate>,
+    /// YYYY-MM-DD
+    pub end_date: Option<NaiveDate>,
+    /// Two-letter country code
+    pub country: Option<String>,
+}
+
+impl ArtistResponse {
+    pub fn into_meta(self, key: &ArtistInfo, plugin_name: &str) -> models::ArtistMeta {
+        let mut meta = models::ArtistMeta::from_key(key);
+        if meta.mbid.is_none() {
+            meta.mbid = self.mbid;
+        }
+        meta.tags = self.tags;
+        meta.image = self.image.into_iter().map(models::ImageMeta::from).collect();
+        meta.url = self.url;
+        meta.bio = self.bio.map(|bio| bio.into_wiki(plugin_name));
+        meta.artist_type = self
+            .artist_type
+            .as_deref()
+            .map_or(ArtistType::Other, models::parse_artist_type);
+        meta.gender = self.gender;
+        meta.begin_date = self.begin_date;
+        meta.end_date = self.end_date;
+        meta.country = self.country;
+        meta
+    }
+}
+
+/// Either raw LRC or plain text, or already-parsed lines.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum LyricsResponse {
+    Text {
+        text: String,
+    },
+    Lines {
+        synced: bool,
+        /// Timestamp in seconds (0 if not synced) and line
+        lines: Vec<(f32, String)>,
+    },
+}
+
+impl LyricsResponse {
+    pub fn into_lyrics(self) -> Option<models::Lyrics> {
+        match self {
+            Self::Text { text } => models::Lyrics::try_from_lrc_or_plain_str(&text),
+            Self::Lines { synced, lines } => {
+                if lines.is_empty() {
+                    None
+                } else {
+                    Some(models::Lyrics { lines, words: Vec::new(), tags: Vec::new(), synced })
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+// Per-provider rate limiting & retrying.
+// Each remote provider has its own token bucket, refilled at the rate given by its
+// "requests-per-second" setting (0 for unlimited). Since providers are called from
+// several worker threads at once, requests block until their provider has a token.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use gio::prelude::SettingsExt;
+use once_cell::sync::Lazy;
+use reqwest::{
+    blocking::{RequestBuilder, Response},
+    header::RETRY_AFTER,
+    StatusCode,
+};
+
+use crate::utils::meta_provider_settings;
+
+/// How many times to retry a request that was rate-limited or hit a server error.
+const MAX_RETRIES: u32 = 3;
+/// Backoff before the first retry when the server doesn't say how long to wait.
+/// Doubles with each retry.
+const BASE_BACKOFF_MS: u64 = 1000;
+/// Never wait longer than this between retries, whatever Retry-After says.
+const MAX_BACKOFF_S: u64 = 60;
+
+struct TokenBucket {
+    /// Tokens per second
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        // Allow short bursts for providers that permit several requests per second,
+        // but never more than one request at once for those that don't.
+        let capacity = rate.max(1.0);
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token, or return how long until one becomes available.
+    fn try_take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.last_refill).as_secs_f64() * self.rate)
+            .min(self.capacity);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
+
+static BUCKETS: Lazy<Mutex<HashMap<String, Arc<Mutex<TokenBucket>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Block until the given provider may send another request.
+pub fn throttle(provider_key: &str) {
+    let rate = meta_provider_settings(provider_key).double("requests-per-second");
+    if rate <= 0.0 {
+        return;
+    }
+    let bucket = {
+        let mut buckets = BUCKETS.lock().unwrap();
+        let bucket = buckets
+            .entry(provider_key.to_owned())
+            .or_insert_with(|| Arc::new(Mutex::new(TokenBucket::new(rate))));
+        // Pick up rate changes from the preferences
+        if bucket.lock().unwrap().rate != rate {
+            *bucket = Arc::new(Mutex::new(TokenBucket::new(rate)));
+        }
+        bucket.clone()
+    };
+    loop {
+        // Don't hold the lock while sleeping, so that the waits of other threads
+        // are computed against up-to-date state.
+        let res = bucket.lock().unwrap().try_take();
+        match res {
+            Ok(()) => return,
+            Err(wait) => thread::sleep(wait),
+        }
+    }
+}
+
+/// How long the response asks us to wait before retrying, if it says so. Retry-After
+/// may be given either in seconds or as an HTTP date.
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let val = resp.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = val.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(val).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(BASE_BACKOFF_MS << attempt)
+}
+
+/// Send a request on behalf of the given provider, respecting its rate limit. Requests
+/// that are rate-limited (429) or hit a server error (5xx), as well as those that fail to
+/// connect or time out, are retried with exponential backoff.
+pub fn send_with_retry(provider_key: &str, request: RequestBuilder) -> reqwest::Result<Response> {
+    let mut attempt: u32 = 0;
+    loop {
+        // Requests with streaming bodies can't be cloned, in which case we don't retry.
+        let Some(this_try) = request.try_clone().filter(|_| attempt < MAX_RETRIES) else {
+            throttle(provider_key);
+            return request.send();
+        };
+        throttle(provider_key);
+        let wait = match this_try.send() {
+            Ok(resp)
+                if resp.status() == StatusCode::TOO_MANY_REQUESTS || resp.status().is_server_error() =>
+            {
+                retry_after(&resp).unwrap_or_else(|| backoff(attempt))
+            }
+            Err(e) if e.is_timeout() || e.is_connect() => backoff(attempt),
+            res => return res,
+        };
+        let wait = wait.min(Duration::from_secs(MAX_BACKOFF_S));
+        attempt += 1;
+        println!("[{provider_key}] Request failed, retrying in {wait:?} (attempt {attempt}/{MAX_RETRIES})");
+        thread::sleep(wait);
+    }
+}
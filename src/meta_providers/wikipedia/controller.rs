@@ -0,0 +1,265 @@
+use gtk::{glib, prelude::*};
+use musicbrainz_rs::entity::artist::ArtistType;
+use reqwest::{blocking::Client, header::USER_AGENT};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    common::{AlbumInfo, ArtistInfo, SongInfo},
+    config::APPLICATION_USER_AGENT,
+    utils::meta_provider_settings,
+};
+
+use super::{
+    super::{models, prelude::*},
+    models::{items, props, Entity, EntitiesResponse, SearchResponse, SummaryResponse},
+    PROVIDER_KEY,
+};
+
+const ATTRIBUTION: &str = "Text from Wikipedia, available under CC BY-SA 4.0.";
+
+/// Fetches artist bios & album descriptions from Wikipedia, in the user's language
+/// where available. Articles are found through Wikidata using the MusicBrainz IDs
+/// provided by earlier providers, so this must come after MusicBrainz in the chain.
+pub struct WikipediaWrapper {
+    client: Client,
+}
+
+impl WikipediaWrapper {
+    fn get_json<T: DeserializeOwned>(&self, url: &str, params: &[(&str, &str)]) -> Option<T> {
+        println!("[Wikipedia] Calling {url} with query {params:?}");
+        let resp = send_with_retry(
+            PROVIDER_KEY,
+            self.client
+                .get(url)
+                .query(params)
+                .header(USER_AGENT, APPLICATION_USER_AGENT),
+        )
+        .and_then(|resp| resp.error_for_status());
+        match resp.and_then(|resp| resp.json::<T>()) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                println!("[Wikipedia] {e:?}");
+                None
+            }
+        }
+    }
+
+    fn wikidata_api(&self, params: &[(&str, &str)]) -> Option<serde_json::Value> {
+        let root = meta_provider_settings(PROVIDER_KEY).string("wikidata-url");
+        let mut all_params = vec![("format", "json")];
+        all_params.extend_from_slice(params);
+        self.get_json(&format!("{}/w/api.php", root.trim_end_matches('/')), &all_params)
+    }
+
+    /// Find the Wikidata item with the given external ID property value.
+    fn find_item(&self, prop: &str, val: &str) -> Option<String> {
+        let query = format!("haswbstatement:{prop}={val}");
+        let resp = self.wikidata_api(&[
+            ("action", "query"),
+            ("list", "search"),
+            ("srlimit", "1"),
+            ("srsearch", &query),
+        ])?;
+        serde_json::from_value::<SearchResponse>(resp)
+            .ok()?
+            .query
+            .search
+            .into_iter()
+            .next()
+            .map(|res| res.title)
+    }
+
+    fn get_entity(&self, qid: &str, props: &str) -> Option<Entity> {
+        let resp = self.wikidata_api(&[("action", "wbgetentities"), ("ids", qid), ("props", props)])?;
+        serde_json::from_value::<EntitiesResponse>(resp)
+            .ok()?
+            .entities
+            .remove(qid)
+    }
+
+    /// Fetch the summary of the entity's article in the first of the user's languages it's
+    /// available in.
+    fn get_summary(&self, entity: &Entity) -> Option<models::Wiki> {
+        let template = meta_provider_settings(PROVIDER_KEY).string("wikipedia-url");
+        for lang in languages() {
+            let Some(title) = entity.sitelink(&format!("{}wiki", lang.replace('-', "_"))) else {
+                continue;
+            };
+            let url = format!(
+                "{}/api/rest_v1/page/summary/{}",
+                template.replace("{lang}", &lang).trim_end_matches('/'),
+                urlencoding::encode(&title.replace(' ', "_"))
+            );
+            let Some(summary) = self.get_json::<SummaryResponse>(&url, &[]) else {
+                continue;
+            };
+            if summary.page_type == "disambiguation" {
+                continue;
+            }
+            if let Some(extract) = summary.extract.filter(|text| !text.trim().is_empty()) {
+                return Some(models::Wiki {
+                    content: extract,
+                    url: summary.content_urls.map(|urls| urls.desktop.page),
+                    attribution: ATTRIBUTION.to_owned(),
+                });
+            }
+        }
+        None
+    }
+
+    /// Two-letter code of the first country item that has one.
+    fn get_country_code(&self, qids: &[&str]) -> Option<String> {
+        qids.iter().find_map(|qid| {
+            self.get_entity(qid, "claims")?
+                .string(props::ISO_3166_ALPHA_2)
+                .map(str::to_owned)
+        })
+    }
+
+    /// Wikidata is more likely to link to release groups than specific releases,
+    /// so if the release itself isn't found, try again with its release group
+    /// (as found by the MusicBrainz provider earlier in the chain).
+    fn find_album_item(&self, release_mbid: &str, release_group_mbid: Option<&str>) -> Option<String> {
+        if let Some(qid) = self.find_item(props::MB_RELEASE_ID, release_mbid) {
+            return Some(qid);
+        }
+        self.find_item(props::MB_RELEASE_GROUP_ID, release_group_mbid?)
+    }
+}
+
+/// Wikipedia language codes to try, in order of preference. Taken from the user's locale,
+/// with English as the last resort.
+fn languages() -> Vec<String> {
+    let mut langs: Vec<String> = Vec::new();
+    for name in glib::language_names() {
+        let lang = name
+            .split(['_', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        // Wikipedia's codes differ from ISO 639-1 for a few languages
+        let lang = match lang.as_str() {
+            "nb" => "no".to_owned(),
+            "c" | "posix" | "" => continue,
+            _ => lang,
+        };
+        if !langs.contains(&lang) {
+            langs.push(lang);
+        }
+    }
+    if !langs.iter().any(|lang| lang == "en") {
+        langs.push("en".to_owned());
+    }
+    langs
+}
+
+fn commons_image(file_name: &str) -> models::ImageMeta {
+    let root = meta_provider_settings(PROVIDER_KEY).string("commons-url");
+    models::ImageMeta {
+        size: models::ImageSize::Mega,
+        url: format!(
+            "{}/w/thumb.php?f={}&w=512",
+            root.trim_end_matches('/'),
+            urlencoding::encode(&file_name.replace(' ', "_"))
+        ),
+    }
+}
+
+impl MetadataProvider for WikipediaWrapper {
+    fn new() -> Self {
+        Self {
+            client: Client::new(),
+        }
+    }
+
+    fn get_album_meta(
+        &self,
+        key: &mut AlbumInfo,
+        existing: Option<models::AlbumMeta>,
+    ) -> Option<models::AlbumMeta> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return existing;
+        }
+        let Some(mbid) = key.mbid.clone() else {
+            println!("[Wikipedia] An MBID is required to look up albums");
+            return existing;
+        };
+        let release_group_mbid = existing.as_ref().and_then(|meta| meta.release_group_mbid.clone());
+        let Some(entity) = self
+            .find_album_item(&mbid, release_group_mbid.as_deref())
+            .and_then(|qid| self.get_entity(&qid, "sitelinks|claims"))
+        else {
+            return existing;
+        };
+        let mut new = models::AlbumMeta::from_key(key);
+        new.artist = key.get_artist_tag().map(str::to_owned);
+        new.wiki = self.get_summary(&entity);
+        if let Some(file_name) = entity.string(props::IMAGE) {
+            new.image.push(commons_image(file_name));
+        }
+        if let Some(old) = existing {
+            Some(old.merge(new))
+        } else {
+            Some(new)
+        }
+    }
+
+    fn get_artist_meta(
+        &self,
+        key: &mut ArtistInfo,
+        existing: Option<models::ArtistMeta>,
+    ) -> Option<models::ArtistMeta> {
+        let settings = meta_provider_settings(PROVIDER_KEY);
+        if !settings.boolean("enabled") {
+            return existing;
+        }
+        let Some(mbid) = key.mbid.clone() else {
+            println!("[Wikipedia] An MBID is required to look up artists");
+            return existing;
+        };
+        let Some(entity) = self
+            .find_item(props::MB_ARTIST_ID, &mbid)
+            .and_then(|qid| self.get_entity(&qid, "sitelinks|claims"))
+        else {
+            return existing;
+        };
+        let mut new = models::ArtistMeta::from_key(key);
+        new.bio = self.get_summary(&entity);
+
+        let kinds = entity.item_ids(props::INSTANCE_OF);
+        if kinds.contains(&items::HUMAN) {
+            new.artist_type = ArtistType::Person;
+            new.begin_date = entity.date(props::BIRTH);
+            new.end_date = entity.date(props::DEATH);
+            new.gender = entity.item_ids(props::GENDER).first().and_then(|id| match *id {
+                items::MALE => Some("male".to_owned()),
+                items::FEMALE => Some("female".to_owned()),
+                _ => None,
+            });
+            new.country = self.get_country_code(&entity.item_ids(props::CITIZENSHIP));
+        } else {
+            if kinds.contains(&items::MUSICAL_GROUP) || kinds.contains(&items::BAND) {
+                new.artist_type = ArtistType::Group;
+            }
+            new.begin_date = entity.date(props::INCEPTION);
+            new.end_date = entity.date(props::DISSOLVED);
+            new.country = self.get_country_code(&entity.item_ids(props::COUNTRY_OF_ORIGIN));
+        }
+
+        if settings.boolean("download-artist-avatar") {
+            if let Some(file_name) = entity.string(props::IMAGE) {
+                new.image.push(commons_image(file_name));
+            }
+        }
+        if let Some(old) = existing {
+            Some(old.merge(new))
+        } else {
+            Some(new)
+        }
+    }
+
+    /// Wikipedia does not provide lyrics.
+    fn get_lyrics(&self, _key: &SongInfo) -> Option<models::Lyrics> {
+        None
+    }
+}
@@ -0,0 +1,6 @@
+static PROVIDER_KEY: &str = "wikipedia";
+
+mod controller;
+mod models;
+
+pub use controller::WikipediaWrapper;
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use serde_json::Value;
+
+// Wikidata & Wikipedia JSON structs, for deserialising API responses only.
+// Widgets should use the standard structs defined in the supercrate's models.rs.
+
+/// Wikidata properties we read.
+pub mod props {
+    pub const MB_ARTIST_ID: &str = "P434";
+    pub const MB_RELEASE_GROUP_ID: &str = "P436";
+    pub const MB_RELEASE_ID: &str = "P5813";
+    pub const INSTANCE_OF: &str = "P31";
+    pub const IMAGE: &str = "P18";
+    pub const GENDER: &str = "P21";
+    pub const CITIZENSHIP: &str = "P27";
+    pub const COUNTRY_OF_ORIGIN: &str = "P495";
+    pub const BIRTH: &str = "P569";
+    pub const DEATH: &str = "P570";
+    pub const INCEPTION: &str = "P571";
+    pub const DISSOLVED: &str = "P576";
+    pub const ISO_3166_ALPHA_2: &str = "P297";
+}
+
+/// Wikidata items we compare against.
+pub mod items {
+    pub const HUMAN: &str = "Q5";
+    pub const MUSICAL_GROUP: &str = "Q215380";
+    pub const BAND: &str = "Q5741069";
+    pub const MALE: &str = "Q6581097";
+    pub const FEMALE: &str = "Q6581072";
+}
+
+// action=query&list=search
+#[derive(Deserialize, Debug)]
+pub struct SearchResponse {
+    pub query: SearchQuery,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchQuery {
+    pub search: Vec<SearchResult>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchResult {
+    /// The item's QID
+    pub title: String,
+}
+
+// action=wbgetentities
+#[derive(Deserialize, Debug)]
+pub struct EntitiesResponse {
+    pub entities: HashMap<String, Entity>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Sitelink {
+    pub title: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Snak {
+    pub datavalue: Option<DataValue>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DataValue {
+    pub value: Value,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Claim {
+    pub mainsnak: Snak,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Entity {
+    #[serde(default)]
+    pub sitelinks: HashMap<String, Sitelink>,
+    #[serde(default)]
+    pub claims: HashMap<String, Vec<Claim>>,
+}
+
+impl Entity {
+    fn values(&self, prop: &str) -> impl Iterator<Item = &Value> {
+        self.claims
+            .get(prop)
+            .into_iter()
+            .flatten()
+            .filter_map(|claim| claim.mainsnak.datavalue.as_ref().map(|dv| &dv.value))
+    }
+
+    /// First value of a string-typed property, such as a file name or an external ID.
+    pub fn string(&self, prop: &str) -> Option<&str> {
+        self.values(prop).find_map(Value::as_str)
+    }
+
+    /// QIDs of an item-typed property.
+    pub fn item_ids(&self, prop: &str) -> Vec<&str> {
+        self.values(prop)
+            .filter_map(|val| val.get("id").and_then(Value::as_str))
+            .collect()
+    }
+
+    /// First value of a time-typed property. Parts beyond the value's precision are
+    /// zeroed out by Wikidata, in which case they're set to 1 instead.
+    pub fn date(&self, prop: &str) -> Option<NaiveDate> {
+        self.values(prop)
+            .find_map(|val| val.get("time").and_then(Value::as_str))
+            .and_then(parse_time)
+    }
+
+    /// Title of the article on the given wiki (e.g. "enwiki"), if there is one.
+    pub fn sitelink(&self, site: &str) -> Option<&str> {
+        self.sitelinks.get(site).map(|link| link.title.as_str())
+    }
+}
+
+/// Parse a Wikidata timestamp such as +1965-03-00T00:00:00Z. BCE dates are not supported.
+fn parse_time(val: &str) -> Option<NaiveDate> {
+    let date = val.strip_prefix('+')?.split('T').next()?;
+    let mut parts = date.split('-').map(|part| part.parse::<u32>().ok());
+    let year = parts.next()??;
+    let month = parts.next()??.max(1);
+    let day = parts.next()??.max(1);
+    NaiveDate::from_ymd_opt(year as i32, month, day)
+}
+
+// Wikipedia REST API page summaries
+#[derive(Deserialize, Debug)]
+pub struct SummaryResponse {
+    #[serde(rename = "type")]
+    pub page_type: String,
+    pub extract: Option<String>,
+    pub content_urls: Option<ContentUrls>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ContentUrls {
+    pub desktop: PageUrls,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PageUrls {
+    pub page: String,
+}
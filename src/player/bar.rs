@@ -14,10 +14,12 @@ use crate::{
 };
 
 use super::{
+    may_feed_snapcast,
     MpdOutput,
     PlaybackControls,
     PlaybackState,
     Player,
+    SnapcastPanel,
     VolumeKnob
 };
 
@@ -66,6 +68,10 @@ mod imp {
         #[template_child]
         pub next_output: TemplateChild<gtk::Button>,
         #[template_child]
+        pub snapcast_btn: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub snapcast_panel: TemplateChild<SnapcastPanel>,
+        #[template_child]
         pub goto_pane: TemplateChild<gtk::Button>,
         #[template_child]
         pub vol_knob: TemplateChild<VolumeKnob>,
@@ -144,6 +150,12 @@ mod imp {
                 .sync_create()
                 .build();
 
+            // The panel hides itself when there is no snapserver to show
+            self.snapcast_panel
+                .bind_property("visible", &self.snapcast_btn.get(), "visible")
+                .sync_create()
+                .build();
+
             obj.bind_property("collapsed", &self.vol_knob.get(), "visible")
                 .invert_boolean()
                 .sync_create()
@@ -197,6 +209,7 @@ impl PlayerBar {
         self.bind_state(player);
         self.imp().playback_controls.setup(player);
         self.imp().seekbar.setup(player);
+        self.imp().snapcast_panel.bind(player.snapcast());
     }
 
     fn setup_volume_knob(&self, player: &Player) {
@@ -376,6 +389,9 @@ impl PlayerBar {
         let section = self.imp().output_section.get();
         let stack = self.imp().output_stack.get();
         let new_len = outputs.len();
+        self.imp().snapcast_panel.set_feeds_snapcast(
+            outputs.iter().any(|o| may_feed_snapcast(&o.borrow::<mpd::Output>().plugin)),
+        );
         if new_len == 0 {
             section.set_visible(false);
         } else {
@@ -2,8 +2,8 @@ extern crate mpd;
 use crate::{
     application::EuphonicaApplication,
     cache::{get_image_cache_path, sqlite, Cache, CacheState},
-    client::{ClientState, ConnectionState, MpdWrapper, StickerSetMode},
-    common::{CoverSource, QualityGrade, Song, SongInfo, Stickers},
+    client::{BackgroundTask, ClientState, ConnectionState, MpdWrapper, StickerSetMode},
+    common::{is_stream_uri, stream_title, AutoDjSource, CoverSource, PodcastEpisode, QualityGrade, QueueSnapshot, RadioStation, Song, SongInfo, Stickers},
     config::APPLICATION_ID,
     meta_providers::models::Lyrics,
     utils::{current_unix_timestamp, prettify_audio_format, settings_manager, strip_filename_linux}
@@ -23,15 +23,30 @@ use gtk::{gio, glib, prelude::*};
 use mpd::{
     error::Error as MpdError, status::{AudioFormat, State, Status}, ReplayGain, SaveMode, Subsystem
 };
+use rand::seq::SliceRandom;
+use rustc_hash::{FxHashMap, FxHashSet};
 use std::{
     cell::{Cell, OnceCell, RefCell},
     ops::Deref, path::PathBuf,
-    rc::Rc, sync::{Arc, Mutex, OnceLock}, vec::Vec,
+    rc::Rc, str::FromStr, sync::{Arc, Mutex, OnceLock}, time::{Duration, Instant}, vec::Vec,
 };
 
 use super::fft_backends::{
     backend::{FftBackendExt, FftStatus}, FifoFftBackend, PipeWireFftBackend
 };
+use super::queue_history::{moves_towards, QueueEdit, QUEUE_HISTORY_LEN};
+use super::snapcast::Snapcast;
+
+/// How often to save the resume position of the podcast episode being played, in seconds.
+const PODCAST_SAVE_INTERVAL_S: f64 = 15.0;
+/// Episodes stopped within this many seconds of their end count as listened.
+const PODCAST_LISTENED_MARGIN_S: f64 = 60.0;
+/// How often to follow word-level lyrics timings, in milliseconds. Status polling is too
+/// coarse for those.
+const LYRIC_WORD_TICK_MS: u64 = 100;
+// How close to its end (on top of crossfade) a song must have been at the last status
+// update for a song change to count as it finishing by itself. We poll once every second.
+const AUTO_DJ_END_SLACK_S: f64 = 2.5;
 
 #[derive(Clone, Copy, Debug, glib::Enum, PartialEq, Default)]
 #[enum_type(name = "EuphonicaPlaybackState")]
@@ -123,6 +138,30 @@ impl PlaybackFlow {
     }
 }
 
+impl FromStr for PlaybackFlow {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sequential" => Ok(Self::Sequential),
+            "repeat" => Ok(Self::Repeat),
+            "single" => Ok(Self::Single),
+            "repeat-single" => Ok(Self::RepeatSingle),
+            _ => Err(())
+        }
+    }
+}
+
+impl PlaybackFlow {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Sequential => "sequential",
+            Self::Repeat => "repeat",
+            Self::Single => "single",
+            Self::RepeatSingle => "repeat-single"
+        }
+    }
+}
+
 impl From<PlaybackFlow> for LoopStatus {
     fn from(pf: PlaybackFlow) -> Self {
         match pf {
@@ -143,6 +182,17 @@ impl From<LoopStatus> for PlaybackFlow {
     }
 }
 
+/// Replace the first matching prefix of a song URI, for servers whose music folders are
+/// laid out differently.
+fn remap_uri(uri: &str, prefixes: &[(String, String)]) -> String {
+    for (from, to) in prefixes.iter() {
+        if let Some(rest) = uri.strip_prefix(from.as_str()) {
+            return format!("{to}{rest}");
+        }
+    }
+    uri.to_owned()
+}
+
 fn cycle_replaygain(curr: ReplayGain) -> ReplayGain {
     match curr {
         ReplayGain::Off => ReplayGain::Auto,
@@ -181,11 +231,26 @@ mod imp {
         pub lyrics: RefCell<Option<Lyrics>>,
         pub queue_len: Cell<u32>,
         pub current_song: RefCell<Option<Song>>,
+        // Saved radio station matching the current song, if it's a stream we know of.
+        pub radio_station: RefCell<Option<RadioStation>>,
+        // Podcast episode matching the current song, if any, and the position at which its
+        // progress was last saved.
+        pub podcast_episode: RefCell<Option<PodcastEpisode>>,
+        pub podcast_saved_pos: Cell<f64>,
         pub current_lyric_line: Cell<u32>,
+        // Word being sung in the current lyric line, or -1. Only tracked for lyrics with
+        // word timings.
+        pub current_lyric_word: Cell<i32>,
+        // When the position was last set, for estimating it between polls.
+        pub position_updated_at: Cell<Option<Instant>>,
+        // Handle to the word timings task
+        pub lyric_ticker: RefCell<Option<glib::JoinHandle<()>>>,
         pub format: RefCell<Option<AudioFormat>>,
         pub bitrate: Cell<u32>,
         pub flow: Cell<PlaybackFlow>,
         pub random: Cell<bool>,
+        // Client-side: MPD has no notion of albums in its playback order.
+        pub album_shuffle: Cell<bool>,
         pub consume: Cell<bool>,
         pub replaygain: Cell<ReplayGain>,
         pub crossfade: Cell<f64>,
@@ -220,7 +285,23 @@ mod imp {
         // to the bar & pane.
         pub cover_source: Cell<CoverSource>,
         pub saved_to_history: Cell<bool>,
-        pub is_foreground: Cell<bool>
+        pub is_foreground: Cell<bool>,
+        // Currently running Auto-DJ source, if any. Used to keep the queue topped up.
+        pub auto_dj: RefCell<Option<AutoDjSource>>,
+        // Queue length at which we last requested a top-up, so that we don't
+        // spam the background thread while waiting for the new songs to arrive.
+        pub topup_requested_at_len: Cell<Option<u32>>,
+        // Queue ID of the song that was playing at the last status update & how many seconds
+        // it had left, for removing songs that finished playing.
+        pub auto_dj_last_id: Cell<Option<u32>>,
+        pub auto_dj_last_remaining: Cell<f64>,
+        // Set when the user jumps to another song, so that the song being left isn't
+        // mistaken for a finished one.
+        pub auto_dj_skipped: Cell<bool>,
+        // Undo & redo stacks for queue edits made from this client.
+        pub queue_undo: RefCell<Vec<QueueEdit>>,
+        pub queue_redo: RefCell<Vec<QueueEdit>>,
+        pub snapcast: Snapcast
     }
 
     #[glib::object_subclass]
@@ -239,6 +320,7 @@ mod imp {
                 lyric_lines: gtk::StringList::new(&[]),
                 lyrics: RefCell::new(None),
                 random: Cell::new(false),
+                album_shuffle: Cell::new(settings_manager().child("player").boolean("album-shuffle")),
                 consume: Cell::new(false),
                 supports_playlists: Cell::new(false),
                 replaygain: Cell::new(ReplayGain::Off),
@@ -248,7 +330,13 @@ mod imp {
                 queue: gio::ListStore::new::<Song>(),
                 queue_len: Cell::new(0),
                 current_song: RefCell::new(None),
+                radio_station: RefCell::new(None),
+                podcast_episode: RefCell::new(None),
+                podcast_saved_pos: Cell::new(0.0),
                 current_lyric_line: Cell::default(),
+                current_lyric_word: Cell::new(-1),
+                position_updated_at: Cell::new(None),
+                lyric_ticker: RefCell::new(None),
                 format: RefCell::new(None),
                 bitrate: Cell::default(),
                 flow: Cell::default(),
@@ -281,7 +369,15 @@ mod imp {
                 outputs: gio::ListStore::new::<BoxedAnyObject>(),
                 cover_source: Cell::default(),
                 saved_to_history: Cell::new(false),
-                is_foreground: Cell::new(false)
+                is_foreground: Cell::new(false),
+                auto_dj: RefCell::new(None),
+                topup_requested_at_len: Cell::new(None),
+                auto_dj_last_id: Cell::new(None),
+                auto_dj_last_remaining: Cell::new(f64::INFINITY),
+                auto_dj_skipped: Cell::new(false),
+                queue_undo: RefCell::new(Vec::new()),
+                queue_redo: RefCell::new(Vec::new()),
+                snapcast: Snapcast::default()
             }
         }
     }
@@ -348,11 +444,17 @@ mod imp {
                     ParamSpecFloat::builder("mixramp-db").build(),
                     ParamSpecDouble::builder("mixramp-delay").build(), // seconds
                     ParamSpecBoolean::builder("random").build(),
+                    ParamSpecBoolean::builder("album-shuffle").build(),
                     ParamSpecBoolean::builder("consume").build(),
                     ParamSpecBoolean::builder("supports-playlists").build(),
                     ParamSpecBoolean::builder("use-visualizer").build(),
                     ParamSpecDouble::builder("position").build(),
                     ParamSpecUInt::builder("current-lyric-line").read_only().build(),
+                    ParamSpecInt::builder("current-lyric-word")
+                        .minimum(-1)
+                        .default_value(-1)
+                        .read_only()
+                        .build(),
                     ParamSpecString::builder("title").read_only().build(),
                     ParamSpecString::builder("artist").read_only().build(),
                     ParamSpecString::builder("album").read_only().build(),
@@ -371,7 +473,10 @@ mod imp {
                         .build(),
                     ParamSpecString::builder("format-desc").read_only().build(),
                     ParamSpecInt::builder("fft-backend-idx").build(),
-                    ParamSpecBoolean::builder("pipewire-restart-between-songs").build()
+                    ParamSpecBoolean::builder("pipewire-restart-between-songs").build(),
+                    ParamSpecString::builder("auto-dj").read_only().build(),  // Description of the current source, empty when off
+                    ParamSpecBoolean::builder("can-undo-queue").read_only().build(),
+                    ParamSpecBoolean::builder("can-redo-queue").read_only().build()
                 ]
             });
             PROPERTIES.as_ref()
@@ -383,6 +488,7 @@ mod imp {
                 "playback-state" => self.state.get().to_value(),
                 "playback-flow" => self.flow.get().to_value(),
                 "random" => self.random.get().to_value(),
+                "album-shuffle" => self.album_shuffle.get().to_value(),
                 "consume" => self.consume.get().to_value(),
                 "supports-playlists" => self.supports_playlists.get().to_value(),
                 "use-visualizer" => self.use_visualizer.get().to_value(),
@@ -392,6 +498,7 @@ mod imp {
                 "replaygain" => get_replaygain_icon_name(self.replaygain.get()).to_value(),
                 "position" => obj.position().to_value(),
                 "current-lyric-line" => self.current_lyric_line.get().to_value(),
+                "current-lyric-word" => self.current_lyric_word.get().to_value(),
                 // These are proxies for Song properties
                 "title" => obj.title().to_value(),
                 "artist" => obj.artist().to_value(),
@@ -406,6 +513,14 @@ mod imp {
                 "format-desc" => obj.format_desc().to_value(),
                 "fft-backend-idx" => self.fft_backend_idx.get().to_value(),
                 "pipewire-restart-between-songs" => self.pipewire_restart_between_songs.get().to_value(),
+                "auto-dj" => self
+                    .auto_dj
+                    .borrow()
+                    .as_ref()
+                    .map_or(String::new(), |source| source.description())
+                    .to_value(),
+                "can-undo-queue" => (!self.queue_undo.borrow().is_empty()).to_value(),
+                "can-redo-queue" => (!self.queue_redo.borrow().is_empty()).to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -506,6 +621,14 @@ mod imp {
                         .build(),
                     Signal::builder("history-changed")
                         .build(),
+                    // A radio station reported a new stream title, which has been logged.
+                    Signal::builder("radio-history-changed")
+                        .param_types([String::static_type()]) // Station name
+                        .build(),
+                    // The listened state or resume position of a podcast episode was saved.
+                    Signal::builder("podcast-progress-changed")
+                        .param_types([String::static_type()]) // Feed URL
+                        .build(),
                     // For simplicity we'll always use the hires version
                     Signal::builder("cover-changed")
                         .param_types([Option::<gdk::Texture>::static_type()])
@@ -640,6 +763,10 @@ impl Player {
         self.imp().outputs.clone()
     }
 
+    pub fn snapcast(&self) -> &Snapcast {
+        &self.imp().snapcast
+    }
+
     pub fn clear(&self) {
         self.imp().queue.remove_all();
         self.imp().outputs.remove_all();
@@ -664,6 +791,7 @@ impl Player {
     ) {
         let client_state = client.clone().get_client_state();
         let _ = self.imp().client.set(client);
+        self.imp().snapcast.setup();
 
         cache.get_cache_state().connect_closure(
             "album-art-downloaded",
@@ -774,11 +902,13 @@ impl Player {
                             if let Some(status) = this.client().get_status(false) {
                                 this.update_status(&status);
                             }
+                            this.refresh_stream_title();
                         }
                         Subsystem::Queue => {
                             if let Some(status) = this.client().get_status(true) {
                                 this.update_status(&status);
                             }
+                            this.refresh_stream_title();
                         }
                         Subsystem::Output => {
                             if let Some(outs) = this.client().get_outputs() {
@@ -903,6 +1033,7 @@ impl Player {
                 let new_state = PlaybackState::Playing;
                 let old_state = self.imp().state.replace(new_state);
                 self.maybe_start_polling();
+                self.maybe_start_lyric_ticker();
                 if old_state != new_state {
                     self.notify("playback-state");
                     if self.imp().mpris_enabled.get() {
@@ -914,6 +1045,7 @@ impl Player {
                 let new_state = PlaybackState::Paused;
                 let old_state = self.imp().state.replace(new_state);
                 self.stop_polling();
+                self.stop_lyric_ticker();
                 if old_state != new_state {
                     self.notify("playback-state");
                     if self.imp().mpris_enabled.get() {
@@ -925,6 +1057,7 @@ impl Player {
                 let new_state = PlaybackState::Stopped;
                 let old_state = self.imp().state.replace(new_state);
                 self.stop_polling();
+                self.stop_lyric_ticker();
                 if old_state != new_state {
                     self.notify("playback-state");
                     if self.imp().mpris_enabled.get() {
@@ -953,8 +1086,14 @@ impl Player {
         let old_rand = self.imp().random.replace(status.random);
         if old_rand != status.random {
             self.notify("random");
+            if status.random && self.imp().album_shuffle.get() {
+                // Probably turned on from another client. The two are mutually exclusive.
+                self.imp().album_shuffle.set(false);
+                let _ = settings_manager().child("player").set_boolean("album-shuffle", false);
+                self.notify("album-shuffle");
+            }
             if self.imp().mpris_enabled.get() {
-                mpris_changes.push(Property::Shuffle(status.random));
+                mpris_changes.push(Property::Shuffle(self.is_shuffling()));
             }
         }
 
@@ -1064,6 +1203,12 @@ impl Player {
                             &current_unix_timestamp().to_string(),
                             StickerSetMode::Set
                         );
+                        self.imp().radio_station.replace(if is_stream_uri(new_song.get_uri()) {
+                            sqlite::find_radio_station_by_url(new_song.get_uri()).ok().flatten()
+                        } else {
+                            None
+                        });
+                        self.set_podcast_episode(&new_song);
                         local_curr_song.replace(new_song.clone());
                         // If using PipeWire visualiser, might need to restart it
                         if self.imp().pipewire_restart_between_songs.get()
@@ -1105,6 +1250,7 @@ impl Player {
                 }
             }
             if needs_refresh {
+                self.log_stream_title();
                 if let Some(new_song) = self.imp().current_song.borrow().as_ref() {
                     self.imp().saved_to_history.set(false);
                     self.notify("title");
@@ -1117,7 +1263,13 @@ impl Player {
                     self.notify("queue-id");
                     // Get album art. Start with CoverSource::Unknown.
                     // We might also get an asynchronous reply later via a cache state signal.
-                    if let Some((tex, is_fallback)) = self
+                    // Streams have no album art, but saved stations might have a logo.
+                    if is_stream_uri(new_song.get_uri()) {
+                        self.imp().cover_source.set(CoverSource::Unknown);
+                        self.emit_by_name::<()>("cover-changed", &[&Option::<gdk::Texture>::None]);
+                        self.load_station_logo();
+                    }
+                    else if let Some((tex, is_fallback)) = self
                         .imp()
                         .cache
                         .get()
@@ -1134,6 +1286,7 @@ impl Player {
                     }
                     // Get new lyrics
                     // First remove all current lines
+                    self.stop_lyric_ticker();
                     self.imp().lyric_lines.splice(0, self.imp().lyric_lines.n_items(), &[]);
                     let _ = self.imp().lyrics.take();
                     // Fetch new lyrics
@@ -1159,6 +1312,9 @@ impl Player {
             println!("No song playing right now");
             // No song is playing. Update state accordingly.
             if let Some(_) = self.imp().current_song.take() {
+                self.imp().radio_station.take();
+                self.save_podcast_progress();
+                self.imp().podcast_episode.take();
                 self.imp().saved_to_history.set(false);
                 self.notify("title");
                 self.notify("artist");
@@ -1183,6 +1339,14 @@ impl Player {
             if new != old && self.imp().mpris_enabled.get() {
                 self.seek_mpris(new);
             }
+            // Positions too close to the start aren't worth saving. This also keeps us from
+            // overwriting the resume position before the seek to it has gone through.
+            if self.imp().podcast_episode.borrow().is_some()
+                && new >= PODCAST_SAVE_INTERVAL_S
+                && (new - self.imp().podcast_saved_pos.get()).abs() >= PODCAST_SAVE_INTERVAL_S
+            {
+                self.save_podcast_progress();
+            }
             // If using PipeWire visualiser and auto-restart is enabled, stop the thread
             // just before song ends. As we poll once every second, we can't use a threshold
             // shorter than 1s.
@@ -1199,13 +1363,7 @@ impl Player {
         } else {
             self.set_position(0.0);
         }
-        if let Some(lyrics) = self.imp().lyrics.borrow().as_ref() {
-            let new_idx = lyrics.get_line_at_timestamp(self.imp().position.get() as f32) as u32;
-            let old_idx = self.imp().current_lyric_line.replace(new_idx);
-            if new_idx != old_idx {
-                self.notify("current-lyric-line");
-            }
-        }
+        self.update_lyric_position();
 
         // We need to separately keep track of queue length here as the queue list model might
         // not have been initialised yet.
@@ -1226,13 +1384,81 @@ impl Player {
         if self.imp().mpris_enabled.get() {
             self.update_mpris_properties(mpris_changes);
         }
+        self.update_auto_dj();
     }
 
     pub fn update_lyrics(&self, lyrics: Lyrics) {
+        self.stop_lyric_ticker();
         self.imp().current_lyric_line.set(0);
-        self.imp().lyric_lines.splice(0, 0, &lyrics.to_plain_lines());
+        self.imp().current_lyric_word.set(-1);
+        let lyric_lines = &self.imp().lyric_lines;
+        lyric_lines.splice(0, lyric_lines.n_items(), &lyrics.to_plain_lines());
         self.imp().lyrics.replace(Some(lyrics));
         self.notify("current-lyric-line");
+        self.maybe_start_lyric_ticker();
+    }
+
+    /// Best guess of the current position, accounting for the time passed since it was
+    /// last polled.
+    pub fn estimated_position(&self) -> f64 {
+        let pos = self.imp().position.get();
+        match (self.imp().state.get(), self.imp().position_updated_at.get()) {
+            (PlaybackState::Playing, Some(at)) => pos + at.elapsed().as_secs_f64(),
+            _ => pos,
+        }
+    }
+
+    /// Move the current lyric line (and word, if timed) to the playback position.
+    fn update_lyric_position(&self) {
+        let (new_line, new_word) = match self.imp().lyrics.borrow().as_ref() {
+            Some(lyrics) => {
+                let pos = self.estimated_position() as f32;
+                let line = lyrics.get_line_at_timestamp(pos);
+                (
+                    line as u32,
+                    lyrics.get_word_at_timestamp(line, pos).map_or(-1, |word| word as i32)
+                )
+            }
+            None => return,
+        };
+        let old_line = self.imp().current_lyric_line.replace(new_line);
+        let old_word = self.imp().current_lyric_word.replace(new_word);
+        if new_line != old_line {
+            self.notify("current-lyric-line");
+        }
+        if new_line != old_line || new_word != old_word {
+            self.notify("current-lyric-word");
+        }
+    }
+
+    /// Follow word timings more closely than status polling does, if the lyrics have them.
+    /// Only runs while playing: update_status() stops it on pause & restarts it on resume.
+    fn maybe_start_lyric_ticker(&self) {
+        if self.imp().state.get() != PlaybackState::Playing {
+            return;
+        }
+        let has_words = self
+            .imp()
+            .lyrics
+            .borrow()
+            .as_ref()
+            .is_some_and(Lyrics::has_word_timings);
+        if has_words && self.imp().lyric_ticker.borrow().is_none() {
+            let this = self.clone();
+            let handle = glib::MainContext::default().spawn_local(async move {
+                loop {
+                    this.update_lyric_position();
+                    glib::timeout_future(Duration::from_millis(LYRIC_WORD_TICK_MS)).await;
+                }
+            });
+            self.imp().lyric_ticker.replace(Some(handle));
+        }
+    }
+
+    fn stop_lyric_ticker(&self) {
+        if let Some(handle) = self.imp().lyric_ticker.take() {
+            handle.abort();
+        }
     }
 
     /// Returns true if we have lyrics for the current song and it is synced; false otherwise.
@@ -1249,6 +1475,45 @@ impl Player {
         self.imp().current_lyric_line.get()
     }
 
+    /// Timestamps of the current lyrics' lines, if they're synced.
+    pub fn lyric_timestamps(&self) -> Option<Vec<f32>> {
+        self.imp()
+            .lyrics
+            .borrow()
+            .as_ref()
+            .filter(|lyrics| lyrics.synced)
+            .map(|lyrics| lyrics.lines.iter().map(|line| line.0).collect())
+    }
+
+    /// The current lyrics with new line timestamps, keeping word timings and ID tags.
+    pub fn retimed_lyrics(&self, stamps: &[f32]) -> Option<Lyrics> {
+        self.imp()
+            .lyrics
+            .borrow()
+            .as_ref()
+            .map(|lyrics| lyrics.retimed(stamps))
+    }
+
+    pub fn current_lyric_word(&self) -> i32 {
+        self.imp().current_lyric_word.get()
+    }
+
+    /// Words of the given lyric line, if its timing is known word by word.
+    pub fn lyric_line_words(&self, line: u32) -> Vec<String> {
+        self.imp()
+            .lyrics
+            .borrow()
+            .as_ref()
+            .map(|lyrics| {
+                lyrics
+                    .line_words(line as usize)
+                    .iter()
+                    .map(|(_, word)| word.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     pub fn n_lyric_lines(&self) -> u32 {
         self.imp().lyric_lines.n_items()
     }
@@ -1345,9 +1610,104 @@ impl Player {
     }
 
     pub fn set_random(&self, new: bool) {
+        if new {
+            self.set_album_shuffle(false);
+        }
         self.client().set_random(new);
     }
 
+    pub fn album_shuffle(&self) -> bool {
+        self.imp().album_shuffle.get()
+    }
+
+    /// Whether either track or album shuffling is on. This is what MPRIS sees.
+    pub fn is_shuffling(&self) -> bool {
+        self.imp().random.get() || self.imp().album_shuffle.get()
+    }
+
+    /// Cycle between no shuffling, song shuffling (MPD random) and album shuffling.
+    pub fn cycle_shuffle(&self) {
+        if self.imp().album_shuffle.get() {
+            self.set_album_shuffle(false);
+        } else if self.imp().random.get() {
+            self.set_album_shuffle(true);
+        } else {
+            self.set_random(true);
+        }
+    }
+
+    /// Album shuffle plays whole albums in track order, picking the next album at random.
+    /// It is implemented by reordering the queue itself so that other clients (and MPD
+    /// itself, once we disconnect) will keep following it.
+    pub fn set_album_shuffle(&self, new: bool) {
+        let old = self.imp().album_shuffle.replace(new);
+        if old == new {
+            return;
+        }
+        let _ = settings_manager().child("player").set_boolean("album-shuffle", new);
+        if new {
+            if self.imp().random.get() {
+                self.client().set_random(false);
+            }
+            self.shuffle_albums();
+        }
+        self.notify("album-shuffle");
+        if self.imp().mpris_enabled.get() {
+            self.update_mpris_properties(vec![Property::Shuffle(self.is_shuffling())]);
+        }
+    }
+
+    /// Reorder the songs after the current one so that each album is contiguous & in track
+    /// order, with albums in random order. The rest of the current album is kept up next.
+    pub fn shuffle_albums(&self) {
+        let queue = &self.imp().queue;
+        let len = queue.n_items();
+        let start = self.queue_pos().map_or(0, |pos| pos + 1);
+        if start >= len {
+            return;
+        }
+        let album_key = |song: &Song| -> String {
+            if let Some(album) = song.get_album() {
+                format!("{}\u{1f}{}", album.title, album.albumartist.as_deref().unwrap_or_default())
+            } else {
+                // Albumless songs are treated as single-song albums
+                song.get_uri().to_owned()
+            }
+        };
+        let curr_key: Option<String> = self.imp().current_song.borrow().as_ref().map(album_key);
+        let mut group_indices: FxHashMap<String, usize> = FxHashMap::default();
+        let mut curr_album: Vec<Song> = Vec::new();
+        let mut others: Vec<Vec<Song>> = Vec::new();
+        for pos in start..len {
+            let song = queue.item(pos).and_downcast::<Song>().unwrap();
+            let key = album_key(&song);
+            if curr_key.as_ref() == Some(&key) {
+                curr_album.push(song);
+            } else if let Some(idx) = group_indices.get(&key) {
+                others[*idx].push(song);
+            } else {
+                group_indices.insert(key, others.len());
+                others.push(vec![song]);
+            }
+        }
+        others.shuffle(&mut rand::rng());
+        let order = self.queue_ids();
+        self.push_queue_edit(QueueEdit::Reordered {
+            moved: order[start as usize..].iter().copied().collect(),
+            order
+        });
+        let moves: Vec<(u32, u32)> = std::iter::once(curr_album)
+            .chain(others)
+            .flat_map(|mut group| {
+                group.sort_by_key(|song| (song.get_disc(), song.get_track()));
+                group
+            })
+            .enumerate()
+            .map(|(idx, song)| (song.get_queue_id(), start + idx as u32))
+            .collect();
+        self.client().move_ids(&moves);
+    }
+
     pub fn set_consume(&self, new: bool) {
         self.client().set_consume(new);
     }
@@ -1366,6 +1726,12 @@ impl Player {
 
     pub fn title(&self) -> Option<String> {
         if let Some(song) = &*self.imp().current_song.borrow() {
+            // Episode files often come without tags
+            if song.get_name() == song.get_uri() {
+                if let Some(episode) = self.imp().podcast_episode.borrow().as_ref() {
+                    return Some(episode.title.clone());
+                }
+            }
             return Some(song.get_name().to_owned());
         }
         None
@@ -1383,11 +1749,143 @@ impl Player {
             if let Some(album) = song.get_album() {
                 return Some(album.title.clone());
             }
-            return None;
+            // Show which station a stream is from in place of the album
+            if let Some(station) = self.imp().radio_station.borrow().as_ref() {
+                return Some(station.name.clone());
+            }
+            return song.get_info().stream_name.clone();
         }
         None
     }
 
+    /// Streams keep their queue ID when the station moves on to the next title, so refetch
+    /// the current song to pick up new ICY metadata. MPD announces such tag changes through
+    /// idle events, so this is only called from there rather than on every poll.
+    fn refresh_stream_title(&self) {
+        let Some(id) = self
+            .imp()
+            .current_song
+            .borrow()
+            .as_ref()
+            .filter(|song| is_stream_uri(song.get_uri()))
+            .map(|song| song.get_queue_id())
+        else {
+            return;
+        };
+        let Some(song) = self.client().get_song_at_queue_id(id, false) else {
+            return;
+        };
+        let changed = self
+            .imp()
+            .current_song
+            .borrow()
+            .as_ref()
+            .is_some_and(|curr| curr.get_name() != song.get_name());
+        if changed {
+            if self.imp().mpris_enabled.get() {
+                self.update_mpris_properties(vec![Property::Metadata(song.get_mpris_metadata())]);
+            }
+            self.imp().current_song.replace(Some(song));
+            self.notify("title");
+            self.notify("artist");
+            self.notify("album");
+            self.log_stream_title();
+        }
+    }
+
+    /// Log the current stream title into the history of the station being played.
+    fn log_stream_title(&self) {
+        let Some(station_name) = self.imp().radio_station.borrow().as_ref().map(|s| s.name.clone()) else {
+            return;
+        };
+        let title = self
+            .imp()
+            .current_song
+            .borrow()
+            .as_ref()
+            .and_then(|song| stream_title(song.get_info()).map(str::to_owned));
+        if let Some(title) = title {
+            if sqlite::add_radio_title(&station_name, &title).is_ok() {
+                self.emit_by_name::<()>("radio-history-changed", &[&station_name]);
+            }
+        }
+    }
+
+    fn load_station_logo(&self) {
+        let Some(station_name) = self.imp().radio_station.borrow().as_ref().map(|s| s.name.clone()) else {
+            return;
+        };
+        let handle = self.imp().cache.get().unwrap().load_cached_station_logo(&station_name, false);
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                if let Ok(Some(tex)) = handle.await {
+                    // Only if we're still playing that station
+                    if this.imp().radio_station.borrow().as_ref().is_some_and(|s| s.name == station_name) {
+                        this.emit_by_name::<()>("cover-changed", &[&Some(tex)]);
+                    }
+                }
+            }
+        ));
+    }
+
+    /// Saved radio station currently being played, if any.
+    pub fn radio_station(&self) -> Option<RadioStation> {
+        self.imp().radio_station.borrow().clone()
+    }
+
+    /// Called when the current song changes. Saves the progress of the episode being left
+    /// (if any), then resumes the new song where it was left off if it's a podcast episode.
+    fn set_podcast_episode(&self, new_song: &Song) {
+        self.save_podcast_progress();
+        let episode = if is_stream_uri(new_song.get_uri()) {
+            sqlite::find_podcast_episode_by_url(new_song.get_uri()).ok().flatten()
+        } else {
+            None
+        };
+        let Some(mut episode) = episode else {
+            self.imp().podcast_episode.take();
+            return;
+        };
+        if episode.duration.is_none() && new_song.get_duration() > 0 {
+            episode.duration = Some(new_song.get_duration() as f64);
+        }
+        if !episode.listened && episode.position > 0.0 {
+            self.client().seek_current_song(episode.position);
+        }
+        self.imp().podcast_saved_pos.set(episode.position);
+        self.imp().podcast_episode.replace(Some(episode));
+    }
+
+    /// Save the resume position of the podcast episode being played. Episodes played to
+    /// (almost) the end are marked as listened.
+    fn save_podcast_progress(&self) {
+        let position = self.position();
+        let progress = {
+            let mut episode = self.imp().podcast_episode.borrow_mut();
+            let Some(episode) = episode.as_mut() else {
+                return;
+            };
+            episode.listened = episode.listened
+                || episode
+                    .duration
+                    .is_some_and(|dur| position >= dur - PODCAST_LISTENED_MARGIN_S);
+            episode.position = if episode.listened { 0.0 } else { position };
+            (episode.feed_url.clone(), episode.guid.clone(), episode.position, episode.listened)
+        };
+        self.imp().podcast_saved_pos.set(position);
+        let (feed_url, guid, position, listened) = progress;
+        match sqlite::set_podcast_episode_progress(&feed_url, &guid, position, listened) {
+            Ok(()) => {
+                self.emit_by_name::<()>("podcast-progress-changed", &[&feed_url]);
+            }
+            Err(e) => {
+                println!("{e:?}");
+            }
+        }
+    }
+
     pub fn current_song_cover(&self) -> Option<Texture> {
         if let Some(cache) = self.imp().cache.get() {
             if let Some(song) = self.imp().current_song.borrow().as_ref() {
@@ -1474,6 +1972,7 @@ impl Player {
     /// To apply this new position, call seek().
     pub fn set_position(&self, new: f64) -> f64 {
         let old = self.imp().position.replace(new);
+        self.imp().position_updated_at.set(Some(Instant::now()));
         if new != old {
             self.notify("position");
         }
@@ -1544,6 +2043,7 @@ impl Player {
             println!("Stopping PipeWire backend to allow samplerate change...");
             self.maybe_stop_fft_thread(block);
         }
+        self.mark_auto_dj_skip();
         self.client().prev();
     }
 
@@ -1556,13 +2056,241 @@ impl Player {
             println!("Stopping PipeWire backend to allow samplerate change...");
             self.maybe_stop_fft_thread(block);
         }
+        self.mark_auto_dj_skip();
         self.client().next();
     }
 
+    /// Clear the queue. This can be undone, restoring the previous queue and play position.
     pub fn clear_queue(&self) {
+        self.stop_auto_dj();
+        if let Some(snapshot) = self.snapshot_queue() {
+            self.push_queue_edit(snapshot);
+        }
         self.client().clear_queue();
     }
 
+    /// Capture the whole current queue & play position, or None if the queue is empty.
+    fn snapshot_queue(&self) -> Option<QueueEdit> {
+        let uris: Vec<String> = self
+            .imp()
+            .queue
+            .iter::<Song>()
+            .filter_map(|song| song.ok().map(|song| song.get_uri().to_owned()))
+            .collect();
+        if uris.is_empty() {
+            return None;
+        }
+        let state = self.imp().state.get();
+        let resume = if state == PlaybackState::Stopped {
+            None
+        } else {
+            self.queue_pos().map(|pos| (pos, self.position()))
+        };
+        Some(QueueEdit::Replaced { uris, resume, paused: state == PlaybackState::Paused })
+    }
+
+    /// Current queue order, as queue IDs.
+    fn queue_ids(&self) -> Vec<u32> {
+        self.imp()
+            .queue
+            .iter::<Song>()
+            .filter_map(|song| song.ok().map(|song| song.get_queue_id()))
+            .collect()
+    }
+
+    /// Record a queue edit so that it can be undone. Any redo steps are dropped.
+    fn push_queue_edit(&self, edit: QueueEdit) {
+        {
+            let mut undo = self.imp().queue_undo.borrow_mut();
+            undo.push(edit);
+            if undo.len() > QUEUE_HISTORY_LEN {
+                undo.remove(0);
+            }
+        }
+        self.imp().queue_redo.borrow_mut().clear();
+        self.notify("can-undo-queue");
+        self.notify("can-redo-queue");
+    }
+
+    /// Undo the last queue edit made from this client.
+    pub fn undo_queue(&self) {
+        let maybe_edit = self.imp().queue_undo.borrow_mut().pop();
+        if let Some(edit) = maybe_edit {
+            if let Some(inverse) = self.revert_queue_edit(edit) {
+                self.imp().queue_redo.borrow_mut().push(inverse);
+            }
+            self.notify("can-undo-queue");
+            self.notify("can-redo-queue");
+        }
+    }
+
+    /// Redo the last undone queue edit.
+    pub fn redo_queue(&self) {
+        let maybe_edit = self.imp().queue_redo.borrow_mut().pop();
+        if let Some(edit) = maybe_edit {
+            if let Some(inverse) = self.revert_queue_edit(edit) {
+                self.imp().queue_undo.borrow_mut().push(inverse);
+            }
+            self.notify("can-undo-queue");
+            self.notify("can-redo-queue");
+        }
+    }
+
+    /// Revert the given edit, returning the edit that would revert it back. Edits that no
+    /// longer apply (for example because another client changed the queue since) are dropped.
+    fn revert_queue_edit(&self, edit: QueueEdit) -> Option<QueueEdit> {
+        match edit {
+            QueueEdit::Removed(songs) => {
+                self.client().insert_at(&songs);
+                Some(QueueEdit::Inserted(songs))
+            }
+            QueueEdit::Inserted(songs) => {
+                let queue = &self.imp().queue;
+                let still_there = songs.iter().all(|(pos, uri)| {
+                    queue
+                        .item(*pos)
+                        .and_downcast::<Song>()
+                        .is_some_and(|song| song.get_uri() == uri)
+                });
+                if !still_there {
+                    return None;
+                }
+                for (pos, _) in songs.iter().rev() {
+                    self.client().delete_at(*pos, false);
+                }
+                Some(QueueEdit::Removed(songs))
+            }
+            QueueEdit::Reordered { order, moved } => {
+                let current = self.queue_ids();
+                let current_set: FxHashSet<u32> = current.iter().copied().collect();
+                if current.len() != order.len() || !order.iter().all(|id| current_set.contains(id)) {
+                    return None;
+                }
+                self.client().move_ids(&moves_towards(&current, &order, &moved));
+                Some(QueueEdit::Reordered { order: current, moved })
+            }
+            QueueEdit::Replaced { uris, resume, paused } => {
+                let inverse = self.snapshot_queue().unwrap_or(QueueEdit::Replaced {
+                    uris: Vec::with_capacity(0),
+                    resume: None,
+                    paused: false
+                });
+                self.stop_auto_dj();
+                self.client().clear_queue();
+                self.client().queue_background(BackgroundTask::RestoreQueue(uris, resume, paused), true);
+                Some(inverse)
+            }
+        }
+    }
+
+    pub fn auto_dj(&self) -> Option<AutoDjSource> {
+        self.imp().auto_dj.borrow().clone()
+    }
+
+    /// Start keeping the queue topped up with songs from the given source. If replace is true,
+    /// the current queue is replaced by a first batch from that source, which starts playing.
+    pub fn start_auto_dj(&self, source: AutoDjSource, replace: bool) {
+        self.imp().auto_dj_last_id.set(self.queue_id());
+        self.imp().auto_dj_skipped.set(false);
+        if replace {
+            if let Some(snapshot) = self.snapshot_queue() {
+                self.push_queue_edit(snapshot);
+            }
+            self.client().clear_queue();
+            self.imp().auto_dj_last_id.set(None);
+            self.imp().topup_requested_at_len.set(Some(0));
+            let batch_size = settings_manager().child("player").uint("topup-batch-size");
+            self.client().queue_background(
+                BackgroundTask::TopUpQueue(source.clone(), batch_size, Vec::with_capacity(0), Some(0)),
+                true
+            );
+            self.imp().auto_dj.replace(Some(source));
+        } else {
+            self.imp().topup_requested_at_len.set(None);
+            self.imp().auto_dj.replace(Some(source));
+            self.update_auto_dj();
+        }
+        self.notify("auto-dj");
+    }
+
+    pub fn stop_auto_dj(&self) {
+        if self.imp().auto_dj.take().is_some() {
+            self.imp().topup_requested_at_len.set(None);
+            self.imp().auto_dj_last_id.set(None);
+            self.imp().auto_dj_last_remaining.set(f64::INFINITY);
+            self.imp().auto_dj_skipped.set(false);
+            self.notify("auto-dj");
+        }
+    }
+
+    /// Remember that the user is jumping to another song, if Auto-DJ is running.
+    fn mark_auto_dj_skip(&self) {
+        if self.imp().auto_dj.borrow().is_some() {
+            self.imp().auto_dj_skipped.set(true);
+        }
+    }
+
+    /// Called on every status update while Auto-DJ is running. Removes the last played song
+    /// if requested, then asks for more songs when the queue is about to run out.
+    fn update_auto_dj(&self) {
+        let source = if let Some(source) = self.imp().auto_dj.borrow().as_ref() {
+            source.clone()
+        } else {
+            return;
+        };
+        let settings = settings_manager().child("player");
+        let curr_id = self.queue_id();
+        let last_id = self.imp().auto_dj_last_id.replace(curr_id);
+        let duration = self.duration() as f64;
+        let last_remaining = self.imp().auto_dj_last_remaining.replace(if duration > 0.0 {
+            duration - self.position()
+        } else {
+            f64::INFINITY
+        });
+        if let Some(last_id) = last_id {
+            if curr_id != Some(last_id) {
+                // Only remove songs that played through to their end. Songs left by skipping,
+                // whether from here or another client, were never actually heard.
+                let skipped = self.imp().auto_dj_skipped.replace(false);
+                if !skipped
+                    && last_remaining <= AUTO_DJ_END_SLACK_S + self.imp().crossfade.get()
+                    && settings.boolean("auto-dj-remove-played")
+                {
+                    self.client().delete_at(last_id, true);
+                }
+            }
+        }
+
+        // Only request once until the queue has grown, so that status updates arriving
+        // before the new songs do won't duplicate work. If the source has run dry, this
+        // also stops us from asking again and again.
+        let queue_len = self.imp().queue_len.get();
+        if let Some(requested_at_len) = self.imp().topup_requested_at_len.get() {
+            if queue_len <= requested_at_len {
+                return;
+            }
+            self.imp().topup_requested_at_len.set(None);
+        }
+        let remaining = self
+            .queue_pos()
+            .map_or(queue_len, |pos| queue_len.saturating_sub(pos + 1));
+        if remaining > settings.uint("topup-threshold") {
+            return;
+        }
+        self.imp().topup_requested_at_len.set(Some(queue_len));
+        // Avoid repeating songs that are already in the queue.
+        let exclude: Vec<String> = self
+            .imp()
+            .queue
+            .iter::<Song>()
+            .filter_map(|item| item.ok().map(|song| song.get_uri().to_owned()))
+            .collect();
+        self.client().queue_background(
+            BackgroundTask::TopUpQueue(source, settings.uint("topup-batch-size"), exclude, None),
+            false
+        );
+    }
+
     pub fn send_set_volume(&self, val: i8) {
         let old_vol = self.imp().volume.replace(val);
         if old_vol != val {
@@ -1579,19 +2307,171 @@ impl Player {
             println!("Stopping PipeWire backend to allow samplerate change...");
             self.maybe_stop_fft_thread(true);
         }
+        self.mark_auto_dj_skip();
         self.client().play_at(song.get_queue_id(), true);
     }
 
     /// Remove given song from queue.
     pub fn remove_pos(&self, pos: u32) {
+        if let Some(song) = self.imp().queue.item(pos).and_downcast::<Song>() {
+            self.push_queue_edit(QueueEdit::Removed(vec![(pos, song.get_uri().to_owned())]));
+        }
         self.client().register_local_queue_changes(1);
         self.queue().remove(pos);
         self.client().delete_at(pos, false);
     }
 
+    /// Set the MPD priority of the songs at the given queue positions. Priorities only
+    /// affect playback order in random mode, where higher-priority songs are played first.
+    pub fn set_priority(&self, positions: &[u32], prio: u8) {
+        let ids: Vec<u32> = positions
+            .iter()
+            .filter_map(|pos| self.imp().queue.item(*pos).and_downcast::<Song>())
+            .map(|song| song.get_queue_id())
+            .collect();
+        self.client().set_priority(&ids, prio);
+    }
+
+    /// Queue the given URIs starting at the given position.
+    pub fn insert_uris(&self, uris: Vec<String>, pos: u32) {
+        self.client().queue_background(
+            BackgroundTask::QueueUris(uris, false, None, Some(pos)),
+            true
+        );
+    }
+
+    /// Move songs (by queue ID) such that they form a contiguous block, in queue order, that
+    /// starts at the given position of the current queue (i.e. right before the song currently
+    /// there). Applied locally right away, then sent to MPD as a single range move if the songs
+    /// are already contiguous, or as one command list of per-song moves otherwise.
+    pub fn move_songs(&self, ids: &[u32], target: u32) {
+        let queue = &self.imp().queue;
+        let moving: FxHashSet<u32> = ids.iter().copied().collect();
+        let songs: Vec<Song> = queue.iter::<Song>().filter_map(Result::ok).collect();
+        let moved_positions: Vec<u32> = songs
+            .iter()
+            .enumerate()
+            .filter(|(_, song)| moving.contains(&song.get_queue_id()))
+            .map(|(pos, _)| pos as u32)
+            .collect();
+        let (Some(first), Some(last)) = (moved_positions.first().copied(), moved_positions.last().copied()) else {
+            return;
+        };
+        let mut order: Vec<u32> = songs.iter().map(|song| song.get_queue_id()).collect();
+        let (moved, rest): (Vec<Song>, Vec<Song>) = songs
+            .into_iter()
+            .partition(|song| moving.contains(&song.get_queue_id()));
+        // Where the block starts once the moved songs have been taken out
+        let dest = (target - moved_positions.iter().filter(|pos| **pos < target).count() as u32)
+            .min(rest.len() as u32);
+        let contiguous = last - first + 1 == moved_positions.len() as u32;
+        if contiguous && dest == first {
+            return;
+        }
+        self.push_queue_edit(QueueEdit::Reordered { order: order.clone(), moved: moving });
+
+        let new_order: Vec<glib::Object> = rest[..dest as usize]
+            .iter()
+            .chain(moved.iter())
+            .chain(rest[dest as usize..].iter())
+            .map(|song| song.clone().upcast::<glib::Object>())
+            .collect();
+        queue.splice(0, queue.n_items(), &new_order);
+
+        if contiguous {
+            self.client().register_local_queue_changes(1);
+            self.client().move_range(first, last + 1, dest);
+        } else {
+            // Each move shifts the others, so insert each song right before whichever song
+            // should follow the block, wherever that is at the time.
+            let anchor = rest.get(dest as usize).map(|song| song.get_queue_id());
+            let mut moves: Vec<(u32, u32)> = Vec::with_capacity(moved.len());
+            for song in moved.iter() {
+                let id = song.get_queue_id();
+                let from = order.iter().position(|other| *other == id);
+                if let Some(from) = from {
+                    order.remove(from);
+                }
+                let to = anchor
+                    .and_then(|anchor| order.iter().position(|other| *other == anchor))
+                    .unwrap_or(order.len());
+                order.insert(to, id);
+                // Songs already in place aren't sent, as MPD wouldn't bump the queue
+                // version for them.
+                if from != Some(to) {
+                    moves.push((id, to as u32));
+                }
+            }
+            // Each move sent bumps the queue version once.
+            self.client().register_local_queue_changes(moves.len() as u32);
+            self.client().move_ids(&moves);
+        }
+    }
+
+    /// Move the songs at the given queue positions to right after the current song, keeping
+    /// their relative order. If nothing is playing, they are moved to the start of the queue.
+    pub fn play_next(&self, positions: &[u32]) {
+        let queue = &self.imp().queue;
+        let curr_id = self.queue_id();
+        let mut positions = positions.to_vec();
+        positions.sort_unstable();
+        let to_move: Vec<u32> = positions
+            .into_iter()
+            .filter_map(|pos| queue.item(pos).and_downcast::<Song>())
+            .map(|song| song.get_queue_id())
+            .filter(|id| Some(*id) != curr_id)
+            .collect();
+        // Simulate the moves locally as each one shifts the positions of the others.
+        let mut order: Vec<u32> = queue
+            .iter::<Song>()
+            .filter_map(|song| song.ok().map(|song| song.get_queue_id()))
+            .collect();
+        if to_move.is_empty() {
+            return;
+        }
+        self.push_queue_edit(QueueEdit::Reordered {
+            order: order.clone(),
+            moved: to_move.iter().copied().collect()
+        });
+        let mut moves: Vec<(u32, u32)> = Vec::with_capacity(to_move.len());
+        for (idx, id) in to_move.into_iter().enumerate() {
+            if let Some(from) = order.iter().position(|other| *other == id) {
+                order.remove(from);
+                let start = curr_id
+                    .and_then(|curr_id| order.iter().position(|other| *other == curr_id))
+                    .map_or(0, |curr_pos| curr_pos + 1);
+                let to = (start + idx).min(order.len());
+                order.insert(to, id);
+                moves.push((id, to as u32));
+            }
+        }
+        self.client().move_ids(&moves);
+    }
+
+    /// Only play the given range (start and optional end, in seconds) of the song at the given
+    /// queue position, or the whole song if None. The range is remembered for this song and
+    /// reapplied whenever it is queued again.
+    pub fn set_range(&self, pos: u32, range: Option<(f64, Option<f64>)>) {
+        if let Some(song) = self.imp().queue.item(pos).and_downcast::<Song>() {
+            if let Err(db_err) = sqlite::write_song_range(song.get_uri(), range) {
+                dbg!(db_err);
+            }
+            self.client().set_range(song.get_queue_id(), range);
+        }
+    }
+
     pub fn swap_dir(&self, pos: u32, direction: SwapDirection) {
-        self.client().register_local_queue_changes(1);
         let target = self.imp().queue.item(pos).and_downcast::<Song>().unwrap();
+        let at_edge = match direction {
+            SwapDirection::Up => pos == 0,
+            SwapDirection::Down => pos + 1 >= self.imp().queue.n_items(),
+        };
+        if !at_edge {
+            let mut moved = FxHashSet::default();
+            moved.insert(target.get_queue_id());
+            self.push_queue_edit(QueueEdit::Reordered { order: self.queue_ids(), moved });
+        }
+        self.client().register_local_queue_changes(1);
         match direction {
             SwapDirection::Up => {
                 if pos > 0 {
@@ -1620,6 +2500,98 @@ impl Player {
         self.client().save_queue_as_playlist(name, save_mode)
     }
 
+    /// Move the queue & current playback position over to another MPD server, then stay
+    /// connected to that one. Song URIs are remapped by the configured prefix replacements.
+    pub fn hand_off(&self, target: &str) {
+        let conn = settings_manager().child("client");
+        let prefixes: Vec<(String, String)> = conn
+            .value("handoff-uri-prefixes")
+            .array_iter_str()
+            .unwrap()
+            .filter_map(|rule| rule.split_once("=>"))
+            .map(|(from, to)| (from.trim().to_owned(), to.trim().to_owned()))
+            .collect();
+        let uris: Vec<String> = self
+            .imp()
+            .queue
+            .iter::<Song>()
+            .filter_map(|song| song.ok().map(|song| remap_uri(song.get_uri(), &prefixes)))
+            .collect();
+        let state = self.imp().state.get();
+        let resume = if state == PlaybackState::Stopped {
+            None
+        } else {
+            self.queue_pos().map(|pos| (pos, self.position()))
+        };
+        let paused = state == PlaybackState::Paused;
+        let stop_source = conn.boolean("handoff-stop-source");
+        // Queue IDs from the old server mean nothing on the new one.
+        self.stop_auto_dj();
+        self.imp().queue_undo.borrow_mut().clear();
+        self.imp().queue_redo.borrow_mut().clear();
+        self.notify("can-undo-queue");
+        self.notify("can-redo-queue");
+
+        let target = target.to_owned();
+        let client = self.client().clone();
+        glib::spawn_future_local(async move {
+            client.hand_off_async(target, uris, resume, paused, stop_source).await;
+        });
+    }
+
+    /// Save the current queue, play position & playback flags as a named snapshot in the
+    /// local DB, overwriting any existing snapshot of the same name.
+    pub fn save_snapshot(&self, name: &str) {
+        let snapshot = QueueSnapshot {
+            name: name.to_owned(),
+            uris: self
+                .imp()
+                .queue
+                .iter::<Song>()
+                .filter_map(|song| song.ok().map(|song| song.get_uri().to_owned()))
+                .collect(),
+            pos: self.queue_pos(),
+            elapsed: self.position(),
+            flow: self.imp().flow.get(),
+            random: self.imp().random.get(),
+            consume: self.imp().consume.get(),
+        };
+        if let Err(db_err) = sqlite::write_queue_snapshot(&snapshot) {
+            dbg!(db_err);
+        }
+    }
+
+    /// Replace the queue with a saved snapshot, restoring its playback flags and resuming
+    /// playback where it was left. The replaced queue can be brought back with undo.
+    pub fn restore_snapshot(&self, name: &str) {
+        let snapshot = match sqlite::get_queue_snapshot(name) {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => {
+                return;
+            }
+            Err(db_err) => {
+                dbg!(db_err);
+                return;
+            }
+        };
+        self.stop_auto_dj();
+        if let Some(previous) = self.snapshot_queue() {
+            self.push_queue_edit(previous);
+        }
+        self.client().clear_queue();
+        self.client().set_playback_flow(snapshot.flow);
+        self.client().set_random(snapshot.random);
+        self.client().set_consume(snapshot.consume);
+        self.client().queue_background(
+            BackgroundTask::RestoreQueue(
+                snapshot.uris,
+                snapshot.pos.map(|pos| (pos, snapshot.elapsed)),
+                false
+            ),
+            true
+        );
+    }
+
     /// Periodically poll for player progress to update seekbar.
     /// Won't start a new loop if there is already one or when polling is blocked by a seekbar.
     pub fn maybe_start_polling(&self) {
@@ -1653,24 +2625,30 @@ impl Player {
     }
 
     pub fn import_lyrics(&self, text: &str) {
+        if let Ok(lyrics) = Lyrics::try_from_synced_lrclib_str(text)
+            // .map_err(|res| {
+            //     println!("Synced lyrics parse error: {:?}", &res);
+            //     return res;
+            // })
+            .or_else(|_| Lyrics::try_from_plain_lrclib_str(text))
+        {
+            self.save_lyrics(lyrics);
+        }
+    }
+
+    /// Store lyrics for the current song, replacing whatever it had, and show them.
+    pub fn save_lyrics(&self, lyrics: Lyrics) {
         if let Some(curr_song) = self.imp().current_song.borrow().as_ref() {
-            if let Ok(lyrics) = Lyrics::try_from_synced_lrclib_str(text)
-                // .map_err(|res| {
-                //     println!("Synced lyrics parse error: {:?}", &res);
-                //     return res;
-                // })
-                .or_else(|_| Lyrics::try_from_plain_lrclib_str(text))
-            {
-                sqlite::write_lyrics(curr_song.get_info(), Some(&lyrics))
-                    .expect("Unable to import lyrics into SQLite DB");
-                self.update_lyrics(lyrics);
-            }
+            sqlite::write_lyrics(curr_song.get_info(), Some(&lyrics))
+                .expect("Unable to import lyrics into SQLite DB");
+            self.update_lyrics(lyrics);
         }
     }
 
     pub fn clear_lyrics(&self) {
         if let Some(curr_song) = self.imp().current_song.borrow().as_ref() {
             sqlite::write_lyrics(curr_song.get_info(), None).expect("Unable to clear lyrics from DB");
+            self.stop_lyric_ticker();
             self.imp().lyric_lines.splice(0, self.imp().lyric_lines.n_items(), &[]);
             let _ = self.imp().lyrics.take();
         }
@@ -1828,11 +2806,19 @@ impl LocalPlayerInterface for Player {
     }
 
     async fn shuffle(&self) -> fdo::Result<bool> {
-        Ok(self.imp().random.get())
+        Ok(self.is_shuffling())
     }
 
     async fn set_shuffle(&self, shuffle: bool) -> zbus::Result<()> {
-        self.set_random(shuffle);
+        if shuffle {
+            // Keep album shuffle if it's already on
+            if !self.is_shuffling() {
+                self.set_random(true);
+            }
+        } else {
+            self.set_album_shuffle(false);
+            self.set_random(false);
+        }
         Ok(())
     }
 
@@ -5,12 +5,16 @@ mod knob;
 mod output;
 mod pane;
 mod playback_controls;
+mod queue_history;
 mod queue_view;
 mod ratio_center_box;
 mod seekbar;
+mod snapcast;
+mod snapcast_panel;
 
 use knob::VolumeKnob;
-use output::MpdOutput;
+use output::{may_feed_snapcast, MpdOutput};
+use snapcast_panel::SnapcastPanel;
 
 pub use fft_backends::backend::FftStatus;
 pub use bar::PlayerBar;
@@ -18,6 +18,11 @@ fn map_icon_name(plugin_name: &str) -> &'static str {
     }
 }
 
+/// Outputs that may be feeding a snapserver.
+pub fn may_feed_snapcast(plugin_name: &str) -> bool {
+    matches!(plugin_name, "fifo" | "pipe" | "snapcast")
+}
+
 mod imp {
     use super::*;
 
@@ -33,7 +38,7 @@ mod imp {
         #[template_child]
         pub enable_output: TemplateChild<gtk::Switch>,
         #[template_child]
-        pub options_preview: TemplateChild<gtk::Label>
+        pub options_preview: TemplateChild<gtk::Label>,
     }
 
     // The central trait for subclassing a GObject
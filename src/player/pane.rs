@@ -7,13 +7,60 @@ use gtk::{
     subclass::prelude::*,
     CompositeTemplate,
 };
-use std::{cell::{Cell, RefCell}, fs::{self, File}, io::Write};
+use std::{cell::{Cell, RefCell}, fs::{self, File}, io::Write, time::Duration};
 
 use crate::{
-    cache::placeholders::{ALBUMART_PLACEHOLDER, EMPTY_ALBUM_STRING, EMPTY_ARTIST_STRING}, client::{state::StickersSupportLevel, ClientState}, common::{paintables::FadePaintable, Rating}, player::seekbar::Seekbar, utils::{self, settings_manager}
+    cache::placeholders::{ALBUMART_PLACEHOLDER, EMPTY_ALBUM_STRING, EMPTY_ARTIST_STRING}, client::{state::StickersSupportLevel, ClientState}, common::{paintables::FadePaintable, Rating}, meta_providers::models::{format_lrc_timestamp, Lyrics}, player::seekbar::Seekbar, utils::{self, settings_manager}
 };
 
-use super::{MpdOutput, PlaybackControls, PlaybackState, Player, VolumeKnob};
+use super::{
+    may_feed_snapcast, MpdOutput, PlaybackControls, PlaybackState, Player, SnapcastPanel, VolumeKnob,
+};
+
+/// How far the nudge buttons of the tap-to-sync editor move a line, in seconds.
+const SYNC_NUDGE_S: f32 = 0.1;
+/// How often the tap-to-sync preview follows playback, in milliseconds.
+const SYNC_PREVIEW_TICK_MS: u64 = 100;
+
+/// Ask the user where to save the given LRC text, then save it there.
+fn save_lrc_file(text: String) {
+    let (sender, receiver) = async_channel::bounded(1);
+    utils::tokio_runtime().spawn(
+        async move {
+            sender.send(
+                SelectedFiles::save_file()
+                    .title("Save lyrics to .lrc file")
+                    .accept_label("Save")
+                    .current_name("lyrics.lrc")
+                    .modal(true)
+                    .filter(FileFilter::new("LRC files").glob("*.lrc"))
+                    .send()
+                    .await
+                    .expect("ashpd file open await failure")
+                    .response()
+            ).await.expect("Unable to send response from ashpd back to main thread");
+        });
+    glib::spawn_future_local(
+        async move {
+            if let Some(uri) = receiver
+                .recv().await
+                       .unwrap().ok()  // Once for receiver result and once for ashpd's
+                       .and_then(|sel_files| {
+                           let uris = sel_files.uris();
+                           if uris.is_empty() {None} else {Some(uris[0].to_string())}
+                       })
+            {
+                let uri = urlencoding::decode(if uri.starts_with("file://") {
+                    &uri[7..]
+                } else {
+                    &uri
+                }).expect("UTF-8").into_owned();
+                let mut output = File::create(uri).expect("Unable to open a file for exporting lyrics");
+                output.write_all(text.as_bytes()).expect("Unable to write to opened file");
+            }
+        }
+    );
+}
 
 mod imp {
     use super::*;
@@ -73,6 +120,26 @@ mod imp {
         #[template_child]
         pub clear_lyrics: TemplateChild<gtk::Button>,
         #[template_child]
+        pub sync_lyrics: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sync_editor: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub sync_lines: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub sync_earlier: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sync_stamp: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sync_later: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sync_preview: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub sync_export: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sync_save: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sync_cancel: TemplateChild<gtk::Button>,
+        #[template_child]
         pub output_btn: TemplateChild<gtk::MenuButton>,
         #[template_child]
         pub output_section: TemplateChild<gtk::Box>,
@@ -83,6 +150,8 @@ mod imp {
         #[template_child]
         pub next_output: TemplateChild<gtk::Button>,
         #[template_child]
+        pub snapcast_panel: TemplateChild<SnapcastPanel>,
+        #[template_child]
         pub vol_knob: TemplateChild<VolumeKnob>,
 
         // Kept here so we can access it in snapshot()
@@ -92,6 +161,18 @@ mod imp {
         // Index of visible child in output_widgets
         pub current_output: Cell<usize>,
         pub output_count: Cell<usize>,
+        // Lyric line currently showing word highlights, to be restored once it's over
+        pub karaoke_line: Cell<Option<u32>>,
+
+        // Tap-to-sync editor state: the new timestamp of each line (if stamped yet), the line
+        // to stamp next, the line nudges apply to & the highlighted one.
+        pub sync_stamps: RefCell<Vec<Option<f32>>>,
+        pub sync_cursor: Cell<usize>,
+        pub sync_selected: Cell<Option<usize>>,
+        pub sync_highlighted: Cell<Option<usize>>,
+        // Rows of sync_lines & their timestamp labels
+        pub sync_rows: RefCell<Vec<(gtk::ListBoxRow, gtk::Label)>>,
+        pub sync_preview_source: RefCell<Option<glib::SourceId>>,
     }
 
     // The central trait for subclassing a GObject
@@ -205,9 +286,167 @@ impl PlayerPane {
 
     pub fn update_lyrics_availability(&self, player: &Player) {
         let has_lyrics = player.n_lyric_lines() > 0;
-        self.imp().lyrics_window.set_visible(has_lyrics && self.imp().show_lyrics.is_active());
+        let editing = self.imp().sync_editor.is_visible();
+        self.imp().lyrics_window.set_visible(has_lyrics && self.imp().show_lyrics.is_active() && !editing);
         self.imp().export_lyrics.set_sensitive(has_lyrics);
         self.imp().clear_lyrics.set_sensitive(has_lyrics);
+        self.imp().sync_lyrics.set_sensitive(has_lyrics && !editing);
+    }
+
+    /// Replace the lyrics view with the tap-to-sync editor. Already-synced lyrics start
+    /// with their current timings.
+    fn enter_sync_editor(&self, player: &Player) {
+        let imp = self.imp();
+        let lines = player.lyrics();
+        let n_lines = lines.n_items() as usize;
+        if n_lines == 0 {
+            return;
+        }
+        let stamps: Vec<Option<f32>> = match player.lyric_timestamps() {
+            Some(stamps) if stamps.len() == n_lines => stamps.into_iter().map(Some).collect(),
+            _ => vec![None; n_lines],
+        };
+        imp.sync_lines.remove_all();
+        let mut rows: Vec<(gtk::ListBoxRow, gtk::Label)> = Vec::with_capacity(n_lines);
+        for i in 0..n_lines {
+            let stamp = gtk::Label::builder()
+                .valign(gtk::Align::Start)
+                .css_classes(["numeric", "dim-label"])
+                .build();
+            let text = gtk::Label::builder()
+                .label(lines.string(i as u32).unwrap_or_default())
+                .xalign(0.0)
+                .hexpand(true)
+                .wrap(true)
+                .build();
+            let content = gtk::Box::builder().spacing(12).build();
+            content.append(&stamp);
+            content.append(&text);
+            let row = gtk::ListBoxRow::builder().child(&content).build();
+            imp.sync_lines.append(&row);
+            rows.push((row, stamp));
+        }
+        imp.sync_rows.replace(rows);
+        imp.sync_stamps.replace(stamps);
+        imp.sync_cursor.set(0);
+        imp.sync_selected.set(None);
+        imp.sync_highlighted.set(None);
+        imp.sync_preview.set_active(false);
+        imp.sync_editor.set_visible(true);
+        self.update_lyrics_availability(player);
+        self.refresh_sync_editor(player);
+        imp.sync_stamp.grab_focus();
+    }
+
+    fn exit_sync_editor(&self, player: &Player) {
+        let imp = self.imp();
+        if !imp.sync_editor.is_visible() {
+            return;
+        }
+        imp.sync_preview.set_active(false);
+        imp.sync_editor.set_visible(false);
+        imp.sync_lines.remove_all();
+        imp.sync_rows.borrow_mut().clear();
+        imp.sync_stamps.borrow_mut().clear();
+        self.update_lyrics_availability(player);
+        self.update_lyrics_state(player);
+    }
+
+    /// Show the new timestamps and highlight the line to stamp next, or when previewing,
+    /// the line being sung according to the new timings.
+    fn refresh_sync_editor(&self, player: &Player) {
+        let imp = self.imp();
+        let stamps = imp.sync_stamps.borrow();
+        let highlighted = if imp.sync_preview.is_active() {
+            let pos = player.estimated_position() as f32;
+            stamps.iter().rposition(|stamp| stamp.is_some_and(|stamp| stamp <= pos))
+        } else {
+            Some(imp.sync_cursor.get())
+        };
+        let rows = imp.sync_rows.borrow();
+        for (idx, (row, label)) in rows.iter().enumerate() {
+            label.set_label(&stamps[idx].map_or("--:--.--".to_owned(), format_lrc_timestamp));
+            row.set_opacity(if Some(idx) == highlighted {
+                1.0
+            } else if Some(idx) == imp.sync_selected.get() {
+                0.7
+            } else {
+                0.4
+            });
+        }
+        // Keep the highlighted line in view
+        if imp.sync_highlighted.replace(highlighted) != highlighted {
+            // Scroll rather than focus the row, so as not to steal focus from the
+            // editor's buttons during preview.
+            if let Some((row, _)) = highlighted.and_then(|idx| rows.get(idx)) {
+                let list = imp.sync_lines.get();
+                if let (Some(window), Some(bounds)) = (
+                    list.ancestor(gtk::ScrolledWindow::static_type())
+                        .and_downcast::<gtk::ScrolledWindow>(),
+                    row.compute_bounds(&list),
+                ) {
+                    let adj = window.vadjustment();
+                    adj.set_value(
+                        (bounds.y() + bounds.height() / 2.0) as f64 - adj.page_size() / 2.0,
+                    );
+                }
+            }
+        }
+        let any_stamped = stamps.iter().any(Option::is_some);
+        imp.sync_save.set_sensitive(any_stamped);
+        imp.sync_export.set_sensitive(any_stamped);
+    }
+
+    /// Stamp the current position onto the next line.
+    fn sync_stamp(&self, player: &Player) {
+        let imp = self.imp();
+        let cursor = imp.sync_cursor.get();
+        if let Some(stamp) = imp.sync_stamps.borrow_mut().get_mut(cursor) {
+            *stamp = Some(player.estimated_position() as f32);
+            imp.sync_selected.set(Some(cursor));
+            imp.sync_cursor.set(cursor + 1);
+        }
+        self.refresh_sync_editor(player);
+    }
+
+    /// Go back a line, clearing its stamp.
+    fn sync_back(&self, player: &Player) {
+        let imp = self.imp();
+        let cursor = imp.sync_cursor.get().saturating_sub(1);
+        if let Some(stamp) = imp.sync_stamps.borrow_mut().get_mut(cursor) {
+            *stamp = None;
+        }
+        imp.sync_cursor.set(cursor);
+        imp.sync_selected.set(cursor.checked_sub(1));
+        self.refresh_sync_editor(player);
+    }
+
+    /// Move the selected line's timestamp by the given number of seconds.
+    fn sync_nudge(&self, player: &Player, delta: f32) {
+        let imp = self.imp();
+        if let Some(idx) = imp.sync_selected.get() {
+            if let Some(Some(stamp)) = imp.sync_stamps.borrow_mut().get_mut(idx) {
+                *stamp = (*stamp + delta).max(0.0);
+            }
+        }
+        self.refresh_sync_editor(player);
+    }
+
+    /// The lyrics with their new timings. Lines not stamped yet start along with the
+    /// line before them.
+    fn synced_lyrics(&self, player: &Player) -> Option<Lyrics> {
+        let mut last: f32 = 0.0;
+        let stamps: Vec<f32> = self
+            .imp()
+            .sync_stamps
+            .borrow()
+            .iter()
+            .map(|stamp| {
+                last = stamp.unwrap_or(last);
+                last
+            })
+            .collect();
+        player.retimed_lyrics(&stamps)
     }
 
     pub fn update_lyrics_state(&self, player: &Player) {
@@ -240,6 +479,52 @@ impl PlayerPane {
                 }
             }
         }
+        self.update_lyric_words(player);
+    }
+
+    /// Karaoke-style highlighting of the words sung so far in the current line, for lyrics
+    /// with word timings.
+    pub fn update_lyric_words(&self, player: &Player) {
+        let imp = self.imp();
+        let lyrics_box = imp.lyrics_box.get();
+        let label_at = |idx: u32| {
+            lyrics_box
+                .row_at_index(idx as i32)
+                .and_then(|row| row.child())
+                .and_downcast::<gtk::Label>()
+        };
+        let curr_line_idx = player.current_lyric_line();
+        let active = player.lyrics_are_synced() && imp.use_synced_lyrics.is_active();
+        if let Some(prev) = imp.karaoke_line.get() {
+            if prev != curr_line_idx || !active {
+                if let (Some(label), Some(text)) = (label_at(prev), player.lyrics().string(prev)) {
+                    label.set_text(&text);
+                }
+                imp.karaoke_line.set(None);
+            }
+        }
+        if !active {
+            return;
+        }
+        let words = player.lyric_line_words(curr_line_idx);
+        let Some(label) = label_at(curr_line_idx).filter(|_| !words.is_empty()) else {
+            return;
+        };
+        let curr_word_idx = player.current_lyric_word();
+        let markup: String = words
+            .iter()
+            .enumerate()
+            .map(|(idx, word)| {
+                let word = glib::markup_escape_text(word);
+                if idx as i32 <= curr_word_idx {
+                    word.to_string()
+                } else {
+                    format!("<span alpha=\"40%\">{word}</span>")
+                }
+            })
+            .collect();
+        label.set_markup(&markup);
+        imp.karaoke_line.set(Some(curr_line_idx));
     }
 
     pub fn setup(&self, player: &Player, client_state: &ClientState) {
@@ -247,6 +532,7 @@ impl PlayerPane {
         self.bind_state(player, client_state);
         self.imp().playback_controls.setup(player);
         self.imp().seekbar.setup(player);
+        self.imp().snapcast_panel.bind(player.snapcast());
     }
 
     fn setup_volume_knob(&self, player: &Player) {
@@ -495,6 +781,13 @@ impl PlayerPane {
                 this.update_lyrics_state(player);
             }
         ));
+        player.connect_notify_local(Some("current-lyric-word"), clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |player, _| {
+                this.update_lyric_words(player);
+            }
+        ));
 
         self.update_outputs(player);
         player.connect_closure(
@@ -614,43 +907,8 @@ impl PlayerPane {
             #[weak]
             player,
             move |_| {
-                let (sender, receiver) = async_channel::bounded(1);
                 if let Some(text) = player.export_lyrics() {
-                    utils::tokio_runtime().spawn(
-                        async move {
-                            sender.send(
-                                SelectedFiles::save_file()
-                                    .title("Save lyrics to .lrc file")
-                                    .accept_label("Save")
-                                    .current_name("lyrics.lrc")
-                                    .modal(true)
-                                    .filter(FileFilter::new("LRC files").glob("*.lrc"))
-                                    .send()
-                                    .await
-                                    .expect("ashpd file open await failure")
-                                    .response()
-                            ).await.expect("Unable to send response from ashpd back to main thread");
-                        });
-                    glib::spawn_future_local(
-                        async move {
-                            if let Some(uri) = receiver
-                                .recv().await
-                                       .unwrap().ok()  // Once for receiver result and once for ashpd's
-                                       .and_then(|sel_files| {
-                                           let uris = sel_files.uris();
-                                           if uris.is_empty() {None} else {Some(uris[0].to_string())}
-                                       })
-                            {
-                                let uri = urlencoding::decode(if uri.starts_with("file://") {
-                                    &uri[7..]
-                                } else {
-                                    &uri
-                                }).expect("UTF-8").into_owned();
-                                let mut output = File::create(uri).expect("Unable to open a file for exporting lyrics");
-                                output.write_all(text.as_bytes()).expect("Unable to write to opened file");
-                            }
-                        }
-                    );
+                    save_lrc_file(text);
                 }
             }
         ));
@@ -663,6 +921,178 @@ impl PlayerPane {
             }
         ));
 
+        // Tap-to-sync editor
+        imp.sync_lyrics.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                this.imp().lyrics_btn.popdown();
+                this.enter_sync_editor(&player);
+            }
+        ));
+
+        // New song or lyrics: the lines being edited no longer apply.
+        lyric_lines.connect_items_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_, _, _, _| {
+                this.exit_sync_editor(&player);
+            }
+        ));
+
+        let sync_keys = gtk::EventControllerKey::new();
+        sync_keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        sync_keys.connect_key_pressed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, key, _, _| {
+                // Leave Space to a focused button, which it activates (the stamp
+                // button's own click stamps anyway).
+                let on_button = this
+                    .root()
+                    .and_then(|root| root.focus())
+                    .is_some_and(|widget| widget.is::<gtk::Button>());
+                match key {
+                    gdk::Key::space if !on_button => {
+                        this.sync_stamp(&player);
+                        glib::Propagation::Stop
+                    }
+                    gdk::Key::BackSpace => {
+                        this.sync_back(&player);
+                        glib::Propagation::Stop
+                    }
+                    _ => glib::Propagation::Proceed,
+                }
+            }
+        ));
+        imp.sync_editor.add_controller(sync_keys);
+
+        imp.sync_stamp.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                this.sync_stamp(&player);
+            }
+        ));
+
+        imp.sync_earlier.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                this.sync_nudge(&player, -SYNC_NUDGE_S);
+            }
+        ));
+
+        imp.sync_later.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                this.sync_nudge(&player, SYNC_NUDGE_S);
+            }
+        ));
+
+        // Clicking a line makes it the next one to stamp, or when previewing, seeks to it.
+        imp.sync_lines.connect_row_activated(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_, row| {
+                let imp = this.imp();
+                let idx = row.index() as usize;
+                if imp.sync_preview.is_active() {
+                    if let Some(Some(stamp)) = imp.sync_stamps.borrow().get(idx) {
+                        player.send_seek(*stamp as f64);
+                    }
+                } else {
+                    imp.sync_cursor.set(idx);
+                    imp.sync_selected.set(Some(idx));
+                    this.refresh_sync_editor(&player);
+                }
+            }
+        ));
+
+        imp.sync_preview.connect_toggled(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |btn| {
+                if let Some(source) = this.imp().sync_preview_source.take() {
+                    source.remove();
+                }
+                if btn.is_active() {
+                    let source = glib::timeout_add_local(
+                        Duration::from_millis(SYNC_PREVIEW_TICK_MS),
+                        clone!(
+                            #[weak]
+                            this,
+                            #[weak]
+                            player,
+                            #[upgrade_or]
+                            glib::ControlFlow::Break,
+                            move || {
+                                this.refresh_sync_editor(&player);
+                                glib::ControlFlow::Continue
+                            }
+                        )
+                    );
+                    this.imp().sync_preview_source.replace(Some(source));
+                }
+                this.refresh_sync_editor(&player);
+            }
+        ));
+
+        imp.sync_export.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                if let Some(lyrics) = this.synced_lyrics(&player) {
+                    save_lrc_file(lyrics.to_string());
+                }
+            }
+        ));
+
+        imp.sync_save.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                // Saving replaces the lyrics, which usually closes the editor already.
+                if let Some(lyrics) = this.synced_lyrics(&player) {
+                    player.save_lyrics(lyrics);
+                }
+                this.exit_sync_editor(&player);
+            }
+        ));
+
+        imp.sync_cancel.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                this.exit_sync_editor(&player);
+            }
+        ));
+
         self.update_lyrics_availability(player);
         self.update_lyrics_state(player);
     }
@@ -686,6 +1116,9 @@ impl PlayerPane {
         let section = self.imp().output_section.get();
         let stack = self.imp().output_stack.get();
         let new_len = outputs.len();
+        self.imp().snapcast_panel.set_feeds_snapcast(
+            outputs.iter().any(|o| may_feed_snapcast(&o.borrow::<mpd::Output>().plugin)),
+        );
         if new_len == 0 {
             section.set_visible(false);
         } else {
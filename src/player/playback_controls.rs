@@ -132,17 +132,38 @@ impl PlaybackControls {
         ));
         let shuffle_btn = imp.random_btn.get();
 
-        // Don't use bidirectional to avoid erroneously firing once on UI init
-        player
-            .bind_property("random", &shuffle_btn, "active")
-            .sync_create()
-            .build();
+        // Don't use bidirectional to avoid erroneously firing once on UI init.
+        // The button cycles between off, song shuffle (MPD random) and album shuffle.
+        let update_shuffle_btn = clone!(
+            #[weak]
+            shuffle_btn,
+            move |player: &Player| {
+                shuffle_btn.set_active(player.is_shuffling());
+                // TODO: translatable
+                if player.album_shuffle() {
+                    shuffle_btn.set_icon_name("library-music-symbolic");
+                    shuffle_btn.set_tooltip_text(Some("Shuffle Albums"));
+                } else {
+                    shuffle_btn.set_icon_name("media-playlist-shuffle-symbolic");
+                    shuffle_btn.set_tooltip_text(Some("Shuffle Queue"));
+                }
+            }
+        );
+        update_shuffle_btn(player);
+        player.connect_notify_local(Some("random"), clone!(
+            #[strong]
+            update_shuffle_btn,
+            move |player, _| update_shuffle_btn(player)
+        ));
+        player.connect_notify_local(Some("album-shuffle"), move |player, _| update_shuffle_btn(player));
 
         shuffle_btn.connect_clicked(clone!(
             #[weak]
             player,
             move |btn| {
-                player.set_random(btn.is_active());
+                player.cycle_shuffle();
+                // Let the player's notifications decide the final state
+                btn.set_active(player.is_shuffling());
             }
         ));
     }
@@ -0,0 +1,56 @@
+use rustc_hash::FxHashSet;
+
+/// Maximum number of queue edits that can be undone.
+pub const QUEUE_HISTORY_LEN: usize = 50;
+
+/// A reversible change to the queue. Each variant records what is needed to revert it.
+/// Reverting an edit yields another edit that reverts the revert (i.e. a redo step).
+#[derive(Debug, Clone)]
+pub enum QueueEdit {
+    /// Songs were removed. Holds their queue positions & URIs, in ascending position order.
+    Removed(Vec<(u32, String)>),
+    /// Songs were inserted. Holds their queue positions & URIs, in ascending position order.
+    Inserted(Vec<(u32, String)>),
+    /// Songs were moved around. Holds the previous queue order (as queue IDs) and the IDs of
+    /// the songs that were moved relative to the rest.
+    Reordered {
+        order: Vec<u32>,
+        moved: FxHashSet<u32>,
+    },
+    /// The whole queue was cleared or replaced. Holds the previous queue's URIs and, if
+    /// something was playing or paused, the queue position & elapsed seconds to resume from
+    /// along with whether it was paused.
+    Replaced {
+        uris: Vec<String>,
+        resume: Option<(u32, f64)>,
+        paused: bool,
+    },
+}
+
+/// Compute the moveid calls that turn the current queue order into the desired one, given
+/// that only the songs in `moving` are out of place relative to each other and the rest.
+/// Each move shifts the others, so every song is placed right after whichever song should
+/// precede it, wherever that is at the time.
+pub fn moves_towards(current: &[u32], desired: &[u32], moving: &FxHashSet<u32>) -> Vec<(u32, u32)> {
+    let mut order = current.to_vec();
+    let mut moves: Vec<(u32, u32)> = Vec::with_capacity(moving.len());
+    for (idx, id) in desired.iter().enumerate() {
+        if !moving.contains(id) {
+            continue;
+        }
+        if let Some(from) = order.iter().position(|other| other == id) {
+            order.remove(from);
+            let to = if idx == 0 {
+                0
+            } else {
+                order
+                    .iter()
+                    .position(|other| *other == desired[idx - 1])
+                    .map_or(0, |prev| prev + 1)
+            };
+            order.insert(to, *id);
+            moves.push((*id, to as u32));
+        }
+    }
+    moves
+}
@@ -5,7 +5,7 @@ use adw::subclass::prelude::*;
 use gio::glib::closure_local;
 use glib::clone;
 use gtk::{
-    gio, glib, CompositeTemplate, ListItem, SignalListItemFactory, SingleSelection,
+    gdk, gio, glib, CompositeTemplate, ListItem, MultiSelection, SignalListItemFactory,
 };
 use mpd::{
     error::{Error as MpdError, ErrorCode as MpdErrorCode, ServerError},
@@ -14,12 +14,14 @@ use mpd::{
 
 use super::PlayerPane;
 
-use crate::{cache::Cache, client::ClientState, common::{RowEditButtons, Song, SongRow}, player::controller::SwapDirection, utils::LazyInit, window::EuphonicaWindow};
+use rustc_hash::FxHashSet;
+
+use crate::{cache::{sqlite, Cache}, client::{BackgroundTask, ClientState}, common::{AutoDjSource, DraggedUris, RowEditButtons, Song, SongRow}, player::controller::SwapDirection, utils::{format_secs_as_duration, g_search_substr, parse_duration_secs, settings_manager, LazyInit}, window::EuphonicaWindow};
 
 use super::Player;
 
 mod imp {
-    use std::{cell::{Cell, OnceCell}, sync::OnceLock};
+    use std::{cell::{Cell, OnceCell, RefCell}, sync::OnceLock};
 
     use ::glib::WeakRef;
     use glib::{subclass::Signal, Properties};
@@ -47,7 +49,63 @@ mod imp {
         #[template_child]
         pub consume: TemplateChild<gtk::ToggleButton>,
         #[template_child]
+        pub search_bar: TemplateChild<gtk::SearchBar>,
+        #[template_child]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child]
+        pub jump_to_current: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub play_next: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub priority: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub range: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub range_start: TemplateChild<gtk::Entry>,
+        #[template_child]
+        pub range_end: TemplateChild<gtk::Entry>,
+        #[template_child]
+        pub range_apply: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub range_reset: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub group: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub group_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub group_volume_relative: TemplateChild<gtk::CheckButton>,
+        #[template_child]
+        pub group_resync: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub handoff: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub handoff_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub snapshots: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub snapshot_name: TemplateChild<gtk::Entry>,
+        #[template_child]
+        pub snapshot_save: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub snapshot_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
         pub clear_queue: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub undo_queue: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub redo_queue: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub auto_dj: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub auto_dj_banner: TemplateChild<adw::Banner>,
+        #[template_child]
+        pub auto_dj_source: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        pub auto_dj_name: TemplateChild<gtk::Entry>,
+        #[template_child]
+        pub auto_dj_remove_played: TemplateChild<gtk::CheckButton>,
+        #[template_child]
+        pub auto_dj_start: TemplateChild<gtk::Button>,
 
         #[template_child]
         pub save: TemplateChild<gtk::MenuButton>,
@@ -58,6 +116,10 @@ mod imp {
 
         pub window: OnceCell<EuphonicaWindow>,
 
+        pub search_filter: gtk::CustomFilter,
+        // Queue IDs matched by MPD for long queues. None means filtering locally.
+        pub search_ids: RefCell<Option<FxHashSet<u32>>>,
+
         #[property(get, set)]
         pub pane_collapsed: Cell<bool>,
         #[property(get, set)]
@@ -150,9 +212,25 @@ mod imp {
                 ))
                 .build();
 
+            let action_set_priority = gio::ActionEntry::builder("set-priority")
+                .parameter_type(Some(glib::VariantTy::UINT32))
+                .activate(clone!(
+                    #[weak]
+                    obj,
+                    move |_, _, param| {
+                        let prio = param
+                            .and_then(|p| p.get::<u32>())
+                            .map_or(0, |p| p.min(255) as u8);
+                        if let Some(player) = obj.imp().player.upgrade() {
+                            player.set_priority(&obj.selected_positions(), prio);
+                        }
+                    }
+                ))
+                .build();
+
             // Create a new action group and add actions to it
             let actions = gio::SimpleActionGroup::new();
-            actions.add_action_entries([action_clear_rating]);
+            actions.add_action_entries([action_clear_rating, action_set_priority]);
             self.obj().insert_action_group("queue-view", Some(&actions));
         }
 
@@ -181,6 +259,12 @@ impl Default for QueueView {
     }
 }
 
+// Queues longer than this are searched server-side using playlistsearch.
+const LOCAL_SEARCH_LIMIT: u32 = 1000;
+
+// Drag payload for reordering songs within the queue
+struct QueueDragIds(Vec<u32>);
+
 fn format_song_count(count: u32) -> Option<String> {
     // TODO: translatable
     if count == 0 {
@@ -214,13 +298,50 @@ impl QueueView {
             })
             .sync_create()
             .build();
-        let sel_model = SingleSelection::new(Some(queue_model));
+        self.imp().search_filter.set_filter_func(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[upgrade_or]
+            true,
+            move |obj| {
+                let song = obj
+                    .downcast_ref::<Song>()
+                    .expect("Search obj has to be a common::Song.");
+                let search_term = this.imp().search_entry.text();
+                if search_term.is_empty() {
+                    return true;
+                }
+                if let Some(ids) = this.imp().search_ids.borrow().as_ref() {
+                    return ids.contains(&song.get_queue_id());
+                }
+                let case_sensitive = settings_manager().child("library").boolean("search-case-sensitive");
+                g_search_substr(Some(song.get_name()), &search_term, case_sensitive)
+                    || g_search_substr(song.get_artist_tag(), &search_term, case_sensitive)
+                    || g_search_substr(song.get_album_title(), &search_term, case_sensitive)
+            }
+        ));
+        let filter_model = gtk::FilterListModel::new(Some(queue_model), Some(self.imp().search_filter.clone()));
+        // Multi-selection for bulk edits such as setting priorities and dragging several
+        // songs at once. Single-click activation is off as it would replace the selection
+        // on every click, so songs are played by double-clicking (or Enter) instead.
+        let sel_model = MultiSelection::new(Some(filter_model));
+        // Play ranges can only be edited one song at a time
+        let range_btn = self.imp().range.get();
+        sel_model.connect_selection_changed(clone!(
+            #[weak]
+            range_btn,
+            move |sel_model, _, _| {
+                range_btn.set_sensitive(sel_model.selection().size() == 1);
+            }
+        ));
         self.imp().queue.set_model(Some(&sel_model));
 
         // Set up factory
         let factory = SignalListItemFactory::new();
 
         factory.connect_setup(clone!(
+            #[weak(rename_to = this)]
+            self,
             #[weak]
             player,
             #[weak]
@@ -246,41 +367,89 @@ impl QueueView {
                     .chain_property::<Song>("artist")
                     .bind(&row, "second-attrib-text", gtk::Widget::NONE);
 
+                // Priority indicator, only shown for songs with a non-default priority
+                item.property_expression("item")
+                    .chain_property::<Song>("queue-priority")
+                    .chain_closure::<Option<String>>(closure_local!(|_: Option<glib::Object>, prio: u32| {
+                        (prio > 0).then_some("up-symbolic".to_owned())
+                    }))
+                    .bind(&row, "third-attrib-icon-name", gtk::Widget::NONE);
+                item.property_expression("item")
+                    .chain_property::<Song>("queue-priority")
+                    .chain_closure::<Option<String>>(closure_local!(|_: Option<glib::Object>, prio: u32| {
+                        (prio > 0).then(|| prio.to_string())
+                    }))
+                    .bind(&row, "third-attrib-text", gtk::Widget::NONE);
+
                 item.property_expression("item")
                     .chain_property::<Song>("quality-grade")
                     .bind(&row, "quality-grade", gtk::Widget::NONE);
+                // Row indices are those of the (possibly filtered) list, not queue positions.
                 let end_widget = RowEditButtons::new(
                     item,
                     // Raise action
                     clone!(
                         #[weak]
                         player,
+                        #[weak]
+                        this,
                         #[upgrade_or]
                         (),
                         move |idx| {
-                            player.swap_dir(idx, SwapDirection::Up);
+                            if let Some(pos) = this.queue_pos_at(idx) {
+                                player.swap_dir(pos, SwapDirection::Up);
+                            }
                         }
                     ),
                     clone!(
                         #[weak]
                         player,
+                        #[weak]
+                        this,
                         #[upgrade_or]
                         (),
                         move |idx| {
-                            player.swap_dir(idx, SwapDirection::Down);
+                            if let Some(pos) = this.queue_pos_at(idx) {
+                                player.swap_dir(pos, SwapDirection::Down);
+                            }
                         }
                     ),
                     clone!(
                         #[weak]
                         player,
+                        #[weak]
+                        this,
                         #[upgrade_or]
                         (),
                         move |idx| {
-                            player.remove_pos(idx);
+                            if let Some(pos) = this.queue_pos_at(idx) {
+                                player.remove_pos(pos);
+                            }
                         }
                     )
                 );
                 row.set_end_widget(Some(&end_widget.into()));
+
+                let drag_source = gtk::DragSource::new();
+                drag_source.set_actions(gdk::DragAction::MOVE);
+                drag_source.connect_prepare(clone!(
+                    #[weak]
+                    this,
+                    #[weak]
+                    row,
+                    #[upgrade_or]
+                    None,
+                    move |_, _, _| {
+                        let ids = this.drag_ids(&row);
+                        if ids.is_empty() {
+                            return None;
+                        }
+                        Some(gdk::ContentProvider::for_value(
+                            &glib::BoxedAnyObject::new(QueueDragIds(ids)).to_value()
+                        ))
+                    }
+                ));
+                row.add_controller(drag_source);
                 item.set_child(Some(&row));
             }
         ));
@@ -333,6 +502,7 @@ impl QueueView {
 
         // Set the factory of the list view
         self.imp().queue.set_factory(Some(&factory));
+        self.setup_drop_target(player);
 
         // Setup click action
         self.imp().queue.connect_activate(clone!(
@@ -349,6 +519,535 @@ impl QueueView {
         ));
     }
 
+    /// Queue position of the song shown at the given row, which may differ while searching.
+    /// Looked up in the queue itself as local edits don't update songs' own positions.
+    fn queue_pos_at(&self, idx: u32) -> Option<u32> {
+        let song = self.imp().queue.model()?.item(idx)?;
+        self.imp().player.upgrade()?.queue().find(&song)
+    }
+
+    /// Queue IDs of the songs to drag when starting from the given row: the whole selection
+    /// if that row is part of it, or just that row otherwise.
+    fn drag_ids(&self, row: &SongRow) -> Vec<u32> {
+        let Some(song) = row.song().clone() else {
+            return Vec::new();
+        };
+        let Some(player) = self.imp().player.upgrade() else {
+            return Vec::new();
+        };
+        let positions = self.selected_positions();
+        let is_selected = player
+            .queue()
+            .find(&song)
+            .is_some_and(|pos| positions.contains(&pos));
+        if is_selected {
+            positions
+                .into_iter()
+                .filter_map(|pos| player.queue().item(pos).and_downcast::<Song>())
+                .map(|song| song.get_queue_id())
+                .collect()
+        } else {
+            vec![song.get_queue_id()]
+        }
+    }
+
+    /// Queue position that a drop at the given point of the list view should insert at.
+    fn drop_pos(&self, player: &Player, x: f64, y: f64) -> u32 {
+        let list = self.imp().queue.get();
+        let row = list
+            .pick(x, y, gtk::PickFlags::DEFAULT)
+            .and_then(|widget| widget.ancestor(SongRow::static_type()))
+            .and_downcast::<SongRow>();
+        let Some(row) = row else {
+            // Dropped past the last row
+            return player.queue().n_items();
+        };
+        let Some(pos) = row.song().as_ref().and_then(|song| player.queue().find(song)) else {
+            return player.queue().n_items();
+        };
+        // Drop into the lower half of a row to insert after it
+        let lower_half = list
+            .compute_point(&row, &gtk::graphene::Point::new(x as f32, y as f32))
+            .is_some_and(|point| point.y() > row.height() as f32 / 2.0);
+        if lower_half {
+            pos + 1
+        } else {
+            pos
+        }
+    }
+
+    fn setup_drop_target(&self, player: &Player) {
+        let drop_target = gtk::DropTarget::new(glib::Type::INVALID, gdk::DragAction::MOVE | gdk::DragAction::COPY);
+        drop_target.set_types(&[glib::BoxedAnyObject::static_type()]);
+        drop_target.connect_drop(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            #[upgrade_or]
+            false,
+            move |_, value, x, y| {
+                let pos = this.drop_pos(&player, x, y);
+                if let Ok(boxed) = value.get::<glib::BoxedAnyObject>() {
+                    // Songs dragged from within the queue
+                    if let Ok(ids) = boxed.try_borrow::<QueueDragIds>() {
+                        player.move_songs(&ids.0, pos);
+                        return true;
+                    }
+                    // Songs dragged in from the library
+                    if let Ok(uris) = boxed.try_borrow::<DraggedUris>() {
+                        player.insert_uris(uris.0.clone(), pos);
+                        return true;
+                    }
+                }
+                false
+            }
+        ));
+        self.imp().queue.add_controller(drop_target);
+    }
+
+    /// Queue positions of all selected songs.
+    fn selected_positions(&self) -> Vec<u32> {
+        let Some(sel_model) = self.imp().queue.model().and_downcast::<MultiSelection>() else {
+            return Vec::new();
+        };
+        let sel = sel_model.selection();
+        (0..sel.size() as u32)
+            .filter_map(|idx| self.queue_pos_at(sel.nth(idx)))
+            .collect()
+    }
+
+    /// Queue position of the selected song, if exactly one is selected.
+    fn selected_pos(&self) -> Option<u32> {
+        let positions = self.selected_positions();
+        (positions.len() == 1).then(|| positions[0])
+    }
+
+    /// Scroll to the currently playing song, clearing the search if it's been filtered out.
+    fn jump_to_current(&self, player: &Player) {
+        let Some(curr_id) = player.queue_id() else {
+            return;
+        };
+        let queue = self.imp().queue.get();
+        let find_visible = || -> Option<u32> {
+            let model = queue.model()?;
+            (0..model.n_items()).find(|idx| {
+                model
+                    .item(*idx)
+                    .and_downcast::<Song>()
+                    .is_some_and(|song| song.get_queue_id() == curr_id)
+            })
+        };
+        let idx = find_visible().or_else(|| {
+            self.imp().search_entry.set_text("");
+            player.queue_pos()
+        });
+        if let Some(idx) = idx {
+            queue.scroll_to(idx, gtk::ListScrollFlags::FOCUS | gtk::ListScrollFlags::SELECT, None);
+        }
+    }
+
+    fn bind_search(&self, player: &Player, client_state: &ClientState) {
+        let search_entry = self.imp().search_entry.get();
+        self.imp().search_bar.connect_entry(&search_entry);
+        search_entry.connect_search_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |entry| {
+                let term = entry.text().as_str().to_owned();
+                if !term.is_empty() && player.queue().n_items() > LOCAL_SEARCH_LIMIT {
+                    // Keep showing the previous results until MPD gets back to us
+                    player.client().queue_background(BackgroundTask::SearchQueue(term), true);
+                } else {
+                    this.imp().search_ids.replace(None);
+                    this.imp().search_filter.changed(gtk::FilterChange::Different);
+                }
+            }
+        ));
+        client_state.connect_closure(
+            "queue-search-results",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, term: String, ids: glib::BoxedAnyObject| {
+                    // Discard outdated results
+                    if this.imp().search_entry.text().as_str() == term {
+                        this.imp().search_ids.replace(Some(
+                            ids.borrow::<Vec<u32>>().iter().copied().collect()
+                        ));
+                        this.imp().search_filter.changed(gtk::FilterChange::Different);
+                    }
+                }
+            )
+        );
+        // Show the whole queue again once the search bar is closed
+        self.imp().search_bar.connect_search_mode_enabled_notify(clone!(
+            #[weak]
+            search_entry,
+            move |bar| {
+                if !bar.is_search_mode() {
+                    search_entry.set_text("");
+                }
+            }
+        ));
+
+        self.imp().jump_to_current.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                this.jump_to_current(&player);
+            }
+        ));
+        self.imp().play_next.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                player.play_next(&this.selected_positions());
+            }
+        ));
+    }
+
+    /// Repopulate the snapshot popover's list from the local DB.
+    fn refresh_snapshots(&self, player: &Player) {
+        let list = self.imp().snapshot_list.get();
+        list.remove_all();
+        let snapshots = match sqlite::get_queue_snapshots() {
+            Ok(snapshots) => snapshots,
+            Err(db_err) => {
+                dbg!(db_err);
+                return;
+            }
+        };
+        list.set_visible(!snapshots.is_empty());
+        for (name, saved_at, n_songs) in snapshots.into_iter() {
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(&name))
+                .subtitle(format!(
+                    "{} · {saved_at}",
+                    format_song_count(n_songs).unwrap_or_default()
+                ))
+                .build();
+            let restore_btn = gtk::Button::builder()
+                .icon_name("play-symbolic")
+                .tooltip_text("Restore this snapshot")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            let delete_btn = gtk::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text("Delete this snapshot")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            restore_btn.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                player,
+                #[strong]
+                name,
+                move |_| {
+                    this.imp().snapshots.set_active(false);
+                    player.restore_snapshot(&name);
+                }
+            ));
+            delete_btn.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                player,
+                #[strong]
+                name,
+                move |_| {
+                    if let Err(db_err) = sqlite::delete_queue_snapshot(&name) {
+                        dbg!(db_err);
+                    }
+                    this.refresh_snapshots(&player);
+                }
+            ));
+            row.add_suffix(&restore_btn);
+            row.add_suffix(&delete_btn);
+            list.append(&row);
+        }
+    }
+
+    fn bind_snapshots(&self, player: &Player) {
+        let snapshot_name = self.imp().snapshot_name.get();
+        let snapshot_save = self.imp().snapshot_save.get();
+        self.imp().snapshots.connect_active_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |btn| {
+                if btn.is_active() {
+                    this.refresh_snapshots(&player);
+                }
+            }
+        ));
+
+        snapshot_name.connect_changed(clone!(
+            #[weak]
+            snapshot_save,
+            move |entry| {
+                snapshot_save.set_sensitive(entry.text_length() > 0);
+            }
+        ));
+
+        snapshot_save.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            #[weak]
+            snapshot_name,
+            move |_| {
+                let name = snapshot_name.text().as_str().trim().to_owned();
+                if name.is_empty() {
+                    return;
+                }
+                player.save_snapshot(&name);
+                snapshot_name.set_text("");
+                this.refresh_snapshots(&player);
+            }
+        ));
+    }
+
+    fn bind_handoff(&self, player: &Player) {
+        let handoff_btn = self.imp().handoff.get();
+        let handoff_list = self.imp().handoff_list.get();
+        let conn_settings = settings_manager().child("client");
+        conn_settings
+            .bind("handoff-servers", &handoff_btn, "visible")
+            .get_only()
+            .mapping(|servers, _| Some((servers.n_children() > 0).to_value()))
+            .build();
+
+        handoff_btn.connect_active_notify(clone!(
+            #[weak]
+            player,
+            #[weak]
+            handoff_list,
+            move |btn| {
+                if !btn.is_active() {
+                    return;
+                }
+                handoff_list.remove_all();
+                let servers = settings_manager().child("client").value("handoff-servers");
+                for server in servers.array_iter_str().unwrap() {
+                    let row = adw::ActionRow::builder()
+                        .title(glib::markup_escape_text(server))
+                        .activatable(true)
+                        .build();
+                    let server = server.to_owned();
+                    row.connect_activated(clone!(
+                        #[weak]
+                        player,
+                        #[weak]
+                        btn,
+                        move |_| {
+                            btn.set_active(false);
+                            player.hand_off(&server);
+                        }
+                    ));
+                    handoff_list.append(&row);
+                }
+            }
+        ));
+    }
+
+    /// Repopulate the group popover with the configured servers & their membership status.
+    fn refresh_group(&self, player: &Player) {
+        let list = self.imp().group_list.get();
+        list.remove_all();
+        let members = player.client().group_members();
+        let servers = settings_manager().child("client").value("handoff-servers");
+        for server in servers.array_iter_str().unwrap() {
+            let state = members
+                .iter()
+                .find(|(address, _)| address == server)
+                .map(|(_, state)| *state);
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(server))
+                .subtitle(state.map_or("Not in group", |state| state.description()))
+                .build();
+            let check = gtk::CheckButton::builder()
+                .active(state.is_some())
+                .valign(gtk::Align::Center)
+                .build();
+            row.add_prefix(&check);
+            row.set_activatable_widget(Some(&check));
+            let server = server.to_owned();
+            check.connect_toggled(clone!(
+                #[weak]
+                player,
+                move |check| {
+                    let client = player.client().clone();
+                    if check.is_active() {
+                        let server = server.clone();
+                        glib::spawn_future_local(async move {
+                            client.add_group_member(server).await;
+                        });
+                    } else {
+                        client.remove_group_member(&server);
+                    }
+                }
+            ));
+            list.append(&row);
+        }
+    }
+
+    fn bind_group(&self, player: &Player, client_state: &ClientState) {
+        let group_btn = self.imp().group.get();
+        let conn_settings = settings_manager().child("client");
+        conn_settings
+            .bind("handoff-servers", &group_btn, "visible")
+            .get_only()
+            .mapping(|servers, _| Some((servers.n_children() > 0).to_value()))
+            .build();
+        conn_settings
+            .bind("group-volume-relative", &self.imp().group_volume_relative.get(), "active")
+            .build();
+
+        group_btn.connect_active_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |btn| {
+                if btn.is_active() {
+                    this.refresh_group(&player);
+                }
+            }
+        ));
+
+        client_state.connect_closure(
+            "group-changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                player,
+                move |_: ClientState| {
+                    if this.imp().group.is_active() {
+                        this.refresh_group(&player);
+                    }
+                }
+            ),
+        );
+
+        self.imp().group_resync.connect_clicked(clone!(
+            #[weak]
+            player,
+            move |_| {
+                player.client().resync_group();
+            }
+        ));
+    }
+
+    fn bind_range_editor(&self, player: &Player) {
+        let range_btn = self.imp().range.get();
+        let range_start = self.imp().range_start.get();
+        let range_end = self.imp().range_end.get();
+        range_btn.connect_active_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            #[weak]
+            range_start,
+            #[weak]
+            range_end,
+            move |btn| {
+                if !btn.is_active() {
+                    return;
+                }
+                // Fill in the selected song's current range
+                let range = this
+                    .selected_pos()
+                    .and_then(|pos| player.queue().item(pos).and_downcast::<Song>())
+                    .and_then(|song| song.get_range());
+                range_start.set_text(
+                    &range.map_or(String::new(), |(start, _)| format_secs_as_duration(start))
+                );
+                range_end.set_text(
+                    &range.and_then(|(_, end)| end).map_or(String::new(), format_secs_as_duration)
+                );
+            }
+        ));
+
+        self.imp().range_apply.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            #[weak]
+            range_btn,
+            #[weak]
+            range_start,
+            #[weak]
+            range_end,
+            move |_| {
+                let Some(pos) = this.selected_pos() else {
+                    return;
+                };
+                // Empty start means from the beginning, empty end means until the end.
+                let start = if range_start.text().trim().is_empty() {
+                    Some(0.0)
+                } else {
+                    parse_duration_secs(range_start.text().as_str())
+                };
+                let end = if range_end.text().trim().is_empty() {
+                    Some(None)
+                } else {
+                    parse_duration_secs(range_end.text().as_str()).map(Some)
+                };
+                match (start, end) {
+                    (Some(start), Some(end)) if end.is_none_or(|end| end > start) => {
+                        range_start.remove_css_class("error");
+                        range_end.remove_css_class("error");
+                        range_btn.set_active(false);
+                        if start == 0.0 && end.is_none() {
+                            player.set_range(pos, None);
+                        } else {
+                            player.set_range(pos, Some((start, end)));
+                        }
+                    }
+                    _ => {
+                        if start.is_none() {
+                            range_start.add_css_class("error");
+                        } else {
+                            range_end.add_css_class("error");
+                        }
+                    }
+                }
+            }
+        ));
+
+        self.imp().range_reset.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            #[weak]
+            range_btn,
+            move |_| {
+                if let Some(pos) = this.selected_pos() {
+                    range_btn.set_active(false);
+                    player.set_range(pos, None);
+                }
+            }
+        ));
+    }
+
     fn show_save_error_dialog(&self, name: String, player: Player) {
         // TODO: translatable
         let diag = adw::AlertDialog::builder()
@@ -504,6 +1203,94 @@ impl QueueView {
                 player.clear_queue();
             }
         ));
+
+        let undo_queue_btn = self.imp().undo_queue.get();
+        let redo_queue_btn = self.imp().redo_queue.get();
+        player
+            .bind_property("can-undo-queue", &undo_queue_btn, "sensitive")
+            .sync_create()
+            .build();
+        player
+            .bind_property("can-redo-queue", &redo_queue_btn, "sensitive")
+            .sync_create()
+            .build();
+        undo_queue_btn.connect_clicked(clone!(
+            #[weak]
+            player,
+            move |_| {
+                player.undo_queue();
+            }
+        ));
+        redo_queue_btn.connect_clicked(clone!(
+            #[weak]
+            player,
+            move |_| {
+                player.redo_queue();
+            }
+        ));
+
+        let auto_dj_banner = self.imp().auto_dj_banner.get();
+        player
+            .bind_property("auto-dj", &auto_dj_banner, "revealed")
+            .transform_to(|_, desc: String| Some(!desc.is_empty()))
+            .sync_create()
+            .build();
+        player
+            .bind_property("auto-dj", &auto_dj_banner, "title")
+            .sync_create()
+            .build();
+        auto_dj_banner.connect_button_clicked(clone!(
+            #[weak]
+            player,
+            move |_| {
+                player.stop_auto_dj();
+            }
+        ));
+
+        let auto_dj_source = self.imp().auto_dj_source.get();
+        let auto_dj_name = self.imp().auto_dj_name.get();
+        let auto_dj_start = self.imp().auto_dj_start.get();
+        let auto_dj_btn = self.imp().auto_dj.get();
+        // Only the whole-library source doesn't need a name
+        auto_dj_source
+            .bind_property("selected", &auto_dj_name, "visible")
+            .transform_to(|_, idx: u32| Some(idx > 0))
+            .sync_create()
+            .build();
+        settings_manager()
+            .child("player")
+            .bind(
+                "auto-dj-remove-played",
+                &self.imp().auto_dj_remove_played.get(),
+                "active"
+            )
+            .build();
+        auto_dj_start.connect_clicked(clone!(
+            #[weak]
+            player,
+            #[weak]
+            auto_dj_source,
+            #[weak]
+            auto_dj_name,
+            #[weak]
+            auto_dj_btn,
+            move |_| {
+                let name = auto_dj_name.text().as_str().to_owned();
+                let source = match auto_dj_source.selected() {
+                    0 => AutoDjSource::Library,
+                    _ if name.is_empty() => {
+                        return;
+                    }
+                    1 => AutoDjSource::Genre(name),
+                    2 => AutoDjSource::Playlist(name),
+                    _ => AutoDjSource::DynamicPlaylist(name),
+                };
+                auto_dj_btn.set_active(false);
+                // Start playing right away if there's nothing to continue from.
+                let replace = player.queue().n_items() == 0;
+                player.start_auto_dj(source, replace);
+            }
+        ));
     }
 
     pub fn setup(&self, player: &Player, cache: Rc<Cache>, client_state: &ClientState, window: EuphonicaWindow) {
@@ -511,6 +1298,11 @@ impl QueueView {
         self.setup_listview(player, cache);
         self.imp().player_pane.setup(player, client_state);
         self.bind_state(player);
+        self.bind_range_editor(player);
+        self.bind_snapshots(player);
+        self.bind_handoff(player);
+        self.bind_group(player, client_state);
+        self.bind_search(player, client_state);
         self.imp().player.set(Some(player));
     }
 }
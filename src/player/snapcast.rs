@@ -0,0 +1,435 @@
+use std::{
+    cell::{Cell, RefCell},
+    io::{BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use async_channel::{Receiver, Sender};
+use glib::{clone, subclass::Signal};
+use gtk::{gio, glib, prelude::*, subclass::prelude::*};
+use serde_json::{json, Value};
+
+use crate::utils::settings_manager;
+
+// Optional Snapcast integration, for MPD setups that feed a snapserver through a FIFO,
+// pipe or snapcast output. We speak Snapcast's newline-delimited JSON-RPC over TCP.
+// A reader thread forwards every line it receives to the main thread, which keeps a
+// simple model of the server's groups & clients. Requests are queued up for a writer
+// thread instead, so that a stalled snapserver can never freeze the UI.
+
+/// A Snapcast client, i.e. one speaker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapClient {
+    pub id: String,
+    pub name: String,
+    pub connected: bool,
+    pub volume: u32, // Percent
+    pub muted: bool,
+    pub latency: i32, // Milliseconds
+}
+
+/// A group of Snapcast clients playing the same stream in sync.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapGroup {
+    pub id: String,
+    pub name: String,
+    pub stream_id: String,
+    pub muted: bool,
+    pub clients: Vec<SnapClient>,
+}
+
+impl SnapGroup {
+    /// Group name for display. Unnamed groups are described by their stream.
+    pub fn display_name(&self) -> String {
+        if self.name.is_empty() {
+            format!("Group ({})", self.stream_id)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+fn parse_client(val: &Value) -> Option<SnapClient> {
+    let config = val.get("config")?;
+    let configured_name = config.get("name").and_then(Value::as_str).unwrap_or_default();
+    let name = if configured_name.is_empty() {
+        val.pointer("/host/name").and_then(Value::as_str).unwrap_or_default()
+    } else {
+        configured_name
+    };
+    Some(SnapClient {
+        id: val.get("id")?.as_str()?.to_owned(),
+        name: name.to_owned(),
+        connected: val.get("connected").and_then(Value::as_bool).unwrap_or(false),
+        volume: config.pointer("/volume/percent").and_then(Value::as_u64).unwrap_or(100) as u32,
+        muted: config.pointer("/volume/muted").and_then(Value::as_bool).unwrap_or(false),
+        latency: config.get("latency").and_then(Value::as_i64).unwrap_or(0) as i32,
+    })
+}
+
+fn parse_groups(server: &Value) -> Vec<SnapGroup> {
+    server
+        .get("groups")
+        .and_then(Value::as_array)
+        .map(|groups| {
+            groups
+                .iter()
+                .filter_map(|group| {
+                    Some(SnapGroup {
+                        id: group.get("id")?.as_str()?.to_owned(),
+                        name: group.get("name").and_then(Value::as_str).unwrap_or_default().to_owned(),
+                        stream_id: group.get("stream_id").and_then(Value::as_str).unwrap_or_default().to_owned(),
+                        muted: group.get("muted").and_then(Value::as_bool).unwrap_or(false),
+                        clients: group
+                            .get("clients")
+                            .and_then(Value::as_array)
+                            .map(|clients| clients.iter().filter_map(parse_client).collect())
+                            .unwrap_or_default(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Blocking. Forward every line from the snapserver to the main thread until the
+/// connection closes.
+fn read_loop(stream: TcpStream, sender: Sender<Option<String>>) {
+    let reader = BufReader::new(stream);
+    for line in reader.lines() {
+        match line {
+            Ok(line) => {
+                if sender.send_blocking(Some(line)).is_err() {
+                    return;
+                }
+            }
+            Err(_) => {
+                break;
+            }
+        }
+    }
+    // Signal disconnection
+    let _ = sender.send_blocking(None);
+}
+
+/// Blocking. Write queued requests to the snapserver until the queue is closed. A failed
+/// write shuts the connection down, which in turn winds the reader loop down.
+fn write_loop(mut stream: TcpStream, requests: Receiver<String>) {
+    while let Ok(msg) = requests.recv_blocking() {
+        if writeln!(stream, "{msg}").is_err() {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+            return;
+        }
+    }
+}
+
+mod imp {
+    use super::*;
+    use std::cell::OnceCell;
+    use glib::{ParamSpec, ParamSpecBoolean};
+    use once_cell::sync::Lazy;
+
+    #[derive(Default)]
+    pub struct Snapcast {
+        // Only kept for shutting the connection down. Requests go through the writer thread.
+        pub stream: RefCell<Option<TcpStream>>,
+        pub requests: RefCell<Option<Sender<String>>>,
+        pub connecting: Cell<bool>,
+        pub groups: RefCell<Vec<SnapGroup>>,
+        pub next_request_id: Cell<u64>,
+        // Bumped on every connection attempt & disconnection so that neither a stale
+        // reader loop nor a stale connection attempt can tear down or replace its successor.
+        pub generation: Cell<u64>,
+        // Kept around so that we keep receiving change notifications.
+        pub settings: OnceCell<gio::Settings>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Snapcast {
+        const NAME: &'static str = "EuphonicaSnapcast";
+        type Type = super::Snapcast;
+    }
+
+    impl ObjectImpl for Snapcast {
+        fn properties() -> &'static [ParamSpec] {
+            static PROPERTIES: Lazy<Vec<ParamSpec>> =
+                Lazy::new(|| vec![ParamSpecBoolean::builder("connected").read_only().build()]);
+            PROPERTIES.as_ref()
+        }
+
+        fn property(&self, _id: usize, pspec: &ParamSpec) -> glib::Value {
+            match pspec.name() {
+                "connected" => self.stream.borrow().is_some().to_value(),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![
+                    // Groups or clients were added, removed or moved around
+                    Signal::builder("changed").build(),
+                    // A client's volume, mute state or latency changed
+                    Signal::builder("client-updated")
+                        .param_types([String::static_type()])
+                        .build(),
+                ]
+            })
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct Snapcast(ObjectSubclass<imp::Snapcast>);
+}
+
+impl Default for Snapcast {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl Snapcast {
+    /// Start following the settings: connect whenever the integration is enabled and
+    /// reconnect when the snapserver address changes.
+    pub fn setup(&self) {
+        let settings = self.imp().settings.get_or_init(|| settings_manager().child("client"));
+        settings.connect_changed(
+            None,
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, key| {
+                    if matches!(key, "snapcast-enabled" | "snapcast-host" | "snapcast-port") {
+                        this.disconnect();
+                        this.maybe_connect();
+                    }
+                }
+            ),
+        );
+        self.maybe_connect();
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.imp().stream.borrow().is_some()
+    }
+
+    pub fn groups(&self) -> Vec<SnapGroup> {
+        self.imp().groups.borrow().clone()
+    }
+
+    pub fn client(&self, id: &str) -> Option<SnapClient> {
+        self.imp()
+            .groups
+            .borrow()
+            .iter()
+            .flat_map(|group| group.clients.iter())
+            .find(|client| client.id == id)
+            .cloned()
+    }
+
+    pub fn maybe_connect(&self) {
+        let settings = settings_manager().child("client");
+        if !settings.boolean("snapcast-enabled") || self.is_connected() || self.imp().connecting.get() {
+            return;
+        }
+        let generation = self.imp().generation.get() + 1;
+        self.imp().generation.set(generation);
+        self.imp().connecting.set(true);
+        let addr = format!("{}:{}", settings.string("snapcast-host"), settings.uint("snapcast-port"));
+        let (sender, receiver) = async_channel::unbounded::<Option<String>>();
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                let res = gio::spawn_blocking(move || -> std::io::Result<(TcpStream, TcpStream, TcpStream)> {
+                    let sock_addr = addr.to_socket_addrs()?.next().ok_or(std::io::ErrorKind::NotFound)?;
+                    let stream = TcpStream::connect_timeout(&sock_addr, Duration::from_secs(3))?;
+                    let reader = stream.try_clone()?;
+                    let writer = stream.try_clone()?;
+                    Ok((stream, reader, writer))
+                })
+                .await;
+                if this.imp().generation.get() != generation {
+                    // Disconnected (e.g. the address changed) while we were connecting.
+                    if let Ok(Ok((stream, _, _))) = res {
+                        let _ = stream.shutdown(std::net::Shutdown::Both);
+                    }
+                    return;
+                }
+                this.imp().connecting.set(false);
+                match res {
+                    Ok(Ok((stream, reader, writer))) => {
+                        let (request_sender, request_receiver) = async_channel::unbounded::<String>();
+                        std::thread::spawn(move || read_loop(reader, sender));
+                        std::thread::spawn(move || write_loop(writer, request_receiver));
+                        this.imp().requests.replace(Some(request_sender));
+                        if let Some(old_stream) = this.imp().stream.replace(Some(stream)) {
+                            let _ = old_stream.shutdown(std::net::Shutdown::Both);
+                        }
+                        this.notify("connected");
+                        this.request("Server.GetStatus", json!({}));
+                        while let Ok(Some(line)) = receiver.recv().await {
+                            if this.imp().generation.get() != generation {
+                                break;
+                            }
+                            this.handle_line(&line);
+                        }
+                        // Connection closed by the server (or by us)
+                        if this.imp().generation.get() == generation {
+                            this.disconnect();
+                        }
+                    }
+                    Ok(Err(e)) => {
+                        println!("[Snapcast] Could not connect: {e:?}");
+                    }
+                    Err(_) => {}
+                }
+            }
+        ));
+    }
+
+    pub fn disconnect(&self) {
+        // Also abandons any connection attempt underway.
+        self.imp().generation.set(self.imp().generation.get() + 1);
+        self.imp().connecting.set(false);
+        // Closing the queue winds the writer thread down.
+        self.imp().requests.take();
+        if let Some(stream) = self.imp().stream.take() {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+            self.imp().groups.borrow_mut().clear();
+            self.notify("connected");
+            self.emit_by_name::<()>("changed", &[]);
+        }
+    }
+
+    fn request(&self, method: &str, params: Value) {
+        let id = self.imp().next_request_id.get();
+        self.imp().next_request_id.set(id + 1);
+        let msg = json!({"id": id, "jsonrpc": "2.0", "method": method, "params": params});
+        if let Some(requests) = self.imp().requests.borrow().as_ref() {
+            let _ = requests.try_send(msg.to_string());
+        }
+    }
+
+    fn handle_line(&self, line: &str) {
+        let Ok(msg) = serde_json::from_str::<Value>(line) else {
+            return;
+        };
+        if let Some(server) = msg.pointer("/result/server") {
+            // Reply to Server.GetStatus
+            self.replace_groups(parse_groups(server));
+            return;
+        }
+        // Notifications have a method but no id. Batched notifications are rare enough
+        // that we simply refetch everything when we see one.
+        let Some(method) = msg.get("method").and_then(Value::as_str) else {
+            if msg.is_array() {
+                self.request("Server.GetStatus", json!({}));
+            }
+            return;
+        };
+        let params = msg.get("params").cloned().unwrap_or_default();
+        match method {
+            "Server.OnUpdate" => {
+                if let Some(server) = params.get("server") {
+                    self.replace_groups(parse_groups(server));
+                }
+            }
+            "Client.OnVolumeChanged" => {
+                let volume = params.get("volume");
+                self.update_client(&params, |client| {
+                    if let Some(percent) = volume.and_then(|v| v.get("percent")).and_then(Value::as_u64) {
+                        client.volume = percent as u32;
+                    }
+                    if let Some(muted) = volume.and_then(|v| v.get("muted")).and_then(Value::as_bool) {
+                        client.muted = muted;
+                    }
+                });
+            }
+            "Client.OnLatencyChanged" => {
+                let latency = params.get("latency").and_then(Value::as_i64);
+                self.update_client(&params, |client| {
+                    if let Some(latency) = latency {
+                        client.latency = latency as i32;
+                    }
+                });
+            }
+            _ => {
+                // Connects, disconnects, renames, group & stream changes: the structure
+                // may have changed so just fetch the whole thing again.
+                self.request("Server.GetStatus", json!({}));
+            }
+        }
+    }
+
+    fn replace_groups(&self, groups: Vec<SnapGroup>) {
+        let changed = *self.imp().groups.borrow() != groups;
+        if changed {
+            self.imp().groups.replace(groups);
+            self.emit_by_name::<()>("changed", &[]);
+        }
+    }
+
+    fn update_client(&self, params: &Value, f: impl FnOnce(&mut SnapClient)) {
+        let Some(id) = params.get("id").and_then(Value::as_str) else {
+            return;
+        };
+        let found = self
+            .imp()
+            .groups
+            .borrow_mut()
+            .iter_mut()
+            .flat_map(|group| group.clients.iter_mut())
+            .find(|client| client.id == id)
+            .map(f)
+            .is_some();
+        if found {
+            self.emit_by_name::<()>("client-updated", &[&id]);
+        }
+    }
+
+    /// Set a client's volume (percent) & mute state.
+    pub fn set_client_volume(&self, id: &str, volume: u32, muted: bool) {
+        let params = json!({"id": id, "volume": {"percent": volume.min(100), "muted": muted}});
+        self.update_client(&params, |client| {
+            client.volume = volume.min(100);
+            client.muted = muted;
+        });
+        self.request("Client.SetVolume", params);
+    }
+
+    /// Set a client's latency compensation, in milliseconds.
+    pub fn set_client_latency(&self, id: &str, latency: i32) {
+        let params = json!({"id": id, "latency": latency});
+        self.update_client(&params, |client| client.latency = latency);
+        self.request("Client.SetLatency", params);
+    }
+
+    /// Move a client into another group. The snapserver takes it out of its old group.
+    pub fn move_client(&self, client_id: &str, group_id: &str) {
+        let clients: Option<Vec<String>> = self
+            .imp()
+            .groups
+            .borrow()
+            .iter()
+            .find(|group| group.id == group_id)
+            .map(|group| {
+                group
+                    .clients
+                    .iter()
+                    .map(|client| client.id.clone())
+                    .filter(|id| id != client_id)
+                    .chain(std::iter::once(client_id.to_owned()))
+                    .collect()
+            });
+        if let Some(clients) = clients {
+            self.request("Group.SetClients", json!({"id": group_id, "clients": clients}));
+            // Clients only get notified of Server.OnUpdate, not the requester.
+            self.request("Server.GetStatus", json!({}));
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::cell::{Cell, RefCell};
+
+use adw::prelude::*;
+use glib::{clone, closure_local, SignalHandlerId};
+use gtk::{glib, subclass::prelude::*, CompositeTemplate};
+use rustc_hash::FxHashMap;
+
+use super::snapcast::{SnapClient, Snapcast};
+
+/// Widgets of one client row that can be updated in place.
+struct ClientWidgets {
+    row: adw::ExpanderRow,
+    mute: gtk::ToggleButton,
+    volume: gtk::Scale,
+    latency: adw::SpinRow,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, CompositeTemplate)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/player/snapcast-panel.ui")]
+    pub struct SnapcastPanel {
+        #[template_child]
+        pub status: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub groups: TemplateChild<gtk::Box>,
+        pub snapcast: RefCell<Option<Snapcast>>,
+        // Handlers connected to the above, to be disconnected when binding to another
+        // instance or when this panel goes away.
+        pub handlers: RefCell<Vec<SignalHandlerId>>,
+        // Whether any MPD output might be feeding the snapserver. The panel is only
+        // shown when that's the case.
+        pub feeds_snapcast: Cell<bool>,
+        pub clients: RefCell<FxHashMap<String, ClientWidgets>>,
+        // Set while we update widgets to match the server, so that their handlers
+        // don't send the same values back.
+        pub updating: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SnapcastPanel {
+        const NAME: &'static str = "EuphonicaSnapcastPanel";
+        type Type = super::SnapcastPanel;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for SnapcastPanel {
+        fn dispose(&self) {
+            self.obj().unbind();
+        }
+    }
+
+    impl WidgetImpl for SnapcastPanel {}
+
+    impl BoxImpl for SnapcastPanel {}
+}
+
+glib::wrapper! {
+    pub struct SnapcastPanel(ObjectSubclass<imp::SnapcastPanel>)
+    @extends gtk::Box, gtk::Widget,
+    @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Orientable;
+}
+
+impl Default for SnapcastPanel {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl SnapcastPanel {
+    pub fn bind(&self, snapcast: &Snapcast) {
+        self.unbind();
+        let mut handlers = self.imp().handlers.borrow_mut();
+        handlers.push(snapcast.connect_closure(
+            "changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: Snapcast| {
+                    this.rebuild();
+                }
+            ),
+        ));
+        handlers.push(snapcast.connect_closure(
+            "client-updated",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |snapcast: Snapcast, id: String| {
+                    if let Some(client) = snapcast.client(&id) {
+                        this.update_client(&client);
+                    }
+                }
+            ),
+        ));
+        handlers.push(snapcast.connect_notify_local(
+            Some("connected"),
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _| {
+                    this.update_visibility();
+                }
+            ),
+        ));
+        drop(handlers);
+        self.imp().snapcast.replace(Some(snapcast.clone()));
+        self.rebuild();
+        self.update_visibility();
+    }
+
+    fn unbind(&self) {
+        if let Some(snapcast) = self.imp().snapcast.take() {
+            for handler in self.imp().handlers.take() {
+                snapcast.disconnect(handler);
+            }
+        }
+    }
+
+    /// Tell the panel whether any MPD output might be feeding the snapserver.
+    pub fn set_feeds_snapcast(&self, feeds: bool) {
+        self.imp().feeds_snapcast.set(feeds);
+        self.update_visibility();
+    }
+
+    fn update_visibility(&self) {
+        let connected = self
+            .imp()
+            .snapcast
+            .borrow()
+            .as_ref()
+            .is_some_and(|snapcast| snapcast.is_connected());
+        self.set_visible(connected && self.imp().feeds_snapcast.get());
+    }
+
+    fn update_client(&self, client: &SnapClient) {
+        if let Some(widgets) = self.imp().clients.borrow().get(&client.id) {
+            self.imp().updating.set(true);
+            widgets.row.set_subtitle(&client_subtitle(client));
+            widgets.mute.set_active(client.muted);
+            widgets.volume.set_value(client.volume as f64);
+            widgets.latency.set_value(client.latency as f64);
+            self.imp().updating.set(false);
+        }
+    }
+
+    fn rebuild(&self) {
+        let Some(snapcast) = self.imp().snapcast.borrow().clone() else {
+            return;
+        };
+        let groups_box = self.imp().groups.get();
+        while let Some(child) = groups_box.first_child() {
+            groups_box.remove(&child);
+        }
+        self.imp().clients.borrow_mut().clear();
+
+        let groups = snapcast.groups();
+        self.imp().status.set_visible(groups.is_empty());
+        let group_names = gtk::StringList::new(&[]);
+        for group in groups.iter() {
+            group_names.append(&group.display_name());
+        }
+        for (group_idx, group) in groups.iter().enumerate() {
+            let pref_group = adw::PreferencesGroup::builder()
+                .title(glib::markup_escape_text(&group.display_name()))
+                .description(glib::markup_escape_text(&group.stream_id))
+                .build();
+            for client in group.clients.iter() {
+                pref_group.add(&self.client_row(&snapcast, client, &group_names, group_idx));
+            }
+            groups_box.append(&pref_group);
+        }
+    }
+
+    fn client_row(
+        &self,
+        snapcast: &Snapcast,
+        client: &SnapClient,
+        group_names: &gtk::StringList,
+        group_idx: usize,
+    ) -> adw::ExpanderRow {
+        let row = adw::ExpanderRow::builder()
+            .title(glib::markup_escape_text(&client.name))
+            .subtitle(client_subtitle(client))
+            .build();
+
+        let mute = gtk::ToggleButton::builder()
+            .icon_name("audio-volume-muted-symbolic")
+            .tooltip_text("Mute")
+            .valign(gtk::Align::Center)
+            .active(client.muted)
+            .css_classes(["flat"])
+            .build();
+        row.add_suffix(&mute);
+
+        let volume = gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.0, 100.0, 1.0);
+        volume.set_value(client.volume as f64);
+        volume.set_hexpand(true);
+        let volume_row = adw::ActionRow::builder().title("Volume").build();
+        volume_row.add_suffix(&volume);
+        row.add_row(&volume_row);
+
+        let latency = adw::SpinRow::with_range(-10000.0, 10000.0, 1.0);
+        latency.set_title("Latency");
+        latency.set_subtitle("Milliseconds");
+        latency.set_value(client.latency as f64);
+        row.add_row(&latency);
+
+        let group = adw::ComboRow::builder()
+            .title("Group")
+            .model(group_names)
+            .selected(group_idx as u32)
+            .build();
+        row.add_row(&group);
+
+        let id = client.id.clone();
+        mute.connect_toggled(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            snapcast,
+            #[weak]
+            volume,
+            #[strong]
+            id,
+            move |btn| {
+                if !this.imp().updating.get() {
+                    snapcast.set_client_volume(&id, volume.value().round() as u32, btn.is_active());
+                }
+            }
+        ));
+        volume.connect_value_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            snapcast,
+            #[weak]
+            mute,
+            #[strong]
+            id,
+            move |scale| {
+                if !this.imp().updating.get() {
+                    snapcast.set_client_volume(&id, scale.value().round() as u32, mute.is_active());
+                }
+            }
+        ));
+        latency.connect_value_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            snapcast,
+            #[strong]
+            id,
+            move |spin| {
+                if !this.imp().updating.get() {
+                    snapcast.set_client_latency(&id, spin.value() as i32);
+                }
+            }
+        ));
+        group.connect_selected_notify(clone!(
+            #[weak]
+            snapcast,
+            #[strong]
+            id,
+            move |combo| {
+                if let Some(target) = snapcast.groups().get(combo.selected() as usize) {
+                    snapcast.move_client(&id, &target.id);
+                }
+            }
+        ));
+
+        self.imp().clients.borrow_mut().insert(
+            client.id.clone(),
+            ClientWidgets {
+                row: row.clone(),
+                mute,
+                volume,
+                latency,
+            },
+        );
+        row
+    }
+}
+
+fn client_subtitle(client: &SnapClient) -> String {
+    if !client.connected {
+        "Disconnected".to_owned()
+    } else if client.muted {
+        "Muted".to_owned()
+    } else {
+        format!("{}%", client.volume)
+    }
+}
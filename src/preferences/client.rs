@@ -4,14 +4,17 @@ use std::{rc::Rc, str::FromStr};
 
 use adw::prelude::*;
 use adw::subclass::prelude::*;
-use gtk::{glib, CompositeTemplate};
+use gtk::{gio, glib, CompositeTemplate};
 
 use glib::clone;
 
 use mpd::status::AudioFormat;
 
 use crate::{
-    client::{password::{get_mpd_password, set_mpd_password}, state::StickersSupportLevel, ClientState, ConnectionState, MpdWrapper},
+    client::{
+        password::{get_mpd_password, set_mpd_password, set_server_password},
+        split_credentials, state::StickersSupportLevel, ClientState, ConnectionState, MpdWrapper
+    },
     player::{FftStatus, Player},
     utils,
 };
@@ -74,6 +77,78 @@ fn set_status_icon(img: &gtk::Image, state: StatusIconState) {
     }
 }
 
+/// Fill a text buffer with a string array setting, one item per line.
+fn set_lines(buf: &gtk::TextBuffer, settings: &gio::Settings, key: &str) {
+    buf.set_text(
+        &settings
+            .value(key)
+            .array_iter_str()
+            .unwrap()
+            .collect::<Vec<&str>>()
+            .join("\n"),
+    );
+}
+
+/// Take a server typed as password@address apart, moving the password (if any) into the
+/// keyring so that only the address gets saved in the plain-text server list.
+fn store_server_password(server: &str) -> String {
+    let (password, address) = split_credentials(server);
+    if let Some(password) = password {
+        let (address, password) = (address.to_owned(), password.to_owned());
+        glib::spawn_future_local(async move {
+            if let Err(msg) = set_server_password(&address, Some(&password)).await {
+                println!("{msg}");
+            }
+        });
+    }
+    address.to_owned()
+}
+
+/// Edit a string array setting as lines of a text view, saved when the apply button is clicked.
+/// Each line is passed through `map_line` before saving.
+fn bind_lines(
+    settings: &gio::Settings,
+    key: &'static str,
+    text_view: &gtk::TextView,
+    apply: &gtk::Button,
+    map_line: fn(&str) -> String,
+) {
+    let buf = text_view.buffer();
+    set_lines(&buf, settings, key);
+    // Setting the text above counts as a change too.
+    apply.set_sensitive(false);
+    buf.connect_changed(clone!(
+        #[weak]
+        apply,
+        move |_| {
+            apply.set_sensitive(true);
+        }
+    ));
+    apply.connect_clicked(clone!(
+        #[strong]
+        settings,
+        #[weak]
+        buf,
+        move |btn| {
+            let _ = settings.set_value(
+                key,
+                &buf
+                    .text(&buf.start_iter(), &buf.end_iter(), false)
+                    .to_string()
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(map_line)
+                    .collect::<Vec<String>>()
+                    .to_variant(),
+            );
+            // Show what was actually saved.
+            set_lines(&buf, &settings, key);
+            btn.set_sensitive(false);
+        }
+    ));
+}
+
 mod imp {
     use super::*;
 
@@ -108,6 +183,28 @@ mod imp {
         #[template_child]
         pub mpd_download_album_art: TemplateChild<adw::SwitchRow>,
 
+        // Playback handoff
+        #[template_child]
+        pub handoff_servers: TemplateChild<gtk::TextView>,
+        #[template_child]
+        pub handoff_servers_apply: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub handoff_prefixes: TemplateChild<gtk::TextView>,
+        #[template_child]
+        pub handoff_prefixes_apply: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub handoff_stop_source: TemplateChild<adw::SwitchRow>,
+
+        // Snapcast
+        #[template_child]
+        pub snapcast_enabled: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub snapcast_host: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub snapcast_port: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub snapcast_status: TemplateChild<adw::ActionRow>,
+
         // Visualiser data source
         #[template_child]
         pub viz_source: TemplateChild<adw::ComboRow>,
@@ -519,6 +616,68 @@ impl ClientPreferences {
             .bind("mpd-download-album-art", &mpd_download_album_art, "active")
             .build();
 
+        // Handing off playback switches the connection settings to the other server.
+        conn_settings.connect_changed(
+            None,
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |settings, key| {
+                    match key {
+                        "mpd-host" => this.imp().mpd_host.set_text(&settings.string("mpd-host")),
+                        "mpd-port" => this.imp().mpd_port.set_text(&settings.uint("mpd-port").to_string()),
+                        "mpd-unix-socket" => this.imp().mpd_unix_socket.set_text(&settings.string("mpd-unix-socket")),
+                        "handoff-servers" => {
+                            set_lines(&this.imp().handoff_servers.buffer(), settings, key);
+                            this.imp().handoff_servers_apply.set_sensitive(false);
+                        }
+                        _ => {}
+                    }
+                }
+            )
+        );
+        bind_lines(
+            &conn_settings,
+            "handoff-servers",
+            &imp.handoff_servers.get(),
+            &imp.handoff_servers_apply.get(),
+            store_server_password,
+        );
+        bind_lines(
+            &conn_settings,
+            "handoff-uri-prefixes",
+            &imp.handoff_prefixes.get(),
+            &imp.handoff_prefixes_apply.get(),
+            str::to_owned,
+        );
+        conn_settings
+            .bind("handoff-stop-source", &imp.handoff_stop_source.get(), "active")
+            .build();
+
+        // Snapcast
+        conn_settings
+            .bind("snapcast-enabled", &imp.snapcast_enabled.get(), "active")
+            .build();
+        conn_settings
+            .bind("snapcast-port", &imp.snapcast_port.get(), "value")
+            .build();
+        imp.snapcast_host.set_text(&conn_settings.string("snapcast-host"));
+        imp.snapcast_host.connect_apply(clone!(
+            #[strong]
+            conn_settings,
+            move |entry| {
+                let _ = conn_settings.set_string("snapcast-host", &entry.text());
+            }
+        ));
+        player
+            .snapcast()
+            .bind_property("connected", &imp.snapcast_status.get(), "subtitle")
+            .transform_to(|_, connected: bool| {
+                Some(if connected { "Connected" } else { "Not connected" })
+            })
+            .sync_create()
+            .build();
+
         // Visualiser
         player
             .bind_property(
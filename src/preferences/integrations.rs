@@ -1,10 +1,18 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
-use gtk::{glib, CompositeTemplate};
-use std::cell::OnceCell;
+use gtk::{gio, glib, glib::clone, CompositeTemplate};
+use std::cell::{OnceCell, RefCell};
 use std::rc::Rc;
 
-use crate::{application::update_xdg_background_request, cache::Cache, utils};
+use crate::{
+    application::update_xdg_background_request,
+    cache::{sqlite, Cache, PrefetchState},
+    meta_providers::{
+        plugin::{add_plugin, plugin_keys, remove_plugin},
+        provider_order, provider_settings,
+    },
+    utils,
+};
 
 use super::ProviderRow;
 
@@ -26,6 +34,9 @@ mod imp {
         #[template_child]
         pub xdg_warn_row: TemplateChild<adw::ActionRow>,
 
+        #[template_child]
+        pub local_music_root: TemplateChild<adw::EntryRow>,
+
         #[template_child]
         pub lastfm_key: TemplateChild<adw::EntryRow>,
         #[template_child]
@@ -36,6 +47,40 @@ mod imp {
         #[template_child]
         pub musicbrainz_download_artist_avatar: TemplateChild<adw::SwitchRow>,
 
+        #[template_child]
+        pub wikipedia_download_artist_avatar: TemplateChild<adw::SwitchRow>,
+
+        #[template_child]
+        pub max_concurrent_requests: TemplateChild<adw::SpinRow>,
+
+        #[template_child]
+        pub prefetch_lyrics: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub prefetch_status: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub prefetch_progress: TemplateChild<gtk::ProgressBar>,
+        #[template_child]
+        pub prefetch_start: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub prefetch_pause: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub prefetch_cancel: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub prefetch_report: TemplateChild<adw::ExpanderRow>,
+        pub prefetch_report_rows: RefCell<Vec<adw::ActionRow>>,
+        // Kept around for its change notifications
+        pub meta_settings: OnceCell<gio::Settings>,
+
+        #[template_child]
+        pub meta_ttl_days: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub negative_ttl_days: TemplateChild<adw::SpinRow>,
+
+        #[template_child]
+        pub plugins_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub plugin_command: TemplateChild<adw::EntryRow>,
+
         #[template_child]
         pub order_box: TemplateChild<gtk::ListBox>,
         pub cache: OnceCell<Rc<Cache>>,
@@ -124,6 +169,11 @@ impl IntegrationsPreferences {
             xdg_warn.set_visible(true);
         }
 
+        // Set up local files settings
+        utils::meta_provider_settings("local")
+            .bind("music-root", &imp.local_music_root.get(), "text")
+            .build();
+
         // Set up Last.fm settings
         let lastfm_settings = utils::meta_provider_settings("lastfm");
         let lastfm_key = imp.lastfm_key.get();
@@ -153,16 +203,123 @@ impl IntegrationsPreferences {
             )
             .build();
 
-        // LRCLIB currently needs no settings.
+        // Set up Wikipedia settings
+        utils::meta_provider_settings("wikipedia")
+            .bind(
+                "download-artist-avatar",
+                &imp.wikipedia_download_artist_avatar.get(),
+                "active",
+            )
+            .build();
+
+        // LRCLIB and embedded tags currently need no settings.
+
+        // Set up fetching & expiry settings
+        let meta_settings = settings.child("metaprovider");
+        meta_settings
+            .bind("max-concurrent-requests", &imp.max_concurrent_requests.get(), "value")
+            .build();
+        meta_settings
+            .bind("meta-ttl-days", &imp.meta_ttl_days.get(), "value")
+            .build();
+        meta_settings
+            .bind("negative-ttl-days", &imp.negative_ttl_days.get(), "value")
+            .build();
+
+        // Set up library prefetch
+        meta_settings
+            .bind("prefetch-lyrics", &imp.prefetch_lyrics.get(), "active")
+            .build();
+        meta_settings.connect_changed(
+            Some("prefetch-state"),
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _| {
+                    this.update_prefetch_status();
+                    this.update_prefetch_report();
+                }
+            ),
+        );
+        for key in ["prefetch-position", "prefetch-total"] {
+            meta_settings.connect_changed(
+                Some(key),
+                clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    move |_, _| {
+                        this.update_prefetch_status();
+                    }
+                ),
+            );
+        }
+        let _ = imp.meta_settings.set(meta_settings);
+        imp.prefetch_start.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                let cache = this.imp().cache.get().unwrap();
+                if PrefetchState::get() == PrefetchState::Paused {
+                    cache.resume_prefetch();
+                } else {
+                    cache.start_prefetch();
+                }
+            }
+        ));
+        imp.prefetch_pause.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.imp().cache.get().unwrap().pause_prefetch();
+            }
+        ));
+        imp.prefetch_cancel.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.imp().cache.get().unwrap().cancel_prefetch();
+            }
+        ));
+        self.update_prefetch_status();
+        self.update_prefetch_report();
+
+        // Set up plugins
+        for key in plugin_keys() {
+            self.add_plugin_row(&key);
+        }
+        let plugin_command = imp.plugin_command.get();
+        plugin_command.connect_changed(|entry| {
+            entry.remove_css_class("error");
+        });
+        plugin_command.connect_apply(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |entry| {
+                match add_plugin(entry.text().as_str()) {
+                    Ok(key) => {
+                        entry.set_text("");
+                        this.add_plugin_row(&key);
+                        let order_box = this.imp().order_box.get();
+                        let mut count = 0;
+                        while order_box.row_at_index(count).is_some() {
+                            count += 1;
+                        }
+                        order_box.append(&ProviderRow::new(&this, &key, count));
+                        this.regen_provider_list();
+                    }
+                    Err(e) => {
+                        println!("Unable to add plugin: {e}");
+                        entry.add_css_class("error");
+                    }
+                }
+            }
+        ));
 
         // Set up priority settings
         let order_box = self.imp().order_box.get();
 
-        for row in settings
-            .child("metaprovider")
-            .value("order")
-            .array_iter_str()
-            .unwrap()
+        for row in provider_order()
+            .iter()
             .enumerate()
             .map(|(prio, key)| ProviderRow::new(self, key, prio as i32))
         {
@@ -193,6 +350,153 @@ impl IntegrationsPreferences {
         });
     }
 
+    fn update_prefetch_status(&self) {
+        let imp = self.imp();
+        let settings = utils::settings_manager().child("metaprovider");
+        let pos = settings.uint("prefetch-position");
+        let total = settings.uint("prefetch-total");
+        let state = PrefetchState::get();
+        imp.prefetch_progress.set_fraction(if total > 0 {
+            pos as f64 / total as f64
+        } else {
+            0.0
+        });
+        // Total is only known once the job has listed the library.
+        imp.prefetch_status.set_subtitle(&match state {
+            PrefetchState::Running if total == 0 => "Listing library contents...".to_owned(),
+            PrefetchState::Running => format!("Fetching item {} of {total}", (pos + 1).min(total)),
+            PrefetchState::Paused if total == 0 => "Paused".to_owned(),
+            PrefetchState::Paused => format!("Paused at item {pos} of {total}"),
+            PrefetchState::Idle if total == 0 => "Not started".to_owned(),
+            PrefetchState::Idle if pos >= total => format!("Finished ({total} items)"),
+            PrefetchState::Idle => format!("Stopped at item {pos} of {total}"),
+        });
+        imp.prefetch_start.set_sensitive(state != PrefetchState::Running);
+        imp.prefetch_start.set_tooltip_text(Some(match state {
+            PrefetchState::Paused => "Resume",
+            _ if total > 0 => "Start over",
+            _ => "Start",
+        }));
+        imp.prefetch_pause.set_sensitive(state == PrefetchState::Running);
+        imp.prefetch_cancel.set_sensitive(state != PrefetchState::Idle);
+    }
+
+    /// List what the last prefetch job couldn't find, one row per album, artist or song.
+    fn update_prefetch_report(&self) {
+        // Don't flood the dialog with rows for huge libraries.
+        const MAX_ROWS: usize = 200;
+        let imp = self.imp();
+        let expander = imp.prefetch_report.get();
+        for row in imp.prefetch_report_rows.take() {
+            expander.remove(&row);
+        }
+        // Entries are sorted by kind then name, so those of the same item are adjacent.
+        let mut items: Vec<(String, String, Vec<String>)> = Vec::new();
+        for (kind, name, missing) in sqlite::get_prefetch_report().unwrap_or_default() {
+            match items.last_mut() {
+                Some((last_kind, last_name, all_missing)) if *last_kind == kind && *last_name == name => {
+                    all_missing.push(missing);
+                }
+                _ => items.push((kind, name, vec![missing])),
+            }
+        }
+        expander.set_visible(!items.is_empty());
+        expander.set_subtitle(&format!("{} items with missing metadata", items.len()));
+        let mut rows = Vec::new();
+        for (kind, name, missing) in items.iter().take(MAX_ROWS) {
+            let kind = match kind.as_str() {
+                "album" => "Album",
+                "artist" => "Artist",
+                _ => "Song",
+            };
+            let row = adw::ActionRow::builder()
+                .use_markup(false)
+                .title(name)
+                .subtitle(format!("{kind}: {}", missing.join(", ")))
+                .build();
+            expander.add_row(&row);
+            rows.push(row);
+        }
+        if items.len() > MAX_ROWS {
+            let row = adw::ActionRow::builder()
+                .title(format!("And {} more", items.len() - MAX_ROWS))
+                .build();
+            expander.add_row(&row);
+            rows.push(row);
+        }
+        imp.prefetch_report_rows.replace(rows);
+    }
+
+    /// Add a row for managing the given plugin. Its name, command and last error are kept
+    /// up to date through their settings.
+    fn add_plugin_row(&self, key: &str) {
+        let settings = provider_settings(key);
+        let row = adw::ActionRow::builder()
+            .use_markup(false)
+            .subtitle_lines(1)
+            .build();
+        settings.bind("name", &row, "title").get().build();
+        settings.bind("command", &row, "subtitle").get().build();
+
+        let error_icon = gtk::Image::builder()
+            .icon_name("dialog-warning-symbolic")
+            .css_classes(["warning"])
+            .build();
+        settings
+            .bind("last-error", &error_icon, "tooltip-text")
+            .get()
+            .build();
+        settings
+            .bind("last-error", &error_icon, "visible")
+            .get()
+            .mapping(|val, _| Some((!val.str().unwrap_or_default().is_empty()).to_value()))
+            .build();
+        row.add_suffix(&error_icon);
+
+        let remove_btn = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Remove plugin")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        let key = key.to_owned();
+        remove_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            row,
+            move |_| {
+                this.imp().plugins_group.remove(&row);
+                this.remove_provider_row(&key);
+                remove_plugin(&key);
+            }
+        ));
+        row.add_suffix(&remove_btn);
+        self.imp().plugins_group.add(&row);
+    }
+
+    /// Remove a provider from the priority list, closing the gap it leaves.
+    fn remove_provider_row(&self, key: &str) {
+        let order_box = self.imp().order_box.get();
+        let mut rows: Vec<ProviderRow> = Vec::new();
+        let mut idx = 0;
+        while let Some(row) = order_box.row_at_index(idx) {
+            rows.push(row.downcast::<ProviderRow>().unwrap());
+            idx += 1;
+        }
+        let mut prio = 0;
+        for row in rows {
+            if row.key() == key {
+                order_box.remove(&row);
+            } else {
+                row.set_priority(prio);
+                prio += 1;
+            }
+        }
+        order_box.invalidate_sort();
+        self.regen_provider_list();
+    }
+
     fn regen_provider_list(&self) {
         // Priority & key
         let mut new_order: Vec<(i32, String)> = Vec::new();
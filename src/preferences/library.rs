@@ -41,6 +41,14 @@ mod imp {
         pub n_recent_songs: TemplateChild<adw::SpinRow>,
         #[template_child]
         pub pause_recent: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub co_listening_session_gap: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub n_co_listened_songs: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub dp_rating_priority: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub podcast_refresh_interval: TemplateChild<adw::SpinRow>,
 
         #[template_child]
         pub image_cache_size: TemplateChild<adw::ActionRow>,
@@ -134,6 +142,18 @@ impl LibraryPreferences {
         library_settings
             .bind("pause-recent", &imp.pause_recent.get(), "active")
             .build();
+        library_settings
+            .bind("co-listening-session-gap-mins", &imp.co_listening_session_gap.get(), "value")
+            .build();
+        library_settings
+            .bind("n-co-listened-songs", &imp.n_co_listened_songs.get(), "value")
+            .build();
+        library_settings
+            .bind("dp-rating-priority", &imp.dp_rating_priority.get(), "active")
+            .build();
+        library_settings
+            .bind("podcast-refresh-interval-mins", &imp.podcast_refresh_interval.get(), "value")
+            .build();
 
         // Setup artist section
         let artist_delims_buf = imp.artist_delims.buffer();
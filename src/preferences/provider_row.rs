@@ -2,7 +2,7 @@ use adw::prelude::*;
 use glib::{clone, Object, Properties};
 use gtk::{glib, subclass::prelude::*, CompositeTemplate};
 
-use crate::utils::meta_provider_settings;
+use crate::meta_providers::provider_settings;
 
 use super::IntegrationsPreferences;
 
@@ -74,7 +74,7 @@ impl ProviderRow {
         let _ = res.imp().priority.replace(priority);
         res.set_key(key.to_owned());
         res.setup_actions(controller);
-        let settings = meta_provider_settings(key);
+        let settings = provider_settings(key);
         // At minimum, each provider's GSettings schema must contain these two keys:
         // - "name": a GUI-friendly name string (s)
         // - "enabled" (b)
@@ -23,6 +23,10 @@ mod imp {
         #[template_child]
         pub folders_btn: TemplateChild<SidebarButton>,
         #[template_child]
+        pub radio_btn: TemplateChild<SidebarButton>,
+        #[template_child]
+        pub podcasts_btn: TemplateChild<SidebarButton>,
+        #[template_child]
         pub playlists_section: TemplateChild<gtk::Box>,
         #[template_child]
         pub playlists_btn: TemplateChild<SidebarButton>,
@@ -164,6 +168,26 @@ impl Sidebar {
             }
         ));
 
+        self.imp().radio_btn.connect_toggled(clone!(
+            #[weak]
+            stack,
+            move |btn| {
+                if btn.is_active() {
+                    stack.set_visible_child_name("radio");
+                }
+            }
+        ));
+
+        self.imp().podcasts_btn.connect_toggled(clone!(
+            #[weak]
+            stack,
+            move |btn| {
+                if btn.is_active() {
+                    stack.set_visible_child_name("podcasts");
+                }
+            }
+        ));
+
         let playlist_view = win.get_playlist_view();
         let playlists = library.playlists();
         let recent_playlists_model = gtk::SliceListModel::new(
@@ -389,6 +413,8 @@ impl Sidebar {
             &self.imp().albums_btn.get(),
             &self.imp().artists_btn.get(),
             &self.imp().folders_btn.get(),
+            &self.imp().radio_btn.get(),
+            &self.imp().podcasts_btn.get(),
             &self.imp().playlists_btn.get(),
             &self.imp().dyn_playlists_btn.get(),
         ] {
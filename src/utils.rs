@@ -58,6 +58,24 @@ pub fn format_secs_as_duration(seconds: f64) -> String {
     }
 }
 
+/// Parse a duration typed as seconds, mm:ss or hh:mm:ss (seconds may have a fractional part)
+/// back into seconds.
+pub fn parse_duration_secs(text: &str) -> Option<f64> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let mut res: f64 = 0.0;
+    for (idx, part) in text.rsplit(':').enumerate() {
+        if idx > 2 {
+            return None;
+        }
+        let val = part.trim().parse::<f64>().ok().filter(|val| *val >= 0.0)?;
+        res += val * 60f64.powi(idx as i32);
+    }
+    Some(res)
+}
+
 pub fn format_bitrate(bitrate_kbps: u32) -> String {
     if bitrate_kbps < 5000 {
         format!("{bitrate_kbps}kbps")
@@ -23,7 +23,7 @@ use crate::{
     client::{ClientError, ClientState, ConnectionState},
     common::{Album, Artist, INode, ThemeSelector, blend_mode::*, paintables::FadePaintable},
     library::{
-        AlbumView, ArtistContentView, ArtistView, DynamicPlaylistView, FolderView, PlaylistView, RecentView
+        AlbumView, ArtistContentView, ArtistView, DynamicPlaylistView, FolderView, PlaylistView, PodcastView, RadioView, RecentView
     },
     player::{Player, PlayerBar, QueueView},
     sidebar::Sidebar,
@@ -140,6 +140,10 @@ mod imp {
         #[template_child]
         pub playlist_view: TemplateChild<PlaylistView>,
         #[template_child]
+        pub radio_view: TemplateChild<RadioView>,
+        #[template_child]
+        pub podcast_view: TemplateChild<PodcastView>,
+        #[template_child]
         pub queue_view: TemplateChild<QueueView>,
 
         #[template_child]
@@ -370,6 +374,8 @@ mod imp {
                 self.folder_view.upcast_ref::<gtk::Widget>(),
                 self.playlist_view.upcast_ref::<gtk::Widget>(),
                 self.dyn_playlist_view.upcast_ref::<gtk::Widget>(),
+                self.radio_view.upcast_ref::<gtk::Widget>(),
+                self.podcast_view.upcast_ref::<gtk::Widget>(),
                 self.queue_view.upcast_ref::<gtk::Widget>()
             ].iter().for_each(clone!(
                 #[weak]
@@ -885,6 +891,18 @@ impl EuphonicaWindow {
             &app.get_client().get_client_state(),
             &win,
         );
+        win.imp().radio_view.setup(
+            app.get_library(),
+            app.get_cache(),
+            app.get_player(),
+            &app.get_client().get_client_state(),
+            &win,
+        );
+        win.imp().podcast_view.setup(
+            app.get_library(),
+            app.get_player(),
+            &win,
+        );
         win.imp().sidebar.setup(&win, &app);
         win.imp().player_bar.setup(app.get_player());
 
@@ -977,6 +995,17 @@ impl EuphonicaWindow {
                 }
             ),
         );
+        win.imp().artist_view.get_content_view().connect_closure(
+            "artist-clicked",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                win,
+                move |_: ArtistContentView, artist: Artist| {
+                    this.goto_artist(&artist);
+                }
+            ),
+        );
 
         win.bind_state();
         win.setup_signals();
@@ -1173,6 +1202,9 @@ impl EuphonicaWindow {
             ClientError::Queuing => {
                 self.send_simple_toast("Some songs could not be queued", 3);
             }
+            ClientError::HandOff => {
+                self.send_simple_toast("Could not hand playback off to that server", 3);
+            }
             // _ => {}
         }
     }